@@ -0,0 +1,13 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protoc comes with the build, so nothing has to be installed to build
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    let well_known_types = protoc_bin_vendored::include_path()?;
+
+    tonic_build::configure().compile(
+        &["proto/shop.proto"],
+        &["proto".into(), well_known_types],
+    )?;
+    // `sqlx::migrate!` embeds the migrations, new ones have to rebuild the crate
+    println!("cargo:rerun-if-changed=migrations");
+    Ok(())
+}
@@ -0,0 +1,8 @@
+-- Add down migration script here
+alter table products_in_orders drop column unit_price;
+
+alter table products
+	alter column price type int using (price).cents::int;
+
+drop type money_amount;
+drop type currency;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+create type currency as enum ('PLN', 'EUR', 'USD', 'GBP');
+
+create type money_amount as (
+	cents bigint,
+	currency currency
+);
+
+-- existing prices were already stored as integer cents
+alter table products
+	alter column price type money_amount using row(price::bigint, 'PLN')::money_amount;
+
+alter table products_in_orders add column unit_price money_amount;
+
+update products_in_orders
+set unit_price = products.price
+from products
+where products.id = products_in_orders.product_id;
+
+alter table products_in_orders alter column unit_price set not null;
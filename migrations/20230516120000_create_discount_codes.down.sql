@@ -0,0 +1,8 @@
+-- Add down migration script here
+alter table orders
+	drop column discount,
+	drop column discount_code_id;
+
+drop table if exists discount_codes_products;
+drop table if exists discount_codes;
+drop type discount_kind;
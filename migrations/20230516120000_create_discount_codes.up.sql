@@ -0,0 +1,30 @@
+-- Add up migration script here
+create type discount_kind as enum ('percentage', 'fixed');
+
+create table if not exists discount_codes (
+	id serial primary key,
+	code text not null unique,
+	kind discount_kind not null,
+	percent int check (percent between 1 and 100),
+	amount money_amount,
+	valid_from timestamp,
+	valid_until timestamp,
+	max_uses int,
+	max_uses_per_customer int,
+	min_order_value money_amount,
+	check (
+		(kind = 'percentage' and percent is not null)
+		or (kind = 'fixed' and amount is not null)
+	)
+);
+
+-- discount codes without any rows here apply to every product
+create table if not exists discount_codes_products (
+	discount_code_id int references discount_codes(id) on delete cascade,
+	product_id int references products(id),
+	primary key(discount_code_id, product_id)
+);
+
+alter table orders
+	add column discount_code_id int references discount_codes(id),
+	add column discount money_amount;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+drop table if exists order_tax_lines;
+drop table if exists tax_rates;
+alter table customers drop column region;
+alter table products drop column tax_category;
+drop table if exists tax_categories;
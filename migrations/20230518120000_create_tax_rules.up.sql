@@ -0,0 +1,34 @@
+-- Add up migration script here
+create table if not exists tax_categories (
+	code text primary key,
+	name text not null
+);
+
+insert into tax_categories (code, name) values
+	('standard', 'Standard rate'),
+	('reduced', 'Reduced rate'),
+	('exempt', 'Tax exempt');
+
+alter table products
+	add column tax_category text not null default 'standard' references tax_categories(code);
+
+alter table customers add column region text not null default 'PL';
+
+-- rates are stored in basis points, 2300 is 23%
+create table if not exists tax_rates (
+	id serial primary key,
+	region text not null,
+	tax_category text not null references tax_categories(code),
+	rate_basis_points int not null check (rate_basis_points >= 0),
+	unique (region, tax_category)
+);
+
+create table if not exists order_tax_lines (
+	order_id int references orders(id),
+	tax_category text not null,
+	region text not null,
+	rate_basis_points int not null,
+	taxable_amount money_amount not null,
+	tax money_amount not null,
+	primary key(order_id, tax_category)
+);
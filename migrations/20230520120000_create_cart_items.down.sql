@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table if exists cart_items;
+alter table users drop column customer_id;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+alter table users add column customer_id int references customers(id);
+
+create table if not exists cart_items (
+	customer_id int references customers(id),
+	product_id int references products(id),
+	quantity int not null check (quantity > 0),
+	added_at timestamp not null default now(),
+	primary key(customer_id, product_id)
+);
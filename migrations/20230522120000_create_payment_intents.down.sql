@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table if exists payment_intents;
+drop type payment_status;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+create type payment_status as enum ('pending', 'succeeded', 'failed', 'refunded');
+
+create table if not exists payment_intents (
+	id serial primary key,
+	order_id int not null references orders(id),
+	provider text not null,
+	provider_reference text not null,
+	amount money_amount not null,
+	status payment_status not null default 'pending',
+	created_at timestamp not null default now(),
+	updated_at timestamp not null default now(),
+	unique (provider, provider_reference)
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table if exists refund_lines;
+drop table if exists refunds;
+alter table products_in_orders drop column refunded_quantity;
+alter table products drop column stock;
@@ -0,0 +1,24 @@
+-- Add up migration script here
+-- products without stock are not tracked and can always be ordered
+alter table products add column stock int check (stock >= 0);
+
+alter table products_in_orders
+	add column refunded_quantity int not null default 0 check (refunded_quantity >= 0);
+
+create table if not exists refunds (
+	id serial primary key,
+	order_id int not null references orders(id),
+	payment_intent_id int references payment_intents(id),
+	amount money_amount not null,
+	reason text not null,
+	restocked boolean not null,
+	created_at timestamp not null default now()
+);
+
+create table if not exists refund_lines (
+	refund_id int references refunds(id),
+	product_id int references products(id),
+	quantity int not null check (quantity > 0),
+	amount money_amount not null,
+	primary key(refund_id, product_id)
+);
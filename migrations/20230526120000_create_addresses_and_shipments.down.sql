@@ -0,0 +1,6 @@
+-- Add down migration script here
+drop table if exists shipment_lines;
+drop table if exists shipments;
+drop table if exists order_shipping_addresses;
+drop table if exists customer_addresses;
+drop type if exists address_kind;
@@ -0,0 +1,50 @@
+-- Add up migration script here
+create type address_kind as enum ('billing', 'shipping');
+
+create table if not exists customer_addresses (
+	id serial primary key,
+	customer_id int not null references customers(id),
+	kind address_kind not null,
+	recipient text not null,
+	line1 text not null,
+	line2 text,
+	city text not null,
+	postal_code text not null,
+	country text not null,
+	is_default boolean not null default false
+);
+
+-- at most one default address of every kind per customer
+create unique index customer_addresses_default
+	on customer_addresses (customer_id, kind) where is_default;
+
+-- the free text addresses become the default shipping addresses
+insert into customer_addresses (customer_id, kind, recipient, line1, city, postal_code, country, is_default)
+	select id, 'shipping', name, address, '', '', region, true from customers;
+
+-- copy of the address at the time of ordering, later edits do not move orders
+create table if not exists order_shipping_addresses (
+	order_id int primary key references orders(id),
+	recipient text not null,
+	line1 text not null,
+	line2 text,
+	city text not null,
+	postal_code text not null,
+	country text not null
+);
+
+create table if not exists shipments (
+	id serial primary key,
+	order_id int not null references orders(id),
+	carrier text not null,
+	tracking_number text not null,
+	shipped_at timestamp not null default now(),
+	delivered_at timestamp
+);
+
+create table if not exists shipment_lines (
+	shipment_id int references shipments(id),
+	product_id int references products(id),
+	quantity int not null check (quantity > 0),
+	primary key(shipment_id, product_id)
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+drop index if exists products_name_trgm;
+drop index if exists products_search_vector;
+alter table products drop column search_vector;
+drop function if exists product_search_vector;
+alter table products drop column tags, drop column description;
+drop extension if exists pg_trgm;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+create extension if not exists pg_trgm;
+
+alter table products
+	add column description text not null default '',
+	add column tags text[] not null default '{}';
+
+-- array_to_string is only stable in general, but it is immutable for text arrays
+create or replace function product_search_vector(name text, description text, tags text[])
+	returns tsvector language sql immutable as $$
+	select setweight(to_tsvector('simple', name), 'A')
+		|| setweight(to_tsvector('simple', array_to_string(tags, ' ')), 'B')
+		|| setweight(to_tsvector('simple', description), 'C')
+$$;
+
+alter table products add column search_vector tsvector
+	generated always as (product_search_vector(name, description, tags)) stored;
+
+create index products_search_vector on products using gin (search_vector);
+-- typo tolerant matching of names
+create index products_name_trgm on products using gin (name gin_trgm_ops);
@@ -0,0 +1,28 @@
+-- Add down migration script here
+-- lines of variants other than the default one cannot be represented anymore
+delete from shipment_lines where variant_id not in (select id from product_variants where is_default);
+alter table shipment_lines drop constraint shipment_lines_pkey, add primary key (shipment_id, product_id);
+alter table shipment_lines drop column variant_id;
+
+delete from refund_lines where variant_id not in (select id from product_variants where is_default);
+alter table refund_lines drop constraint refund_lines_pkey, add primary key (refund_id, product_id);
+alter table refund_lines drop column variant_id;
+
+delete from cart_items where variant_id not in (select id from product_variants where is_default);
+alter table cart_items drop constraint cart_items_pkey, add primary key (customer_id, product_id);
+alter table cart_items drop column variant_id;
+
+delete from products_in_orders where variant_id not in (select id from product_variants where is_default);
+alter table products_in_orders drop constraint products_in_orders_pkey, add primary key (order_id, product_id);
+alter table products_in_orders drop column variant_id;
+
+alter table products add column stock int check (stock >= 0);
+update products set stock = product_variants.stock from product_variants
+	where product_variants.product_id = products.id and product_variants.is_default;
+
+drop table if exists variant_attributes;
+drop table if exists product_variants;
+drop table if exists attributes;
+drop type if exists attribute_kind;
+alter table products drop column category_id;
+drop table if exists categories;
@@ -0,0 +1,75 @@
+-- Add up migration script here
+create table if not exists categories (
+	id serial primary key,
+	name text not null,
+	parent_id int references categories(id)
+);
+
+alter table products add column category_id int references categories(id);
+
+create type attribute_kind as enum ('text', 'number', 'boolean');
+
+create table if not exists attributes (
+	id serial primary key,
+	code text not null unique,
+	name text not null,
+	kind attribute_kind not null
+);
+
+-- variants without a price of their own sell at the price of their product
+create table if not exists product_variants (
+	id serial primary key,
+	product_id int not null references products(id),
+	sku text not null unique,
+	price money_amount,
+	stock int check (stock >= 0),
+	available boolean not null default true,
+	is_default boolean not null default false
+);
+
+-- the default variant is what gets ordered when only the product is named
+create unique index product_variants_default on product_variants (product_id) where is_default;
+
+-- values are stored as text and checked against the kind of the attribute
+create table if not exists variant_attributes (
+	variant_id int references product_variants(id),
+	attribute_id int references attributes(id),
+	value text not null,
+	primary key(variant_id, attribute_id)
+);
+
+insert into product_variants (product_id, sku, stock, is_default)
+	select id, 'P-' || id, stock, true from products;
+alter table products drop column stock;
+
+alter table products_in_orders add column variant_id int references product_variants(id);
+update products_in_orders set variant_id = product_variants.id from product_variants
+	where product_variants.product_id = products_in_orders.product_id and product_variants.is_default;
+alter table products_in_orders
+	alter column variant_id set not null,
+	drop constraint products_in_orders_pkey,
+	add primary key (order_id, variant_id);
+
+alter table cart_items add column variant_id int references product_variants(id);
+update cart_items set variant_id = product_variants.id from product_variants
+	where product_variants.product_id = cart_items.product_id and product_variants.is_default;
+alter table cart_items
+	alter column variant_id set not null,
+	drop constraint cart_items_pkey,
+	add primary key (customer_id, variant_id);
+
+alter table refund_lines add column variant_id int references product_variants(id);
+update refund_lines set variant_id = product_variants.id from product_variants
+	where product_variants.product_id = refund_lines.product_id and product_variants.is_default;
+alter table refund_lines
+	alter column variant_id set not null,
+	drop constraint refund_lines_pkey,
+	add primary key (refund_id, variant_id);
+
+alter table shipment_lines add column variant_id int references product_variants(id);
+update shipment_lines set variant_id = product_variants.id from product_variants
+	where product_variants.product_id = shipment_lines.product_id and product_variants.is_default;
+alter table shipment_lines
+	alter column variant_id set not null,
+	drop constraint shipment_lines_pkey,
+	add primary key (shipment_id, variant_id);
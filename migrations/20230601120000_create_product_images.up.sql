@@ -0,0 +1,13 @@
+create table product_images (
+	id serial primary key,
+	product_id int not null references products(id),
+	blob_key text not null unique,
+	thumbnail_key text not null unique,
+	content_type text not null,
+	width int not null,
+	height int not null,
+	position int not null default 0,
+	created_at timestamp not null default now()
+);
+
+create index product_images_product_id_idx on product_images (product_id, position, id);
@@ -0,0 +1,3 @@
+alter table orders drop column deleted_at;
+alter table customers drop column deleted_at;
+alter table products drop column deleted_at;
@@ -0,0 +1,7 @@
+alter table products add column deleted_at timestamp;
+alter table customers add column deleted_at timestamp;
+alter table orders add column deleted_at timestamp;
+
+create index products_not_deleted_idx on products (id) where deleted_at is null;
+create index customers_not_deleted_idx on customers (id) where deleted_at is null;
+create index orders_not_deleted_idx on orders (id) where deleted_at is null;
@@ -0,0 +1,8 @@
+drop trigger orders_bump_version on orders;
+drop trigger customers_bump_version on customers;
+drop trigger products_bump_version on products;
+drop function bump_version;
+
+alter table orders drop column version;
+alter table customers drop column version;
+alter table products drop column version;
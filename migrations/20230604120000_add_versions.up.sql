@@ -0,0 +1,18 @@
+alter table products add column version integer not null default 1;
+alter table customers add column version integer not null default 1;
+alter table orders add column version integer not null default 1;
+
+-- every write makes a new version, so ETags change along with the rows
+create function bump_version() returns trigger as $$
+begin
+    new.version := old.version + 1;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger products_bump_version before update on products
+    for each row execute function bump_version();
+create trigger customers_bump_version before update on customers
+    for each row execute function bump_version();
+create trigger orders_bump_version before update on orders
+    for each row execute function bump_version();
@@ -0,0 +1,2 @@
+drop trigger orders_notify_event on orders;
+drop function notify_order_event;
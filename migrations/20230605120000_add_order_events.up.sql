@@ -0,0 +1,25 @@
+-- notifications are sent on commit, to every server listening on the channel
+create function notify_order_event() returns trigger as $$
+begin
+    if tg_op = 'INSERT' then
+        perform pg_notify('order_events', json_build_object(
+            'event', 'order_created',
+            'order_id', new.id,
+            'customer_id', new.customer_id,
+            'status', new.status
+        )::text);
+    elsif new.status is distinct from old.status then
+        perform pg_notify('order_events', json_build_object(
+            'event', 'order_status_changed',
+            'order_id', new.id,
+            'customer_id', new.customer_id,
+            'status', new.status,
+            'previous_status', old.status
+        )::text);
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger orders_notify_event after insert or update on orders
+    for each row execute function notify_order_event();
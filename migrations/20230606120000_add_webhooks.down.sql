@@ -0,0 +1,9 @@
+drop trigger products_queue_webhook_event on products;
+drop function queue_product_webhook_event;
+drop trigger orders_queue_webhook_event on orders;
+drop function queue_order_webhook_event;
+drop table if exists webhook_deliveries;
+drop table if exists webhook_events;
+drop table if exists webhook_subscriptions;
+drop type webhook_delivery_status;
+drop type webhook_event_type;
@@ -0,0 +1,78 @@
+create type webhook_event_type as enum (
+	'order.created', 'order.status_changed', 'product.created', 'product.updated', 'product.deleted'
+);
+create type webhook_delivery_status as enum ('pending', 'delivered', 'dead');
+
+create table if not exists webhook_subscriptions (
+	id serial primary key,
+	url text not null,
+	event_types webhook_event_type[] not null,
+	secret text not null,
+	active boolean not null default true,
+	created_at timestamp not null default now()
+);
+
+-- outbox of events, written by triggers in the same transaction as the change
+-- they describe, so no event is lost or sent for a change that was rolled back
+create table if not exists webhook_events (
+	id serial primary key,
+	event_type webhook_event_type not null,
+	payload jsonb not null,
+	created_at timestamp not null default now(),
+	-- set once a delivery to every matching subscription was queued
+	dispatched_at timestamp
+);
+create index webhook_events_undispatched on webhook_events (id) where dispatched_at is null;
+
+create table if not exists webhook_deliveries (
+	id serial primary key,
+	subscription_id int not null references webhook_subscriptions(id) on delete cascade,
+	event_id int not null references webhook_events(id) on delete cascade,
+	status webhook_delivery_status not null default 'pending',
+	attempts int not null default 0,
+	next_attempt_at timestamp not null default now(),
+	last_attempt_at timestamp,
+	response_status int,
+	last_error text,
+	created_at timestamp not null default now(),
+	unique (subscription_id, event_id)
+);
+create index webhook_deliveries_due on webhook_deliveries (next_attempt_at) where status = 'pending';
+
+create function queue_order_webhook_event() returns trigger as $$
+begin
+	if tg_op = 'INSERT' then
+		insert into webhook_events (event_type, payload) values ('order.created', to_jsonb(new));
+	elsif new.status is distinct from old.status then
+		insert into webhook_events (event_type, payload)
+		values ('order.status_changed', to_jsonb(new) || jsonb_build_object('previous_status', old.status));
+	end if;
+	return null;
+end;
+$$ language plpgsql;
+
+create trigger orders_queue_webhook_event after insert or update on orders
+	for each row execute function queue_order_webhook_event();
+
+-- soft deletes count as deletes, the search vector is left out of payloads
+create function queue_product_webhook_event() returns trigger as $$
+begin
+	if tg_op = 'INSERT' then
+		insert into webhook_events (event_type, payload)
+		values ('product.created', to_jsonb(new) - 'search_vector');
+	elsif tg_op = 'DELETE' then
+		insert into webhook_events (event_type, payload)
+		values ('product.deleted', to_jsonb(old) - 'search_vector');
+	elsif new.deleted_at is not null and old.deleted_at is null then
+		insert into webhook_events (event_type, payload)
+		values ('product.deleted', to_jsonb(new) - 'search_vector');
+	else
+		insert into webhook_events (event_type, payload)
+		values ('product.updated', to_jsonb(new) - 'search_vector');
+	end if;
+	return null;
+end;
+$$ language plpgsql;
+
+create trigger products_queue_webhook_event after insert or update or delete on products
+	for each row execute function queue_product_webhook_event();
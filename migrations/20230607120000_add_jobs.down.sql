@@ -0,0 +1,4 @@
+drop trigger webhook_events_queue_dispatch on webhook_events;
+drop function queue_webhook_dispatch;
+drop table if exists jobs;
+drop type job_status;
@@ -0,0 +1,38 @@
+create type job_status as enum ('queued', 'running', 'succeeded', 'failed');
+
+-- background jobs, queued in the same transaction as the change that calls
+-- for them and run by the workers of every instance
+create table if not exists jobs (
+	id serial primary key,
+	kind text not null,
+	payload jsonb not null,
+	status job_status not null default 'queued',
+	attempts int not null default 0,
+	run_at timestamp not null default now(),
+	-- running jobs whose worker did not finish them by then are run again
+	locked_until timestamp,
+	last_error text,
+	created_at timestamp not null default now(),
+	finished_at timestamp
+);
+create index jobs_due on jobs (run_at) where status in ('queued', 'running');
+
+-- webhook events are dispatched by a job queued together with the event
+create function queue_webhook_dispatch() returns trigger as $$
+begin
+	insert into jobs (kind, payload)
+	values ('dispatch_webhook_event', jsonb_build_object('event_id', new.id));
+	return null;
+end;
+$$ language plpgsql;
+
+create trigger webhook_events_queue_dispatch after insert on webhook_events
+	for each row execute function queue_webhook_dispatch();
+
+insert into jobs (kind, payload)
+select 'dispatch_webhook_event', jsonb_build_object('event_id', id)
+from webhook_events where dispatched_at is null;
+
+insert into jobs (kind, payload)
+select 'deliver_webhook', jsonb_build_object('delivery_id', id)
+from webhook_deliveries where status = 'pending';
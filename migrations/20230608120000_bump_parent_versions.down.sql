@@ -0,0 +1,8 @@
+drop trigger order_tax_lines_bump_version on order_tax_lines;
+drop trigger order_shipping_addresses_bump_version on order_shipping_addresses;
+drop trigger products_in_orders_bump_version on products_in_orders;
+drop trigger product_images_bump_version on product_images;
+drop trigger variant_attributes_bump_version on variant_attributes;
+drop trigger product_variants_bump_version on product_variants;
+drop function bump_variant_product_version;
+drop function bump_parent_version;
@@ -0,0 +1,61 @@
+-- products and orders are sent together with rows of other tables, so their
+-- ETags have to change along with those rows too. A parent already written by
+-- the same transaction has a new version that covers the change.
+create function bump_parent_version() returns trigger as $$
+declare
+    parent_id int;
+begin
+    if tg_op = 'UPDATE' and new is not distinct from old then
+        return null;
+    end if;
+    if tg_op = 'DELETE' then
+        parent_id := to_jsonb(old) ->> tg_argv[1];
+    else
+        parent_id := to_jsonb(new) ->> tg_argv[1];
+    end if;
+    execute format(
+        'update %I set version = version + 1 where id = $1 and xmin <> pg_current_xact_id()::xid',
+        tg_argv[0]
+    ) using parent_id;
+    return null;
+end;
+$$ language plpgsql;
+
+-- attributes belong to products through their variants
+create function bump_variant_product_version() returns trigger as $$
+declare
+    changed_variant_id int;
+begin
+    if tg_op = 'UPDATE' and new is not distinct from old then
+        return null;
+    end if;
+    if tg_op = 'DELETE' then
+        changed_variant_id := old.variant_id;
+    else
+        changed_variant_id := new.variant_id;
+    end if;
+    update products set version = version + 1
+    where id = (select product_id from product_variants where id = changed_variant_id)
+        and xmin <> pg_current_xact_id()::xid;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger product_variants_bump_version
+    after insert or update or delete on product_variants
+    for each row execute function bump_parent_version('products', 'product_id');
+create trigger variant_attributes_bump_version
+    after insert or update or delete on variant_attributes
+    for each row execute function bump_variant_product_version();
+create trigger product_images_bump_version
+    after insert or update or delete on product_images
+    for each row execute function bump_parent_version('products', 'product_id');
+create trigger products_in_orders_bump_version
+    after insert or update or delete on products_in_orders
+    for each row execute function bump_parent_version('orders', 'order_id');
+create trigger order_shipping_addresses_bump_version
+    after insert or update or delete on order_shipping_addresses
+    for each row execute function bump_parent_version('orders', 'order_id');
+create trigger order_tax_lines_bump_version
+    after insert or update or delete on order_tax_lines
+    for each row execute function bump_parent_version('orders', 'order_id');
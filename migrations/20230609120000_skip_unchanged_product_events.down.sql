@@ -0,0 +1,18 @@
+create or replace function queue_product_webhook_event() returns trigger as $$
+begin
+	if tg_op = 'INSERT' then
+		insert into webhook_events (event_type, payload)
+		values ('product.created', to_jsonb(new) - 'search_vector');
+	elsif tg_op = 'DELETE' then
+		insert into webhook_events (event_type, payload)
+		values ('product.deleted', to_jsonb(old) - 'search_vector');
+	elsif new.deleted_at is not null and old.deleted_at is null then
+		insert into webhook_events (event_type, payload)
+		values ('product.deleted', to_jsonb(new) - 'search_vector');
+	else
+		insert into webhook_events (event_type, payload)
+		values ('product.updated', to_jsonb(new) - 'search_vector');
+	end if;
+	return null;
+end;
+$$ language plpgsql;
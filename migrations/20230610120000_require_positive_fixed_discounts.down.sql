@@ -0,0 +1 @@
+alter table discount_codes drop constraint discount_codes_fixed_amount_check;
@@ -0,0 +1,3 @@
+-- a fixed discount of nothing or less would add to the price of the order
+alter table discount_codes
+	add constraint discount_codes_fixed_amount_check check (kind <> 'fixed' or (amount).cents > 0);
@@ -1,16 +1,30 @@
 use axum::{
     error_handling::HandleErrorLayer,
-    http::StatusCode,
-    routing::{get, patch, post, put},
+    extract::DefaultBodyLimit,
+    handler::Handler,
+    http::{Method, StatusCode},
+    routing::{delete, get, on, patch, post, put, MethodFilter, MethodRouter},
     Router,
     middleware,
+    ServiceExt,
 };
 use color_eyre::Result;
 use std::env;
 use std::time::Duration;
-use tower::{BoxError, ServiceBuilder};
+use tower::{BoxError, Layer, ServiceBuilder};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{controllers::*, db_actions::get_pool, middleware::*};
+use crate::{
+    controllers::*,
+    db_actions::get_pool,
+    graphql::GRAPHQL_PATH,
+    grpc,
+    middleware::*,
+    models::{Roles, MAX_IMPORT_BYTES},
+    openapi::{ApiDoc, DOCS_PATH, OPENAPI_PATH},
+    services::{DeliverWebhook, DispatchWebhookEvent, JobWorker, OrderEventService},
+};
 
 pub type DbPool = sqlx::PgPool;
 pub struct App;
@@ -28,25 +42,56 @@ impl App {
 
     pub async fn start_app(self) -> Result<()> {
         let pool = get_pool().await?;
-        let router = self.build_router().with_state(pool);
+        let router = self
+            .build_router()
+            .with_state(pool.clone())
+            .merge(grpc::routes(pool.clone()));
+        let app = middleware::from_fn(middleware_negotiate_version).layer(router);
         let addr = env::var("SERVER_ADDR")?;
 
-        axum::Server::bind(&addr.parse()?)
-            .serve(router.into_make_service())
-            .await?;
+        let server = axum::Server::bind(&addr.parse()?).serve(app.into_make_service());
+        // runs for as long as the server does, stopping it when it fails
+        tokio::try_join!(
+            async { server.await.map_err(color_eyre::Report::from) },
+            OrderEventService::listen(pool.clone()),
+            Self::job_worker(pool).run(),
+        )?;
 
         Ok(())
     }
 
+    /// Worker running every type of background job there is.
+    fn job_worker(pool: DbPool) -> JobWorker {
+        JobWorker::new(pool)
+            .register::<DispatchWebhookEvent>()
+            .register::<DeliverWebhook>()
+    }
+
     fn build_router(self) -> Router<DbPool> {
-        let api_routes = Router::new()
+        let deprecated_routes = Router::new()
             .nest("/product", Routes::product_routes())
+            .nest("/category", Routes::category_routes())
             .nest("/order", Routes::order_routes())
             .nest("/customer", Routes::customer_routes())
+            .nest("/cart", Routes::cart_routes())
+            .nest("/payment", Routes::payment_routes())
             .nest("/user", Routes::user_routes())
-            .nest("/admin", Routes::admin_routes());
+            .nest("/admin", Routes::admin_routes())
+            .layer(middleware::from_fn(middleware_deprecated));
+        let api_routes = Router::new()
+            .nest("/v1", V1Routes::routes())
+            .merge(deprecated_routes)
+            // stored image urls point here, so it stays unversioned
+            .route("/media/*key", get(image_controller::get_media));
 
-        self.add_error_handler(Router::new().nest("/api", api_routes))
+        let router = Router::new()
+            .nest("/api", api_routes)
+            .route(
+                GRAPHQL_PATH,
+                get(graphql_controller::graphiql).post(graphql_controller::graphql),
+            )
+            .merge(SwaggerUi::new(DOCS_PATH).url(OPENAPI_PATH, ApiDoc::openapi()));
+        self.add_error_handler(router)
     }
 
     fn add_error_handler(self, router: Router<DbPool>) -> Router<DbPool> {
@@ -74,40 +119,394 @@ struct Routes;
 impl Routes {
     fn product_routes() -> Router<DbPool> {
         Router::new()
-            .route("/", get(ProductController::get_product))
-            .route("/all", get(ProductController::get_all_products))
+            .route("/", get(product_controller::get_product))
+            .route("/all", get(product_controller::get_all_products))
+            .route("/search", get(product_controller::search_products))
+            .route("/variants", get(variant_controller::get_product_variants))
+            .route("/images", get(image_controller::get_product_images))
+    }
+
+    fn category_routes() -> Router<DbPool> {
+        Router::new()
+            .route("/", get(category_controller::get_category))
+            .route("/all", get(category_controller::get_all_categories))
     }
 
     fn customer_routes() -> Router<DbPool> {
         Router::new()
-            .route("/", get(CustomerController::get_customer))
-            .route("/all", get(CustomerController::get_all_customers))
+            .route("/", get(customer_controller::get_customer))
+            .route("/all", get(customer_controller::get_all_customers))
+            .route("/address", get(customer_controller::get_addresses))
+            .route("/address", post(customer_controller::create_address))
+            .route("/address", put(customer_controller::update_address))
+            .route("/address", delete(customer_controller::delete_address))
             .route_layer(middleware::from_fn(middleware_require_customer_role))
     }
 
+    fn cart_routes() -> Router<DbPool> {
+        Router::new()
+            .route("/", get(cart_controller::get_cart))
+            .route("/", post(cart_controller::add_item))
+            .route("/", put(cart_controller::update_item))
+            .route("/", delete(cart_controller::remove_item))
+            .route("/checkout", post(cart_controller::checkout))
+            .route_layer(middleware::from_fn(middleware_require_customer_role))
+    }
+
+    fn payment_routes() -> Router<DbPool> {
+        Router::new()
+            .route("/", post(payment_controller::create_payment_intent))
+            .route("/", get(payment_controller::get_payment_intent))
+            .route_layer(middleware::from_fn(middleware_require_customer_role))
+            .route("/webhook", post(payment_controller::webhook))
+    }
+
     fn order_routes() -> Router<DbPool> {
         Router::new()
-            .route("/", post(OrderController::create_order))
-            .route("/", get(OrderController::get_order))
-            .route("/all", get(OrderController::get_all_orders))
+            .route("/cancel", post(order_controller::cancel_order))
+            .route("/shipments", get(shipment_controller::get_own_order_shipments))
+            .route_layer(middleware::from_fn(middleware_require_customer_role))
+            .route("/stream", get(order_event_controller::stream_order_events))
+            .route("/ws", get(order_event_controller::order_events_socket))
+            .route("/", post(order_controller::create_order))
+            .route("/", get(order_controller::get_order))
+            .route("/all", get(order_controller::get_all_orders))
     }
 
     fn admin_routes() -> Router<DbPool> {
         Router::new()
-            .route("/product", post(ProductController::create_product))
-            .route("/product/", put(ProductController::update_product))
-            .route("/product/", patch(ProductController::partial_update_product))
-            .route("/customer", post(CustomerController::create_customer))
-            .route("/customer/", put(CustomerController::update_customer))
-            .route("/customer/", patch(CustomerController::partial_update_customer))
-            .route("/order/", put(OrderController::update_order))
-            .route("/order/", patch(OrderController::partial_update_order))
+            .route("/product", post(product_controller::create_product))
+            .route("/product/", put(product_controller::update_product))
+            .route("/product/", patch(product_controller::partial_update_product))
+            .route("/product/", delete(product_controller::delete_product))
+            .route("/product/restore", post(product_controller::restore_product))
+            .route(
+                "/product/import",
+                post(product_controller::import_products)
+                    .layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+            )
+            .route("/product/export", get(product_controller::export_products))
+            .route(
+                "/product/image",
+                post(image_controller::upload_product_images)
+                    .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+            )
+            .route("/product/image", delete(image_controller::delete_product_image))
+            .route("/product/variant", post(variant_controller::create_variant))
+            .route("/product/variant", put(variant_controller::update_variant))
+            .route("/product/variant", delete(variant_controller::delete_variant))
+            .route("/attribute", post(variant_controller::create_attribute))
+            .route("/attribute/all", get(variant_controller::get_all_attributes))
+            .route("/category", post(category_controller::create_category))
+            .route("/category/", put(category_controller::update_category))
+            .route("/category/", delete(category_controller::delete_category))
+            .route("/customer", post(customer_controller::create_customer))
+            .route("/customer/", put(customer_controller::update_customer))
+            .route("/customer/", patch(customer_controller::partial_update_customer))
+            .route("/customer/", delete(customer_controller::delete_customer))
+            .route("/customer/restore", post(customer_controller::restore_customer))
+            .route(
+                "/customer/import",
+                post(customer_controller::import_customers)
+                    .layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+            )
+            .route("/customer/export", get(customer_controller::export_customers))
+            .route("/customer/address", get(customer_controller::get_customer_addresses))
+            .route("/order/", put(order_controller::update_order))
+            .route("/order/", patch(order_controller::partial_update_order))
+            .route("/order/", delete(order_controller::delete_order))
+            .route("/order/restore", post(order_controller::restore_order))
+            .route("/order/cancel", post(order_controller::admin_cancel_order))
+            .route("/order/refund", post(order_controller::refund_order))
+            .route("/order/refunds", get(order_controller::get_order_refunds))
+            .route("/order/shipment", post(shipment_controller::create_shipment))
+            .route("/order/shipments", get(shipment_controller::get_order_shipments))
+            .route("/order/shipment/delivered", post(shipment_controller::mark_delivered))
+            .route("/discount", post(discount_controller::create_discount_code))
+            .route("/discount", get(discount_controller::get_discount_code))
+            .route("/discount/all", get(discount_controller::get_all_discount_codes))
+            .route("/discount/", put(discount_controller::update_discount_code))
+            .route("/discount/", delete(discount_controller::delete_discount_code))
+            .route("/tax/category", post(tax_controller::create_tax_category))
+            .route("/tax/category/all", get(tax_controller::get_all_tax_categories))
+            .route("/tax/rate", post(tax_controller::create_tax_rate))
+            .route("/tax/rate", get(tax_controller::get_tax_rate))
+            .route("/tax/rate/all", get(tax_controller::get_all_tax_rates))
+            .route("/tax/rate/", put(tax_controller::update_tax_rate))
+            .route("/tax/rate/", delete(tax_controller::delete_tax_rate))
             .route_layer(middleware::from_fn(middleware_require_admin_role))
     }
 
     fn user_routes() -> Router<DbPool> {
         Router::new()
-            .route("/authorize", post(UserController::authorize))
-            .route("/register", post(UserController::create_user))
+            .route("/authorize", post(user_controller::authorize))
+            .route("/register", post(user_controller::create_user))
+    }
+}
+
+/// Routes of version 1 of the API, addressing resources by their path and
+/// acting on them with the matching method. Keeps track of the operations it
+/// routes, all of which the OpenAPI document has to describe.
+pub struct V1Routes {
+    router: Router<DbPool>,
+    operations: Vec<(Method, String)>,
+}
+
+impl V1Routes {
+    /// Method and path of every operation of version 1, with path parameters
+    /// written as in OpenAPI, e.g. `/api/v1/products/{id}`.
+    pub fn operations() -> Vec<(Method, String)> {
+        Self::all()
+            .operations
+            .into_iter()
+            .map(|(method, path)| (method, format!("/api/v1{path}")))
+            .collect()
+    }
+
+    fn routes() -> Router<DbPool> {
+        Self::all().router
+    }
+
+    fn new() -> Self {
+        Self {
+            router: Router::new(),
+            operations: Vec::new(),
+        }
+    }
+
+    fn route<H, T>(self, path: &str, method: Method, handler: H) -> Self
+    where
+        H: Handler<T, DbPool>,
+        T: 'static,
+    {
+        let method_router = on(Self::method_filter(&method), handler);
+        self.add(path, method, method_router)
+    }
+
+    fn route_with_body_limit<H, T>(
+        self,
+        path: &str,
+        method: Method,
+        handler: H,
+        limit: usize,
+    ) -> Self
+    where
+        H: Handler<T, DbPool>,
+        T: 'static,
+    {
+        let method_router =
+            on(Self::method_filter(&method), handler).layer(DefaultBodyLimit::max(limit));
+        self.add(path, method, method_router)
+    }
+
+    fn add(mut self, path: &str, method: Method, method_router: MethodRouter<DbPool>) -> Self {
+        self.router = self.router.route(path, method_router);
+        let path = path
+            .split('/')
+            .map(|segment| match segment.strip_prefix([':', '*']) {
+                Some(name) => format!("{{{name}}}"),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        self.operations.push((method, path));
+        self
+    }
+
+    fn method_filter(method: &Method) -> MethodFilter {
+        MethodFilter::try_from(method.clone())
+            .unwrap_or_else(|_| panic!("{method} cannot be routed"))
+    }
+
+    /// Lets only users with `role` use the routes added so far.
+    fn require_role(mut self, role: Roles) -> Self {
+        self.router = match role {
+            Roles::Customer => self
+                .router
+                .route_layer(middleware::from_fn(middleware_require_customer_role)),
+            Roles::Admin => self
+                .router
+                .route_layer(middleware::from_fn(middleware_require_admin_role)),
+        };
+        self
+    }
+
+    fn nest(mut self, prefix: &str, routes: Self) -> Self {
+        self.router = self.router.nest(prefix, routes.router);
+        self.operations
+            .extend(routes.operations.into_iter().map(|(method, path)| {
+                let path = match path.as_str() {
+                    "/" => prefix.to_string(),
+                    _ => format!("{prefix}{path}"),
+                };
+                (method, path)
+            }));
+        self
+    }
+
+    fn all() -> Self {
+        Self::new()
+            .nest("/products", Self::product_routes())
+            .nest("/categories", Self::category_routes())
+            .nest("/orders", Self::order_routes())
+            .nest("/customers", Self::customer_routes())
+            .nest("/addresses", Self::address_routes())
+            .nest("/cart", Self::cart_routes())
+            .nest("/payments", Self::payment_routes())
+            .nest("/users", Self::user_routes())
+            .nest("/admin", Self::admin_routes())
+            .route("/media/*key", Method::GET, image_controller::get_media)
+    }
+
+    fn product_routes() -> Self {
+        Self::new()
+            .route("/", Method::GET, product_controller::get_all_products)
+            .route("/search", Method::GET, product_controller::search_products)
+            .route("/:id", Method::GET, product_controller::get_product)
+            .route("/:id/variants", Method::GET, variant_controller::get_product_variants)
+            .route("/:id/images", Method::GET, image_controller::get_product_images)
+    }
+
+    fn category_routes() -> Self {
+        Self::new()
+            .route("/", Method::GET, category_controller::get_all_categories)
+            .route("/:id", Method::GET, category_controller::get_category)
+    }
+
+    fn customer_routes() -> Self {
+        Self::new()
+            .route("/", Method::GET, customer_controller::get_all_customers)
+            .route("/:id", Method::GET, customer_controller::get_customer)
+            .require_role(Roles::Customer)
+    }
+
+    fn address_routes() -> Self {
+        Self::new()
+            .route("/", Method::GET, customer_controller::get_addresses)
+            .route("/", Method::POST, customer_controller::create_address)
+            .route("/:id", Method::PUT, customer_controller::update_address)
+            .route("/:id", Method::DELETE, customer_controller::delete_address)
+            .require_role(Roles::Customer)
+    }
+
+    fn cart_routes() -> Self {
+        Self::new()
+            .route("/", Method::GET, cart_controller::get_cart)
+            .route("/items", Method::POST, cart_controller::add_item)
+            .route("/items/:id", Method::PUT, cart_controller::update_item)
+            .route("/items/:id", Method::DELETE, cart_controller::remove_item)
+            .route("/checkout", Method::POST, cart_controller::checkout)
+            .require_role(Roles::Customer)
+    }
+
+    fn payment_routes() -> Self {
+        Self::new()
+            .route("/", Method::POST, payment_controller::create_payment_intent)
+            .route("/:id", Method::GET, payment_controller::get_payment_intent)
+            .require_role(Roles::Customer)
+            .route("/webhook", Method::POST, payment_controller::webhook)
+    }
+
+    fn order_routes() -> Self {
+        Self::new()
+            .route("/:id/cancel", Method::POST, order_controller::cancel_order)
+            .route("/:id/shipments", Method::GET, shipment_controller::get_own_order_shipments)
+            .require_role(Roles::Customer)
+            .route("/stream", Method::GET, order_event_controller::stream_order_events)
+            .route("/ws", Method::GET, order_event_controller::order_events_socket)
+            .route("/", Method::GET, order_controller::get_all_orders)
+            .route("/", Method::POST, order_controller::create_order)
+            .route("/:id", Method::GET, order_controller::get_order)
+    }
+
+    fn user_routes() -> Self {
+        Self::new()
+            .route("/", Method::POST, user_controller::create_user)
+            .route("/authorize", Method::POST, user_controller::authorize)
+    }
+
+    fn admin_routes() -> Self {
+        Self::new()
+            .route("/products", Method::POST, product_controller::create_product)
+            .route("/products/:id", Method::PUT, product_controller::update_product)
+            .route("/products/:id", Method::PATCH, product_controller::partial_update_product)
+            .route("/products/:id", Method::DELETE, product_controller::delete_product)
+            .route("/products/:id/restore", Method::POST, product_controller::restore_product)
+            .route_with_body_limit(
+                "/products/import",
+                Method::POST,
+                product_controller::import_products,
+                MAX_IMPORT_BYTES,
+            )
+            .route("/products/export", Method::GET, product_controller::export_products)
+            .route_with_body_limit(
+                "/products/:id/images",
+                Method::POST,
+                image_controller::upload_product_images,
+                MAX_UPLOAD_BYTES,
+            )
+            .route("/images/:id", Method::DELETE, image_controller::delete_product_image)
+            .route("/variants", Method::POST, variant_controller::create_variant)
+            .route("/variants/:id", Method::PUT, variant_controller::update_variant)
+            .route("/variants/:id", Method::DELETE, variant_controller::delete_variant)
+            .route("/attributes", Method::GET, variant_controller::get_all_attributes)
+            .route("/attributes", Method::POST, variant_controller::create_attribute)
+            .route("/categories", Method::POST, category_controller::create_category)
+            .route("/categories/:id", Method::PUT, category_controller::update_category)
+            .route("/categories/:id", Method::DELETE, category_controller::delete_category)
+            .route("/customers", Method::POST, customer_controller::create_customer)
+            .route("/customers/:id", Method::PUT, customer_controller::update_customer)
+            .route("/customers/:id", Method::PATCH, customer_controller::partial_update_customer)
+            .route("/customers/:id", Method::DELETE, customer_controller::delete_customer)
+            .route("/customers/:id/restore", Method::POST, customer_controller::restore_customer)
+            .route_with_body_limit(
+                "/customers/import",
+                Method::POST,
+                customer_controller::import_customers,
+                MAX_IMPORT_BYTES,
+            )
+            .route("/customers/export", Method::GET, customer_controller::export_customers)
+            .route(
+                "/customers/:id/addresses",
+                Method::GET,
+                customer_controller::get_customer_addresses,
+            )
+            .route("/orders/:id", Method::PUT, order_controller::update_order)
+            .route("/orders/:id", Method::PATCH, order_controller::partial_update_order)
+            .route("/orders/:id", Method::DELETE, order_controller::delete_order)
+            .route("/orders/:id/restore", Method::POST, order_controller::restore_order)
+            .route("/orders/:id/cancel", Method::POST, order_controller::admin_cancel_order)
+            .route("/orders/:id/refunds", Method::GET, order_controller::get_order_refunds)
+            .route("/orders/:id/refunds", Method::POST, order_controller::refund_order)
+            .route("/orders/:id/shipments", Method::GET, shipment_controller::get_order_shipments)
+            .route("/orders/:id/shipments", Method::POST, shipment_controller::create_shipment)
+            .route("/shipments/:id/delivered", Method::POST, shipment_controller::mark_delivered)
+            .route("/discounts", Method::GET, discount_controller::get_all_discount_codes)
+            .route("/discounts", Method::POST, discount_controller::create_discount_code)
+            .route("/discounts/:id", Method::GET, discount_controller::get_discount_code)
+            .route("/discounts/:id", Method::PUT, discount_controller::update_discount_code)
+            .route("/discounts/:id", Method::DELETE, discount_controller::delete_discount_code)
+            .route("/tax/categories", Method::GET, tax_controller::get_all_tax_categories)
+            .route("/tax/categories", Method::POST, tax_controller::create_tax_category)
+            .route("/tax/rates", Method::GET, tax_controller::get_all_tax_rates)
+            .route("/tax/rates", Method::POST, tax_controller::create_tax_rate)
+            .route("/tax/rates/:id", Method::GET, tax_controller::get_tax_rate)
+            .route("/tax/rates/:id", Method::PUT, tax_controller::update_tax_rate)
+            .route("/tax/rates/:id", Method::DELETE, tax_controller::delete_tax_rate)
+            .route("/webhooks", Method::GET, webhook_controller::get_all_subscriptions)
+            .route("/webhooks", Method::POST, webhook_controller::create_subscription)
+            .route("/webhooks/:id", Method::GET, webhook_controller::get_subscription)
+            .route("/webhooks/:id", Method::PUT, webhook_controller::update_subscription)
+            .route("/webhooks/:id", Method::DELETE, webhook_controller::delete_subscription)
+            .route("/webhooks/:id/deliveries", Method::GET, webhook_controller::get_deliveries)
+            .route(
+                "/webhooks/deliveries/:id/retry",
+                Method::POST,
+                webhook_controller::retry_delivery,
+            )
+            .route("/jobs", Method::GET, job_controller::get_all_jobs)
+            .route("/jobs/:id", Method::GET, job_controller::get_job)
+            .route("/jobs/:id/retry", Method::POST, job_controller::retry_job)
+            .require_role(Roles::Admin)
     }
 }
@@ -0,0 +1,190 @@
+use crate::models::{CartItemParams, ResourceId};
+use crate::{
+    app::DbPool,
+    models::{CartError, CartItem, CartItemQuantity, CheckoutRequest, Claims},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use super::{customer_controller, order_controller};
+use crate::services::CartService;
+
+/// Gets the cart of the customer
+#[utoipa::path(
+    get,
+    path = "/api/v1/cart",
+    tag = "cart",
+    responses(
+        (status = 200, body = Cart),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Not a customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_cart(
+    State(pool): State<DbPool>,
+    claims: Claims,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = customer_controller::get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        CartService::get_cart(&pool, customer_id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Puts a product into the cart
+#[utoipa::path(
+    post,
+    path = "/api/v1/cart/items",
+    tag = "cart",
+    request_body = CartItem,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid quantity"),
+        (status = 403, description = "Not a customer"),
+        (status = 404, description = "Unknown product or variant"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn add_item(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    Json(item): Json<CartItem>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received cart item: {:?}", item);
+
+    let customer_id = customer_controller::get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        CartService::add_item(&pool, customer_id, item)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                cart_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Changes the quantity of a product in the cart
+///
+/// `id` is the id of the product in the cart, `variant_id` picks one of its
+/// variants, the default one if left out.
+#[utoipa::path(
+    put,
+    path = "/api/v1/cart/items/{id}",
+    tag = "cart",
+    params(("id" = i32, Path, description = "Id of the product"), CartItemParams),
+    request_body = CartItemQuantity,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid quantity"),
+        (status = 403, description = "Not a customer"),
+        (status = 404, description = "Product not in the cart"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_item(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+    Query(CartItemParams { variant_id }): Query<CartItemParams>,
+    Json(CartItemQuantity { quantity }): Json<CartItemQuantity>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let item = CartItem {
+        product_id: id,
+        variant_id,
+        quantity,
+    };
+    let customer_id = customer_controller::get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        CartService::update_item(&pool, customer_id, item)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                cart_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Takes a product out of the cart
+///
+/// `id` is the id of the product to take out of the cart, `variant_id`
+/// limits it to one of its variants.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cart/items/{id}",
+    tag = "cart",
+    params(("id" = i32, Path, description = "Id of the product"), CartItemParams),
+    responses(
+        (status = 200),
+        (status = 403, description = "Not a customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn remove_item(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+    Query(CartItemParams { variant_id }): Query<CartItemParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = customer_controller::get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        CartService::remove_item(&pool, customer_id, id, variant_id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Places an order of everything in the cart
+#[utoipa::path(
+    post,
+    path = "/api/v1/cart/checkout",
+    tag = "cart",
+    request_body = CheckoutRequest,
+    responses(
+        (status = 200, body = i32, description = "Id of the new order"),
+        (status = 400, description = "Empty cart or invalid discount code"),
+        (status = 403, description = "Not a customer"),
+        (status = 409, description = "Out of stock"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn checkout(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    Json(checkout): Json<CheckoutRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = customer_controller::get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        CartService::checkout(&pool, customer_id, checkout)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                cart_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+fn cart_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<CartError>() {
+        Some(CartError::NotInCart(_)) => StatusCode::NOT_FOUND,
+        Some(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        None => order_controller::order_error_status(e),
+    }
+}
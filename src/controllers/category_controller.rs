@@ -0,0 +1,150 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{Category, CategoryError},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::CategoryService;
+
+/// Gets a category
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(("id" = i32, Path, description = "Id of the category")),
+    responses(
+        (status = 200, body = Category),
+        (status = 404, description = "Unknown category"),
+    )
+)]
+pub async fn get_category(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(CategoryService::get_category(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?);
+    Ok(response)
+}
+
+/// Lists categories
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "categories",
+    responses((status = 200, body = [Category]))
+)]
+pub async fn get_all_categories(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(CategoryService::get_all_categories(&pool).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Creates a category
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/categories",
+    tag = "admin",
+    request_body = Category,
+    responses((status = 200, body = i32, description = "Id of the new category")),
+    security(("bearer" = []))
+)]
+pub async fn create_category(
+    State(pool): State<DbPool>,
+    Json(category): Json<Category>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received category: {:?}", category);
+
+    let response = Json(
+        CategoryService::create_category(&pool, category)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::BAD_REQUEST
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a category
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/categories/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the category")),
+    request_body = Category,
+    responses(
+        (status = 200),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 409, description = "Category would be nested in itself"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_category(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(category): Json<Category>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != category.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let response = Json(
+        CategoryService::update_category(&pool, category)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                category_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Deletes an empty category
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/categories/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the category")),
+    responses(
+        (status = 200),
+        (status = 409, description = "Category still has products or subcategories"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_category(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        CategoryService::delete_category(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                category_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// A category cannot become its own ancestor, and categories still
+/// holding products or subcategories are kept.
+fn category_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<CategoryError>() {
+        Some(CategoryError::Cycle(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+        Some(CategoryError::NotEmpty(_)) => StatusCode::CONFLICT,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
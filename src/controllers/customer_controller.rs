@@ -1,120 +1,470 @@
-use crate::models::QueryIdParam;
-use crate::{app::DbPool, models::Customer};
+use crate::models::{
+    etag, DeleteParams, ExportParams, Fields, FieldsQuery, IfMatch, IfNoneMatch, ImportParams,
+    ListQuery, Patch, ResourceId,
+};
+use crate::{
+    app::DbPool,
+    models::{Address, AddressError, Claims, Customer, CustomerFilter, ListParams},
+};
 use axum::extract::Query;
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use serde_json::Value;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use tracing::{info, warn};
 
-use crate::services::CustomerService;
-
-pub struct CustomerController;
-
-impl CustomerController {
-    pub async fn get_customer(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(
-            CustomerService::get_customer(&pool, id)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::NOT_FOUND
-                })?,
-        );
-        Ok(response)
+use super::{
+    delete_error_status, export_response, import_format, list_error_status, patch_error_status,
+    update_error_status,
+};
+use crate::services::{AddressService, CustomerService, UserService};
+
+/// Gets a customer
+#[utoipa::path(
+    get,
+    path = "/api/v1/customers/{id}",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Id of the customer"), FieldsQuery),
+    responses(
+        (status = 200, body = Customer, description = "Versioned by ETag"),
+        (status = 304, description = "Matches If-None-Match"),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Not a customer"),
+        (status = 404, description = "Unknown customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_customer(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    fields: Fields,
+    if_none_match: IfNoneMatch,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer = CustomerService::get_customer(&pool, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::NOT_FOUND
+        })?;
+    let body = fields.select(&customer)?;
+    Ok(if_none_match.respond(fields.etag(customer.version, &body), body))
+}
+
+/// Lists customers
+#[utoipa::path(
+    get,
+    path = "/api/v1/customers",
+    tag = "customers",
+    params(ListQuery, CustomerFilter, FieldsQuery),
+    responses(
+        (
+            status = 200,
+            body = [Customer],
+            description = "Page of customers, counted in X-Total-Count"
+        ),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Not a customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_all_customers(
+    State(pool): State<DbPool>,
+    list_params: ListParams,
+    Query(filter): Query<CustomerFilter>,
+    fields: Fields,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = CustomerService::get_customers(&pool, &list_params, &filter)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            list_error_status(&e)
+        })?;
+    Ok(list_params.respond(fields.select_page(page)?))
+}
+
+/// Creates a customer
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/customers",
+    tag = "admin",
+    request_body = Customer,
+    responses((status = 200, body = i32, description = "Id of the new customer")),
+    security(("bearer" = []))
+)]
+pub async fn create_customer(
+    State(pool): State<DbPool>,
+    Json(customer): Json<Customer>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        CustomerService::create_customer(&pool, customer)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a customer
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/customers/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the customer"),
+        ("If-Match" = String, Header, description = "ETag of the customer or `*`"),
+    ),
+    request_body = Customer,
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 412, description = "Customer changed since"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_customer(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    Json(customer): Json<Customer>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != customer.id {
+        return Err(StatusCode::BAD_REQUEST);
     }
 
-    pub async fn get_all_customers(
-        State(pool): State<DbPool>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(
-            CustomerService::get_all_customers(&pool)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+    let version = CustomerService::update_customer(&pool, customer, version)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            update_error_status(&e)
+        })?;
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
+
+/// Changes a customer by JSON Merge Patch or JSON Patch
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/customers/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the customer"),
+        ("If-Match" = String, Header, description = "ETag of the customer or `*`"),
+    ),
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "Also accepts a JSON Patch as `application/json-patch+json`",
+    ),
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 409, description = "Test operation failed"),
+        (status = 412, description = "Customer changed since"),
+        (status = 415, description = "Unsupported patch format"),
+        (status = 422, description = "Patch does not apply"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn partial_update_customer(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    patch: Patch,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer = CustomerService::get_customer(&pool, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::NOT_FOUND
+        })?;
+
+    info!("Received patch: {:?}\nTo update: {:?}", patch, customer);
+
+    let customer = patch.apply_to(&customer).map_err(|e| {
+        warn!("{e}");
+        patch_error_status(&e)
+    })?;
+    if customer.id != id {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
 
-    pub async fn create_customer(
-        State(pool): State<DbPool>,
-        Json(customer): Json<Customer>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(
-            CustomerService::create_customer(&pool, customer)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+    let version = CustomerService::update_customer(&pool, customer, version)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            update_error_status(&e)
+        })?;
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
+
+/// Lists the addresses of the customer
+#[utoipa::path(
+    get,
+    path = "/api/v1/addresses",
+    tag = "customers",
+    responses(
+        (status = 200, body = [Address]),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Not a customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_addresses(
+    State(pool): State<DbPool>,
+    claims: Claims,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        AddressService::get_addresses(&pool, customer_id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists the addresses of a customer
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/customers/{id}/addresses",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the customer")),
+    responses((status = 200, body = [Address])),
+    security(("bearer" = []))
+)]
+pub async fn get_customer_addresses(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(AddressService::get_addresses(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Adds an address of the customer
+#[utoipa::path(
+    post,
+    path = "/api/v1/addresses",
+    tag = "customers",
+    request_body = Address,
+    responses(
+        (status = 200, body = i32, description = "Id of the new address"),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Not a customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_address(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    Json(address): Json<Address>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received address: {:?}", address);
+
+    let customer_id = get_customer_id(&pool, &claims).await?;
+    let response = Json(
+        AddressService::create_address(&pool, customer_id, address)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces an address of the customer
+#[utoipa::path(
+    put,
+    path = "/api/v1/addresses/{id}",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Id of the address")),
+    request_body = Address,
+    responses(
+        (status = 200),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 403, description = "Not a customer"),
+        (status = 404, description = "Unknown address"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_address(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+    Json(address): Json<Address>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != address.id {
+        return Err(StatusCode::BAD_REQUEST);
     }
 
-    pub async fn update_customer(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(customer): Json<Customer>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        if id != customer.id {
-            return Err(StatusCode::BAD_REQUEST);
-        }
-
-        let response = Json(
-            CustomerService::update_customer(&pool, customer)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+    let customer_id = get_customer_id(&pool, &claims).await?;
+    AddressService::update_address(&pool, customer_id, address)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            address_error_status(&e)
+        })?;
+    Ok(StatusCode::OK)
+}
+
+/// Removes an address of the customer
+#[utoipa::path(
+    delete,
+    path = "/api/v1/addresses/{id}",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Id of the address")),
+    responses(
+        (status = 200),
+        (status = 403, description = "Not a customer"),
+        (status = 404, description = "Unknown address"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_address(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = get_customer_id(&pool, &claims).await?;
+    AddressService::delete_address(&pool, customer_id, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            address_error_status(&e)
+        })?;
+    Ok(StatusCode::OK)
+}
+
+/// Resolves the customer the authenticated user acts for.
+pub(crate) async fn get_customer_id(pool: &DbPool, claims: &Claims) -> Result<i32, StatusCode> {
+    UserService::get_customer_id(pool, &claims.name)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::FORBIDDEN
+        })
+}
+
+fn address_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<AddressError>() {
+        Some(_) => StatusCode::NOT_FOUND,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
     }
+}
+
+/// Deletes a customer
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/customers/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the customer"), DeleteParams),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown customer"),
+        (status = 409, description = "Still referred to"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_customer(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Query(DeleteParams { hard }): Query<DeleteParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        CustomerService::delete_customer(&pool, id, hard)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                delete_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
 
-    pub async fn partial_update_customer(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(mut body): Json<Value>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let body_map = body.as_object_mut().ok_or(StatusCode::BAD_REQUEST)?;
-        body_map.remove("id");
-        let mut customer_with_id = CustomerService::get_customer(&pool, id)
+/// Restores a soft deleted customer
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/customers/{id}/restore",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the customer")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_customer(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        CustomerService::restore_customer(&pool, id)
             .await
             .map_err(|e| {
                 warn!("{e}");
                 StatusCode::NOT_FOUND
-            })?;
-
-        info!(
-            "Received body_map: {:?}\nTo update: {:?}",
-            body_map, customer_with_id
-        );
-
-        for (key, value) in body_map.iter_mut() {
-            match key.as_str() {
-                "name" => {
-                    customer_with_id.name =
-                        value.as_str().ok_or(StatusCode::BAD_REQUEST)?.to_string()
-                }
-                "address" => {
-                    customer_with_id.address =
-                        value.as_str().ok_or(StatusCode::BAD_REQUEST)?.to_string()
-                }
-                _ => return Err(StatusCode::BAD_REQUEST),
-            }
-        }
-
-        let response = Json(
-            CustomerService::update_customer(&pool, customer_with_id)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
-    }
+            })?,
+    );
+    Ok(response)
+}
+
+/// Creates or updates customers in bulk
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/customers/import",
+    tag = "admin",
+    params(ImportParams),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "Also accepts JSON Lines as `application/x-ndjson`",
+    ),
+    responses(
+        (status = 200, body = ImportReport),
+        (status = 415, description = "Unsupported file format"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn import_customers(
+    State(pool): State<DbPool>,
+    Query(ImportParams { dry_run }): Query<ImportParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = import_format(&headers)?;
+    let response = Json(
+        CustomerService::import_customers(&pool, format, &body, dry_run)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Streams all customers
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/customers/export",
+    tag = "admin",
+    params(ExportParams),
+    responses((status = 200, body = String, content_type = "text/csv")),
+    security(("bearer" = []))
+)]
+pub async fn export_customers(
+    State(pool): State<DbPool>,
+    Query(ExportParams { format }): Query<ExportParams>,
+) -> impl IntoResponse {
+    export_response("customers", format, CustomerService::export_customers(pool, format))
 }
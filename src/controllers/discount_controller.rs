@@ -0,0 +1,146 @@
+use crate::models::ResourceId;
+use crate::{app::DbPool, models::DiscountCode};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::DiscountService;
+
+/// Gets a discount code
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/discounts/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the discount code")),
+    responses(
+        (status = 200, body = DiscountCode),
+        (status = 404, description = "Unknown discount code"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_discount_code(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        DiscountService::get_discount_code(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::NOT_FOUND
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists discount codes
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/discounts",
+    tag = "admin",
+    responses((status = 200, body = [DiscountCode])),
+    security(("bearer" = []))
+)]
+pub async fn get_all_discount_codes(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        DiscountService::get_all_discount_codes(&pool)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Creates a discount code
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/discounts",
+    tag = "admin",
+    request_body = DiscountCode,
+    responses(
+        (status = 200, body = i32, description = "Id of the new discount code"),
+        (status = 400, description = "Invalid discount"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_discount_code(
+    State(pool): State<DbPool>,
+    Json(discount_code): Json<DiscountCode>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received discount code: {:?}", discount_code);
+
+    let response = Json(
+        DiscountService::create_discount_code(&pool, discount_code)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::BAD_REQUEST
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a discount code
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/discounts/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the discount code")),
+    request_body = DiscountCode,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid discount or id differing from the one in the body"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_discount_code(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(discount_code): Json<DiscountCode>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != discount_code.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let response = Json(
+        DiscountService::update_discount_code(&pool, discount_code)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::BAD_REQUEST
+            })?,
+    );
+    Ok(response)
+}
+
+/// Deletes a discount code
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/discounts/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the discount code")),
+    responses((status = 200)),
+    security(("bearer" = []))
+)]
+pub async fn delete_discount_code(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        DiscountService::delete_discount_code(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::CONFLICT
+            })?,
+    );
+    Ok(response)
+}
@@ -0,0 +1,34 @@
+use crate::{
+    app::DbPool,
+    graphql::{CallerCustomer, CustomerLoader, OrderProductsLoader, ProductLoader, GRAPHQL_PATH},
+    models::Claims,
+    setup::GRAPHQL_SCHEMA,
+};
+use async_graphql::{dataloader::DataLoader, http::GraphiQLSource};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{extract::State, response::Html};
+
+/// Runs queries and mutations over the same services as the REST API.
+/// Anyone may send them, fields are guarded by the role of the user the
+/// bearer token was issued for, if any.
+pub async fn graphql(
+    State(pool): State<DbPool>,
+    claims: Option<Claims>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut request = request
+        .into_inner()
+        .data(DataLoader::new(ProductLoader::new(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(CustomerLoader::new(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(OrderProductsLoader::new(pool.clone()), tokio::spawn))
+        .data(CallerCustomer::default())
+        .data(pool);
+    if let Some(claims) = claims {
+        request = request.data(claims);
+    }
+    GRAPHQL_SCHEMA.execute(request).await.into()
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint(GRAPHQL_PATH).finish())
+}
@@ -0,0 +1,176 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{ImageError, MAX_IMAGE_BYTES},
+};
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::ImageService;
+use crate::setup::BLOB_STORE;
+
+/// Largest upload request, several images can be sent at once.
+pub const MAX_UPLOAD_BYTES: usize = 10 * MAX_IMAGE_BYTES;
+
+/// Uploads images of a product
+///
+/// Stores every file sent in an `image` field of the multipart body as
+/// an image of the product with id `id`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products/{id}/images",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the product")),
+    request_body(
+        content = String,
+        content_type = "multipart/form-data",
+        description = "PNG, JPEG or WebP images in `image` fields",
+    ),
+    responses(
+        (status = 200, body = [ProductImage]),
+        (status = 400, description = "No or invalid image"),
+        (status = 404, description = "Unknown product"),
+        (status = 413, description = "Image too large"),
+        (status = 415, description = "Unsupported image type"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn upload_product_images(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut images = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        warn!("{e}");
+        e.status()
+    })? {
+        if field.name() != Some("image") {
+            continue;
+        }
+        info!(
+            "Received image {:?} of type {:?} for product {}",
+            field.file_name(),
+            field.content_type(),
+            id
+        );
+        let data = field.bytes().await.map_err(|e| {
+            warn!("{e}");
+            e.status()
+        })?;
+
+        let image = ImageService::add_product_image(&pool, id, data.to_vec())
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                image_error_status(&e)
+            })?;
+        images.push(image);
+    }
+    if images.is_empty() {
+        warn!("{}", ImageError::NoImage);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    Ok(Json(images))
+}
+
+/// Lists the images of a product
+///
+/// `id` is the id of the product to list the images of.
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/images",
+    tag = "products",
+    params(("id" = i32, Path, description = "Id of the product")),
+    responses((status = 200, body = [ProductImage]))
+)]
+pub async fn get_product_images(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut images = ImageService::get_product_images(&pool, &[id])
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(images.remove(&id).unwrap_or_default()))
+}
+
+/// Deletes an image of a product
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/images/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the image")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown image"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_product_image(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        ImageService::delete_product_image(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::NOT_FOUND
+            })?,
+    );
+    Ok(response)
+}
+
+/// Downloads an uploaded image
+///
+/// Serves blobs of the configured store, for stores that are not
+/// reachable by clients themselves.
+#[utoipa::path(
+    get,
+    path = "/api/v1/media/{key}",
+    tag = "products",
+    params(("key" = String, Path, description = "Key of the image in the blob store")),
+    responses(
+        (status = 200, description = "Image"),
+        (status = 404, description = "Unknown image"),
+    )
+)]
+pub async fn get_media(Path(key): Path<String>) -> Result<impl IntoResponse, StatusCode> {
+    let blob = BLOB_STORE
+        .get(&key)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, blob.content_type),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable".to_string()),
+        ],
+        blob.data,
+    ))
+}
+
+/// Oversized images and images of other types get their own statuses,
+/// images that cannot be decoded are unprocessable.
+fn image_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<ImageError>() {
+        Some(ImageError::UnknownProduct(_)) => StatusCode::NOT_FOUND,
+        Some(ImageError::TooLarge(_)) => StatusCode::PAYLOAD_TOO_LARGE,
+        Some(ImageError::UnsupportedType(_)) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Some(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,98 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{JobError, JobFilter, ListParams, ListQuery},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::warn;
+
+use super::list_error_status;
+use crate::services::JobQueue;
+
+/// Lists background jobs
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs",
+    tag = "admin",
+    params(ListQuery, JobFilter),
+    responses(
+        (
+            status = 200,
+            body = [QueuedJob],
+            description = "Page of jobs, counted in X-Total-Count"
+        ),
+        (status = 400, description = "Invalid filter or pagination"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_all_jobs(
+    State(pool): State<DbPool>,
+    list_params: ListParams,
+    Query(filter): Query<JobFilter>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = JobQueue::get_jobs(&pool, &list_params, &filter)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            list_error_status(&e)
+        })?;
+    Ok(list_params.respond(page))
+}
+
+/// Gets a background job
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the job")),
+    responses(
+        (status = 200, body = QueuedJob),
+        (status = 404, description = "Unknown job"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_job(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(JobQueue::get_job(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?);
+    Ok(response)
+}
+
+/// Queues a failed background job again
+///
+/// Only failed jobs can be retried, anything else failing means there
+/// was no such job.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/{id}/retry",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the job")),
+    responses(
+        (status = 200, body = QueuedJob),
+        (status = 404, description = "Unknown job"),
+        (status = 409, description = "Job has not failed"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn retry_job(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(JobQueue::retry_job(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        match e.downcast_ref::<JobError>() {
+            Some(JobError::NotFailed(_)) => StatusCode::CONFLICT,
+            None => StatusCode::NOT_FOUND,
+        }
+    })?);
+    Ok(response)
+}
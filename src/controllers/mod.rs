@@ -1,9 +1,88 @@
-mod customer_controller;
-mod order_controller;
-mod product_controller;
-mod user_controller;
-
-pub use customer_controller::CustomerController;
-pub use order_controller::OrderController;
-pub use product_controller::ProductController;
-pub use user_controller::UserController;
+pub mod cart_controller;
+pub mod category_controller;
+pub mod customer_controller;
+pub mod discount_controller;
+pub mod graphql_controller;
+pub mod image_controller;
+pub mod job_controller;
+pub mod order_controller;
+pub mod order_event_controller;
+pub mod payment_controller;
+pub mod product_controller;
+pub mod shipment_controller;
+pub mod tax_controller;
+pub mod user_controller;
+pub mod variant_controller;
+pub mod webhook_controller;
+
+pub use image_controller::MAX_UPLOAD_BYTES;
+
+/// Bad pagination or sorting parameters are the client's fault.
+pub(crate) fn list_error_status(e: &color_eyre::Report) -> axum::http::StatusCode {
+    match e.downcast_ref::<crate::models::ListError>() {
+        Some(_) => axum::http::StatusCode::BAD_REQUEST,
+        None => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// A failed `test` operation conflicts with the current state of the
+/// resource, any other patch that cannot be applied is unprocessable.
+pub(crate) fn patch_error_status(e: &crate::models::PatchError) -> axum::http::StatusCode {
+    match e {
+        crate::models::PatchError::TestFailed(_) => axum::http::StatusCode::CONFLICT,
+        _ => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+/// Updates made against an outdated version fail their precondition.
+pub(crate) fn update_error_status(e: &color_eyre::Report) -> axum::http::StatusCode {
+    match e.downcast_ref::<crate::models::VersionError>() {
+        Some(_) => axum::http::StatusCode::PRECONDITION_FAILED,
+        None => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Rows that are still referenced cannot be hard deleted, anything else
+/// failing means there was no such row to delete or restore.
+pub(crate) fn delete_error_status(e: &color_eyre::Report) -> axum::http::StatusCode {
+    match e.downcast_ref::<crate::models::DeleteError>() {
+        Some(_) => axum::http::StatusCode::CONFLICT,
+        None => axum::http::StatusCode::NOT_FOUND,
+    }
+}
+
+/// Format of an import, told by the content type of its body.
+pub(crate) fn import_format(
+    headers: &axum::http::HeaderMap,
+) -> Result<crate::models::BulkFormat, axum::http::StatusCode> {
+    headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(crate::models::BulkFormat::from_content_type)
+        .ok_or(axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE)
+}
+
+/// Sends an export as a download while it is being read. Failures midway
+/// can only cut the download short.
+pub(crate) fn export_response(
+    name: &str,
+    format: crate::models::BulkFormat,
+    records: impl futures_util::Stream<Item = color_eyre::Result<axum::body::Bytes>> + Send + 'static,
+) -> impl axum::response::IntoResponse {
+    use futures_util::TryStreamExt;
+
+    let body = axum::body::StreamBody::new(records.map_err(|e| {
+        tracing::warn!("Export failed: {e}");
+        std::io::Error::other(e.to_string())
+    }));
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", name, format.extension()),
+            ),
+        ],
+        body,
+    )
+}
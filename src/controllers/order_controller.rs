@@ -1,150 +1,498 @@
-use crate::models::QueryIdParam;
+use crate::models::ResourceId;
 use crate::{app::DbPool, models::*};
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use chrono::NaiveDateTime;
-use serde_json::Value;
 use tracing::{info, warn};
 
-use crate::services::OrderService;
+use super::{customer_controller, delete_error_status, list_error_status, patch_error_status};
+use crate::services::{OrderService, RefundService};
 
-pub struct OrderController;
-
-impl OrderController {
-    pub async fn get_order(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(OrderService::get_order(&pool, id).await.map_err(|e| {
+/// Gets an order with its lines and totals
+///
+/// Expanded resources change without the order, so only orders sent
+/// without them are tagged. Only admins and the customer who placed the
+/// order may expand its customer.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Id of the order"), FieldsQuery, ExpandQuery),
+    responses(
+        (
+            status = 200,
+            body = OrderWithProducts,
+            description = "Expansions are embedded under `expanded`"
+        ),
+        (status = 304, description = "Matches If-None-Match"),
+        (status = 400, description = "Unknown expansion"),
+        (status = 401, description = "Customer expanded without a token"),
+        (status = 403, description = "Customer of an order of someone else expanded"),
+        (status = 404, description = "Unknown order"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    claims: Option<Claims>,
+    fields: Fields,
+    expand: Expand,
+    if_none_match: IfNoneMatch,
+) -> Result<Response, StatusCode> {
+    let owner = customer_expansion_owner(&pool, claims.as_ref(), &expand).await?;
+    let order = OrderService::get_order_with_products(&pool, id)
+        .await
+        .map_err(|e| {
             warn!("{e}");
             StatusCode::NOT_FOUND
-        })?);
-        Ok(response)
+        })?;
+    if owner.is_some_and(|customer_id| customer_id != order.customer_id) {
+        warn!("Customer {:?} does not own order {}", owner, id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut body = fields.select(&order)?;
+    if expand.is_empty() {
+        return Ok(if_none_match.respond(fields.etag(order.version, &body), body));
+    }
+    embed_expansions(
+        &pool,
+        &expand,
+        owner,
+        &[(order.id, order.customer_id)],
+        std::slice::from_mut(&mut body),
+    )
+    .await?;
+    Ok(Json(body).into_response())
+}
+
+/// Lists orders
+///
+/// Customers expanding the customer of the listed orders only get it for
+/// the orders they placed themselves.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders",
+    tag = "orders",
+    params(ListQuery, OrderFilter, FieldsQuery, ExpandQuery),
+    responses(
+        (
+            status = 200,
+            body = [ListedOrder],
+            description = "Page of orders, counted in X-Total-Count"
+        ),
+        (status = 400, description = "Invalid filter, pagination or expansion"),
+        (status = 401, description = "Customer expanded without a token"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn get_all_orders(
+    State(pool): State<DbPool>,
+    claims: Option<Claims>,
+    list_params: ListParams,
+    Query(filter): Query<OrderFilter>,
+    fields: Fields,
+    expand: Expand,
+) -> Result<impl IntoResponse, StatusCode> {
+    let owner = customer_expansion_owner(&pool, claims.as_ref(), &expand).await?;
+    let page = OrderService::get_orders(&pool, &list_params, &filter)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            list_error_status(&e)
+        })?;
+    let ids: Vec<(i32, i32)> = page
+        .items
+        .iter()
+        .map(|listed| (listed.order.id, listed.order.customer_id))
+        .collect();
+    let mut page = fields.select_page(page)?;
+    embed_expansions(&pool, &expand, owner, &ids, &mut page.items).await?;
+    Ok(list_params.respond(page))
+}
+
+/// Customers are personal data, so expanding them takes a customer or
+/// admin token. Returns the id of the customer the expansion is limited
+/// to, which is none for admins and for requests not expanding customers.
+async fn customer_expansion_owner(
+    pool: &DbPool,
+    claims: Option<&Claims>,
+    expand: &Expand,
+) -> Result<Option<i32>, StatusCode> {
+    if !expand.contains(Expansion::Customer) {
+        return Ok(None);
+    }
+    let claims = claims.ok_or(StatusCode::UNAUTHORIZED)?;
+    match claims.role {
+        Roles::Admin => Ok(None),
+        Roles::Customer => Ok(Some(customer_controller::get_customer_id(pool, claims).await?)),
     }
+}
 
-    pub async fn get_all_orders(
-        State(pool): State<DbPool>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(OrderService::get_all_orders(&pool).await.map_err(|e| {
+/// Adds what `expand` asks for to each order under `expanded`. `ids`
+/// holds the id and the customer id of every order in `orders`. With an
+/// `owner`, customers are left out of orders placed by someone else.
+async fn embed_expansions(
+    pool: &DbPool,
+    expand: &Expand,
+    owner: Option<i32>,
+    ids: &[(i32, i32)],
+    orders: &mut [serde_json::Value],
+) -> Result<(), StatusCode> {
+    if expand.is_empty() {
+        return Ok(());
+    }
+    let mut expansions = OrderService::get_expansions(pool, ids, expand)
+        .await
+        .map_err(|e| {
             warn!("{e}");
             StatusCode::INTERNAL_SERVER_ERROR
-        })?);
-        Ok(response)
+        })?;
+    for ((order_id, customer_id), order) in ids.iter().zip(orders.iter_mut()) {
+        if let (Some(mut expansion), serde_json::Value::Object(order)) =
+            (expansions.remove(order_id), order)
+        {
+            if owner.is_some_and(|owner| owner != *customer_id) {
+                expansion.customer = None;
+            }
+            let expansion = serde_json::to_value(expansion)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            order.insert("expanded".to_string(), expansion);
+        }
     }
+    Ok(())
+}
 
-    pub async fn create_order(
-        State(pool): State<DbPool>,
-        Json(order): Json<OrderWithProducts>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        info!("Received order: {:?}", order);
-
-        let response = Json(
-            OrderService::create_order(&pool, order)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+/// Places an order
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders",
+    tag = "orders",
+    request_body = OrderWithProducts,
+    responses(
+        (status = 200, body = i32, description = "Id of the new order"),
+        (status = 400, description = "Unknown products or invalid discount code"),
+        (status = 409, description = "Out of stock"),
+    )
+)]
+pub async fn create_order(
+    State(pool): State<DbPool>,
+    Json(order): Json<OrderWithProducts>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received order: {:?}", order);
+
+    let response = Json(
+        OrderService::create_order(&pool, order)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                order_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces an order
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/orders/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the order"),
+        ("If-Match" = String, Header, description = "ETag of the order or `*`"),
+    ),
+    request_body = OrderWithProducts,
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 409, description = "Order was cancelled or refunded"),
+        (status = 412, description = "Order changed since"),
+        (status = 422, description = "Line below its refunded quantity"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    Json(order): Json<OrderWithProducts>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != order.id {
+        return Err(StatusCode::BAD_REQUEST);
     }
 
-    pub async fn update_order(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(order): Json<OrderWithProducts>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        if id != order.id {
-            return Err(StatusCode::BAD_REQUEST);
-        }
+    let version = OrderService::update_order(&pool, order, version)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            order_error_status(&e)
+        })?;
+
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
+
+/// Changes an order by JSON Merge Patch or JSON Patch
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/orders/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the order"),
+        ("If-Match" = String, Header, description = "ETag of the order or `*`"),
+    ),
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "Also accepts a JSON Patch as `application/json-patch+json`",
+    ),
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 409, description = "Test operation failed, or order was cancelled or refunded"),
+        (status = 412, description = "Order changed since"),
+        (status = 415, description = "Unsupported patch format"),
+        (status = 422, description = "Patch does not apply"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn partial_update_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    patch: Patch,
+) -> Result<impl IntoResponse, StatusCode> {
+    let order_with_products = OrderService::get_order_with_products(&pool, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::NOT_FOUND
+        })?;
 
-        let response = Json(
-            OrderService::update_order(&pool, order)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
+    info!(
+        "Received patch: {:?}\nTo update: {:?}",
+        patch, order_with_products
+    );
 
-        Ok(response)
+    let order_with_products = patch.apply_to(&order_with_products).map_err(|e| {
+        warn!("{e}");
+        patch_error_status(&e)
+    })?;
+    if order_with_products.id != id {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
 
-    pub async fn partial_update_order(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(mut body): Json<Value>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let body_map = body.as_object_mut().ok_or(StatusCode::BAD_REQUEST)?;
-        body_map.remove("id");
+    let version = OrderService::update_order(&pool, order_with_products, version)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            order_error_status(&e)
+        })?;
+
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
+
+/// Cancels an order of the customer
+///
+/// Customers may cancel their own orders, until they are fulfilled.
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders/{id}/cancel",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses(
+        (status = 200),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Order of someone else"),
+        (status = 409, description = "Order cannot be cancelled anymore"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn cancel_order(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    ensure_order_owner(&pool, &claims, id).await?;
+    admin_cancel_order(State(pool), ResourceId(id)).await
+}
+
+/// Cancels any order
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/{id}/cancel",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses(
+        (status = 200),
+        (status = 409, description = "Order cannot be cancelled anymore"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_cancel_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    RefundService::cancel_order(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        order_error_status(&e)
+    })?;
+    Ok(StatusCode::OK)
+}
+
+/// Refunds products of an order
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/{id}/refunds",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    request_body = RefundRequest,
+    responses(
+        (status = 200, body = Refund),
+        (status = 400, description = "Invalid quantity"),
+        (status = 409, description = "Order is not paid or has nothing to refund"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn refund_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(refund): Json<RefundRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received refund: {:?}", refund);
+
+    let response = Json(
+        RefundService::refund_order(&pool, id, refund)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                order_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
 
-        let mut order_with_products = OrderService::get_order_with_products(&pool, id)
+/// Lists the refunds of an order
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/{id}/refunds",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses((status = 200, body = [Refund])),
+    security(("bearer" = []))
+)]
+pub async fn get_order_refunds(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(RefundService::get_order_refunds(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Deletes an order
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/orders/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order"), DeleteParams),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown order"),
+        (status = 409, description = "Still referred to"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Query(DeleteParams { hard }): Query<DeleteParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        OrderService::delete_order(&pool, id, hard)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                delete_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Restores a soft deleted order
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/{id}/restore",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown order"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_order(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        OrderService::restore_order(&pool, id)
             .await
             .map_err(|e| {
                 warn!("{e}");
                 StatusCode::NOT_FOUND
-            })?;
-
-        info!(
-            "Received body_map: {:?}\nTo update: {:?}",
-            body_map, order_with_products
-        );
-
-        for (key, value) in body_map.iter_mut() {
-            match key.as_str() {
-                "customer_id" => {
-                    order_with_products.customer_id =
-                        value.as_i64().ok_or(StatusCode::BAD_REQUEST)? as i32
-                }
-                "status" => {
-                    order_with_products.status =
-                        value.as_str().ok_or(StatusCode::BAD_REQUEST)?.to_string()
-                }
-                "created_at" => {
-                    order_with_products.created_at = NaiveDateTime::parse_from_str(
-                        value.as_str().ok_or(StatusCode::BAD_REQUEST)?,
-                        "%Y-%m-%d %H:%M:%S",
-                    )
-                    .map_err(|e| {
-                        warn!("{e}");
-                        StatusCode::BAD_REQUEST
-                    })?
-                }
-                "products" => {
-                    order_with_products.products = value
-                        .as_object()
-                        .ok_or(StatusCode::BAD_REQUEST)?
-                        .iter()
-                        .map(|(key, value)| -> (i32, i32) {
-                            (
-                                key.parse::<i32>()
-                                    .map_err(|e| {
-                                        warn!("{e}");
-                                        StatusCode::BAD_REQUEST
-                                    })
-                                    .unwrap(),
-                                value.as_i64().ok_or(StatusCode::BAD_REQUEST).unwrap() as i32,
-                            )
-                        })
-                        .collect()
-                }
-                _ => return Err(StatusCode::BAD_REQUEST),
-            }
-        }
+            })?,
+    );
+    Ok(response)
+}
 
-        let response = Json(
-            OrderService::update_order(&pool, order_with_products)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
+/// Lets customers act only on their own orders.
+pub(crate) async fn ensure_order_owner(
+    pool: &DbPool,
+    claims: &Claims,
+    order_id: i32,
+) -> Result<(), StatusCode> {
+    let customer_id = customer_controller::get_customer_id(pool, claims).await?;
+    let order = OrderService::get_order(pool, order_id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?;
+
+    if order.customer_id != customer_id {
+        warn!("{} does not own order {}", claims, order_id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
 
-        Ok(response)
+/// Orders that cannot be priced because of a bad discount code or missing
+/// tax configuration are rejected as unprocessable, not as server errors.
+/// Running out of stock, cancelling or shipping too late conflicts with
+/// the current state instead, and updates made against an outdated
+/// version fail their precondition.
+pub(crate) fn order_error_status(e: &color_eyre::Report) -> StatusCode {
+    if e.downcast_ref::<VersionError>().is_some() {
+        StatusCode::PRECONDITION_FAILED
+    } else if e.downcast_ref::<StockError>().is_some()
+        || matches!(e.downcast_ref(), Some(RefundError::NotCancellable(_)))
+        || matches!(e.downcast_ref(), Some(RefundError::NotUpdatable(_)))
+        || matches!(e.downcast_ref(), Some(ShipmentError::NotShippable(_)))
+    {
+        StatusCode::CONFLICT
+    } else if e.downcast_ref::<DiscountError>().is_some()
+        || e.downcast_ref::<TaxError>().is_some()
+        || e.downcast_ref::<RefundError>().is_some()
+        || e.downcast_ref::<ShipmentError>().is_some()
+        || e.downcast_ref::<AddressError>().is_some()
+        || e.downcast_ref::<VariantError>().is_some()
+    {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
     }
 }
@@ -0,0 +1,120 @@
+use std::convert::Infallible;
+
+use crate::{
+    app::DbPool,
+    models::{Claims, OrderEvent, Roles},
+    services::OrderEventService,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use futures_util::{Stream, StreamExt};
+use tracing::{info, warn};
+
+use super::customer_controller;
+
+/// Streams order events
+///
+/// Server-Sent Events named `order_created` or `order_status_changed`, with
+/// the event as JSON data. Customers only get the events of their own orders.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/stream",
+    tag = "orders",
+    responses(
+        (status = 200, body = OrderEvent, content_type = "text/event-stream"),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "User is no customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn stream_order_events(
+    State(pool): State<DbPool>,
+    claims: Claims,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let customer_id = get_scope(&pool, &claims).await?;
+    info!("{} subscribed to order events", claims);
+
+    let events = OrderEventService::subscribe(customer_id).filter_map(|event| async move {
+        match Event::default().event(event.event.name()).json_data(&event) {
+            Ok(sse_event) => Some(Ok(sse_event)),
+            Err(e) => {
+                warn!("{e}");
+                None
+            }
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Sends order events over a WebSocket
+///
+/// Every event is sent as a JSON text message. Customers only get the events
+/// of their own orders. Messages from the client are ignored.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/ws",
+    tag = "orders",
+    responses(
+        (status = 101, body = OrderEvent, description = "Switched to the WebSocket protocol"),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "User is no customer"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn order_events_socket(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    upgrade: WebSocketUpgrade,
+) -> Result<impl IntoResponse, StatusCode> {
+    let customer_id = get_scope(&pool, &claims).await?;
+    info!("{} subscribed to order events", claims);
+
+    Ok(upgrade.on_upgrade(move |socket| send_order_events(socket, customer_id)))
+}
+
+async fn send_order_events(mut socket: WebSocket, customer_id: Option<i32>) {
+    let events = OrderEventService::subscribe(customer_id);
+    futures_util::pin_mut!(events);
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else { break };
+                if !send_order_event(&mut socket, &event).await {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Returns whether the client is still there.
+async fn send_order_event(socket: &mut WebSocket, event: &OrderEvent) -> bool {
+    match serde_json::to_string(event) {
+        Ok(text) => socket.send(Message::Text(text)).await.is_ok(),
+        Err(e) => {
+            warn!("{e}");
+            true
+        }
+    }
+}
+
+/// Customers only get the events of their own orders, admins get all.
+async fn get_scope(pool: &DbPool, claims: &Claims) -> Result<Option<i32>, StatusCode> {
+    match claims.role {
+        Roles::Admin => Ok(None),
+        Roles::Customer => Ok(Some(customer_controller::get_customer_id(pool, claims).await?)),
+    }
+}
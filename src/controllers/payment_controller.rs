@@ -0,0 +1,121 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{Claims, PaymentError, PaymentRequest},
+};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use super::order_controller;
+use crate::services::{PaymentService, PAYMENT_SIGNATURE_HEADER};
+
+/// Starts paying for an order of the customer
+#[utoipa::path(
+    post,
+    path = "/api/v1/payments",
+    tag = "payments",
+    request_body = PaymentRequest,
+    responses(
+        (status = 200, body = PaymentIntent),
+        (status = 403, description = "Order of someone else"),
+        (status = 409, description = "Order has nothing to pay for"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_payment_intent(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    Json(payment): Json<PaymentRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received payment request: {:?}", payment);
+    order_controller::ensure_order_owner(&pool, &claims, payment.order_id).await?;
+
+    let response = Json(
+        PaymentService::create_payment_intent(&pool, payment.order_id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                payment_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Gets a payment of the customer
+#[utoipa::path(
+    get,
+    path = "/api/v1/payments/{id}",
+    tag = "payments",
+    params(("id" = i32, Path, description = "Id of the payment")),
+    responses(
+        (status = 200, body = PaymentIntent),
+        (status = 403, description = "Payment of someone else"),
+        (status = 404, description = "Unknown payment"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_payment_intent(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let payment_intent = PaymentService::get_payment_intent(&pool, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::NOT_FOUND
+        })?;
+    order_controller::ensure_order_owner(&pool, &claims, payment_intent.order_id).await?;
+
+    Ok(Json(payment_intent))
+}
+
+/// Receives payment status changes from the payment provider
+///
+/// Called by the payment provider, authenticated by the payload signature
+/// instead of a token.
+#[utoipa::path(
+    post,
+    path = "/api/v1/payments/webhook",
+    tag = "payments",
+    params(("x-payment-signature" = String, Header, description = "Signature of the body")),
+    request_body = PaymentEvent,
+    responses(
+        (status = 200),
+        (status = 401, description = "Missing or invalid signature"),
+        (status = 409, description = "Payment cannot change to the given status"),
+    )
+)]
+pub async fn webhook(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let signature = headers
+        .get(PAYMENT_SIGNATURE_HEADER)
+        .and_then(|signature| signature.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    PaymentService::handle_webhook(&pool, &body, signature)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            payment_error_status(&e)
+        })?;
+    Ok(StatusCode::OK)
+}
+
+fn payment_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<PaymentError>() {
+        Some(PaymentError::InvalidSignature) => StatusCode::UNAUTHORIZED,
+        Some(PaymentError::NothingToPay) => StatusCode::UNPROCESSABLE_ENTITY,
+        Some(PaymentError::InvalidTransition { .. }) => StatusCode::CONFLICT,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
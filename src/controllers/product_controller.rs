@@ -1,118 +1,332 @@
-use crate::models::QueryIdParam;
-use crate::{app::DbPool, models::Product};
+use crate::models::{
+    etag, DeleteParams, ExportParams, Fields, FieldsQuery, IfMatch, IfNoneMatch, ImportParams,
+    ListQuery, Patch, ResourceId,
+};
+use crate::{
+    app::DbPool,
+    models::{ListParams, Product, ProductFilter, SearchError, SearchParams},
+};
 use axum::{
+    body::Bytes,
     extract::{Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
-use serde_json::Value;
 use tracing::{info, warn};
 
+use super::{
+    delete_error_status, export_response, import_format, list_error_status, patch_error_status,
+    update_error_status,
+};
 use crate::services::ProductService;
 
-pub struct ProductController;
+/// Gets a product
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}",
+    tag = "products",
+    params(("id" = i32, Path, description = "Id of the product"), FieldsQuery),
+    responses(
+        (status = 200, body = Product, description = "Versioned by ETag"),
+        (status = 304, description = "Matches If-None-Match"),
+        (status = 404, description = "Unknown product"),
+    )
+)]
+pub async fn get_product(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    fields: Fields,
+    if_none_match: IfNoneMatch,
+) -> Result<impl IntoResponse, StatusCode> {
+    let product = ProductService::get_product(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?;
+    let body = fields.select(&product)?;
+    Ok(if_none_match.respond(fields.etag(product.version, &body), body))
+}
 
-impl ProductController {
-    pub async fn get_product(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(ProductService::get_product(&pool, id).await.map_err(|e| {
+/// Lists products
+#[utoipa::path(
+    get,
+    path = "/api/v1/products",
+    tag = "products",
+    params(ListQuery, ProductFilter, FieldsQuery),
+    responses(
+        (
+            status = 200,
+            body = [Product],
+            description = "Page of products, counted in X-Total-Count"
+        ),
+        (status = 400, description = "Invalid pagination or sort order"),
+    )
+)]
+pub async fn get_all_products(
+    State(pool): State<DbPool>,
+    list_params: ListParams,
+    Query(filter): Query<ProductFilter>,
+    fields: Fields,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = ProductService::get_products(&pool, &list_params, &filter)
+        .await
+        .map_err(|e| {
             warn!("{e}");
-            StatusCode::NOT_FOUND
-        })?);
-        Ok(response)
-    }
+            list_error_status(&e)
+        })?;
+    Ok(list_params.respond(fields.select_page(page)?))
+}
 
-    pub async fn get_all_products(
-        State(pool): State<DbPool>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let response = Json(ProductService::get_all_products(&pool).await.map_err(|e| {
+/// Searches products by name, description and tags
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/search",
+    tag = "products",
+    params(SearchParams, ListQuery, ProductFilter, FieldsQuery),
+    responses(
+        (status = 200, body = [Product], description = "Best matches first"),
+        (status = 400, description = "Query has no words"),
+    )
+)]
+pub async fn search_products(
+    State(pool): State<DbPool>,
+    Query(SearchParams { q }): Query<SearchParams>,
+    list_params: ListParams,
+    Query(filter): Query<ProductFilter>,
+    fields: Fields,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = ProductService::search_products(&pool, &q, &list_params, &filter)
+        .await
+        .map_err(|e| {
             warn!("{e}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?);
-        Ok(response)
-    }
+            match e.downcast_ref::<SearchError>() {
+                Some(_) => StatusCode::BAD_REQUEST,
+                None => list_error_status(&e),
+            }
+        })?;
+    Ok(list_params.respond(fields.select_page(page)?))
+}
 
-    pub async fn create_product(
-        State(pool): State<DbPool>,
-        Json(product): Json<Product>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        info!("Received product: {:?}", product);
-
-        let response = Json(
-            ProductService::create_product(&pool, product)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
-    }
+/// Creates a product
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products",
+    tag = "admin",
+    request_body = Product,
+    responses((status = 200, body = i32, description = "Id of the new product")),
+    security(("bearer" = []))
+)]
+pub async fn create_product(
+    State(pool): State<DbPool>,
+    Json(product): Json<Product>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received product: {:?}", product);
 
-    pub async fn update_product(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(product): Json<Product>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        if id != product.id {
-            return Err(StatusCode::BAD_REQUEST);
-        }
-
-        let response = Json(
-            ProductService::update_product(&pool, product)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+    let response = Json(
+        ProductService::create_product(&pool, product)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a product
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/products/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the product"),
+        ("If-Match" = String, Header, description = "ETag of the product or `*`"),
+    ),
+    request_body = Product,
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 412, description = "Product changed since"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_product(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    Json(product): Json<Product>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != product.id {
+        return Err(StatusCode::BAD_REQUEST);
     }
 
-    pub async fn partial_update_product(
-        State(pool): State<DbPool>,
-        Query(QueryIdParam { id }): Query<QueryIdParam>,
-        Json(mut body): Json<Value>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let body_map = body.as_object_mut().ok_or(StatusCode::BAD_REQUEST)?;
-        body_map.remove("id");
-        let mut product_with_id = ProductService::get_product(&pool, id).await.map_err(|e| {
+    let version = ProductService::update_product(&pool, product, version)
+        .await
+        .map_err(|e| {
             warn!("{e}");
-            StatusCode::NOT_FOUND
+            update_error_status(&e)
         })?;
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
 
-        info!(
-            "Received body_map: {:?}\nTo update: {:?}",
-            body_map, product_with_id
-        );
-
-        for (key, value) in body_map.iter_mut() {
-            match key.as_str() {
-                "name" => {
-                    product_with_id.name =
-                        value.as_str().ok_or(StatusCode::BAD_REQUEST)?.to_string()
-                }
-                "price" => {
-                    product_with_id.price = value.as_i64().ok_or(StatusCode::BAD_REQUEST)? as i32
-                }
-                "available" => {
-                    product_with_id.available = value.as_bool().ok_or(StatusCode::BAD_REQUEST)?
-                }
-                _ => return Err(StatusCode::BAD_REQUEST),
-            }
-        }
-
-        let response = Json(
-            ProductService::update_product(&pool, product_with_id)
-                .await
-                .map_err(|e| {
-                    warn!("{e}");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?,
-        );
-        Ok(response)
+/// Changes a product by JSON Merge Patch or JSON Patch
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/products/{id}",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the product"),
+        ("If-Match" = String, Header, description = "ETag of the product or `*`"),
+    ),
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "Also accepts a JSON Patch as `application/json-patch+json`",
+    ),
+    responses(
+        (status = 200, description = "New version in ETag"),
+        (status = 409, description = "Test operation failed"),
+        (status = 412, description = "Product changed since"),
+        (status = 415, description = "Unsupported patch format"),
+        (status = 422, description = "Patch does not apply"),
+        (status = 428, description = "Missing If-Match"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn partial_update_product(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    IfMatch(version): IfMatch,
+    patch: Patch,
+) -> Result<impl IntoResponse, StatusCode> {
+    let product = ProductService::get_product(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?;
+
+    info!("Received patch: {:?}\nTo update: {:?}", patch, product);
+
+    let product = patch.apply_to(&product).map_err(|e| {
+        warn!("{e}");
+        patch_error_status(&e)
+    })?;
+    if product.id != id {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
     }
+
+    let version = ProductService::update_product(&pool, product, version)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            update_error_status(&e)
+        })?;
+    Ok(([(header::ETAG, etag(version))], Json(())))
+}
+
+/// Deletes a product
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/products/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the product"), DeleteParams),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown product"),
+        (status = 409, description = "Still referred to"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_product(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Query(DeleteParams { hard }): Query<DeleteParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        ProductService::delete_product(&pool, id, hard)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                delete_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Restores a soft deleted product
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products/{id}/restore",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the product")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown product"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_product(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        ProductService::restore_product(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::NOT_FOUND
+            })?,
+    );
+    Ok(response)
+}
+
+/// Creates or updates products in bulk
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/products/import",
+    tag = "admin",
+    params(ImportParams),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "Also accepts JSON Lines as `application/x-ndjson`",
+    ),
+    responses(
+        (status = 200, body = ImportReport),
+        (status = 415, description = "Unsupported file format"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn import_products(
+    State(pool): State<DbPool>,
+    Query(ImportParams { dry_run }): Query<ImportParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = import_format(&headers)?;
+    let response = Json(
+        ProductService::import_products(&pool, format, &body, dry_run)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Streams all products
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/products/export",
+    tag = "admin",
+    params(ExportParams),
+    responses((status = 200, body = String, content_type = "text/csv")),
+    security(("bearer" = []))
+)]
+pub async fn export_products(
+    State(pool): State<DbPool>,
+    Query(ExportParams { format }): Query<ExportParams>,
+) -> impl IntoResponse {
+    export_response("products", format, ProductService::export_products(pool, format))
 }
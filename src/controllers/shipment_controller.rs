@@ -0,0 +1,120 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{Claims, NewShipment},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use super::order_controller;
+use crate::services::ShipmentService;
+
+/// Ships products of an order
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/{id}/shipments",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    request_body = NewShipment,
+    responses(
+        (status = 200, body = Shipment),
+        (status = 400, description = "Invalid quantity"),
+        (status = 409, description = "Order cannot be shipped or has nothing to ship"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_shipment(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(shipment): Json<NewShipment>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received shipment of order {}: {:?}", id, shipment);
+
+    let response = Json(
+        ShipmentService::create_shipment(&pool, id, shipment)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                order_controller::order_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists the shipments of an order
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/{id}/shipments",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses((status = 200, body = [Shipment])),
+    security(("bearer" = []))
+)]
+pub async fn get_order_shipments(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        ShipmentService::get_order_shipments(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists the shipments of an order of the customer
+///
+/// Lets customers track the shipments of their own orders.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/shipments",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Id of the order")),
+    responses(
+        (status = 200, body = [Shipment]),
+        (status = 400, body = AuthErrorResponse, description = "Missing or invalid token"),
+        (status = 403, description = "Order of someone else"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_own_order_shipments(
+    State(pool): State<DbPool>,
+    claims: Claims,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    order_controller::ensure_order_owner(&pool, &claims, id).await?;
+    get_order_shipments(State(pool), ResourceId(id)).await
+}
+
+/// Marks a shipment as delivered
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/shipments/{id}/delivered",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the shipment")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown shipment"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn mark_delivered(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    ShipmentService::mark_delivered(&pool, id)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            StatusCode::NOT_FOUND
+        })?;
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,183 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{TaxCategory, TaxError, TaxRate},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::TaxService;
+
+/// Lists tax categories
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/tax/categories",
+    tag = "admin",
+    responses((status = 200, body = [TaxCategory])),
+    security(("bearer" = []))
+)]
+pub async fn get_all_tax_categories(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        TaxService::get_all_tax_categories(&pool)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Creates a tax category
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tax/categories",
+    tag = "admin",
+    request_body = TaxCategory,
+    responses((status = 200)),
+    security(("bearer" = []))
+)]
+pub async fn create_tax_category(
+    State(pool): State<DbPool>,
+    Json(tax_category): Json<TaxCategory>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received tax category: {:?}", tax_category);
+
+    let response = Json(
+        TaxService::create_tax_category(&pool, tax_category)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::BAD_REQUEST
+            })?,
+    );
+    Ok(response)
+}
+
+/// Gets a tax rate
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/tax/rates/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the tax rate")),
+    responses(
+        (status = 200, body = TaxRate),
+        (status = 404, description = "Unknown tax rate"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_tax_rate(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(TaxService::get_tax_rate(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::NOT_FOUND
+    })?);
+    Ok(response)
+}
+
+/// Lists tax rates
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/tax/rates",
+    tag = "admin",
+    responses((status = 200, body = [TaxRate])),
+    security(("bearer" = []))
+)]
+pub async fn get_all_tax_rates(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(TaxService::get_all_tax_rates(&pool).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Creates a tax rate
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tax/rates",
+    tag = "admin",
+    request_body = TaxRate,
+    responses((status = 200, body = i32, description = "Id of the new tax rate")),
+    security(("bearer" = []))
+)]
+pub async fn create_tax_rate(
+    State(pool): State<DbPool>,
+    Json(tax_rate): Json<TaxRate>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received tax rate: {:?}", tax_rate);
+
+    let response = Json(TaxService::create_tax_rate(&pool, tax_rate).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::BAD_REQUEST
+    })?);
+    Ok(response)
+}
+
+/// Replaces a tax rate
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/tax/rates/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the tax rate")),
+    request_body = TaxRate,
+    responses(
+        (status = 200),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 404, description = "Unknown tax rate"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_tax_rate(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(tax_rate): Json<TaxRate>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != tax_rate.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let response = Json(TaxService::update_tax_rate(&pool, tax_rate).await.map_err(|e| {
+        warn!("{e}");
+        tax_rate_error_status(&e, StatusCode::BAD_REQUEST)
+    })?);
+    Ok(response)
+}
+
+/// Deletes a tax rate
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/tax/rates/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the tax rate")),
+    responses((status = 200), (status = 404, description = "Unknown tax rate")),
+    security(("bearer" = []))
+)]
+pub async fn delete_tax_rate(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(TaxService::delete_tax_rate(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        tax_rate_error_status(&e, StatusCode::INTERNAL_SERVER_ERROR)
+    })?);
+    Ok(response)
+}
+
+/// Missing tax rates are not found, anything else failing gets `otherwise`.
+fn tax_rate_error_status(e: &color_eyre::Report, otherwise: StatusCode) -> StatusCode {
+    match e.downcast_ref::<TaxError>() {
+        Some(TaxError::UnknownRate(_)) => StatusCode::NOT_FOUND,
+        _ => otherwise,
+    }
+}
@@ -0,0 +1,181 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{Attribute, Variant, VariantError},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::VariantService;
+
+/// Lists attributes variants can differ in
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/attributes",
+    tag = "admin",
+    responses((status = 200, body = [Attribute])),
+    security(("bearer" = []))
+)]
+pub async fn get_all_attributes(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(VariantService::get_all_attributes(&pool).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Creates an attribute
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/attributes",
+    tag = "admin",
+    request_body = Attribute,
+    responses((status = 200, body = i32, description = "Id of the new attribute")),
+    security(("bearer" = []))
+)]
+pub async fn create_attribute(
+    State(pool): State<DbPool>,
+    Json(attribute): Json<Attribute>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received attribute: {:?}", attribute);
+
+    let response = Json(
+        VariantService::create_attribute(&pool, attribute)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::BAD_REQUEST
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists the variants of a product
+///
+/// `id` is the id of the product to list the variants of.
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/variants",
+    tag = "products",
+    params(("id" = i32, Path, description = "Id of the product")),
+    responses((status = 200, body = [Variant]))
+)]
+pub async fn get_product_variants(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        VariantService::get_product_variants(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    );
+    Ok(response)
+}
+
+/// Creates a variant of a product
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/variants",
+    tag = "admin",
+    request_body = Variant,
+    responses(
+        (status = 200, body = i32, description = "Id of the new variant"),
+        (status = 400, description = "Attributes do not match their kinds"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_variant(
+    State(pool): State<DbPool>,
+    Json(variant): Json<Variant>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received variant: {:?}", variant);
+
+    let response = Json(
+        VariantService::create_variant(&pool, variant)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                variant_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a variant
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/variants/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the variant")),
+    request_body = Variant,
+    responses(
+        (status = 200),
+        (status = 400, description = "Id differs from the one in the body"),
+        (status = 404, description = "Unknown variant"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_variant(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(variant): Json<Variant>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if id != variant.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let response = Json(
+        VariantService::update_variant(&pool, variant)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                variant_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Deletes a variant
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/variants/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the variant")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown variant"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_variant(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(VariantService::delete_variant(&pool, id).await.map_err(|e| {
+        warn!("{e}");
+        variant_error_status(&e)
+    })?);
+    Ok(response)
+}
+
+/// Unknown variants are not found, attributes that do not match their
+/// definitions are unprocessable and default variants go with their
+/// product only.
+fn variant_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<VariantError>() {
+        Some(VariantError::UnknownVariant(_)) => StatusCode::NOT_FOUND,
+        Some(VariantError::DefaultVariant(_)) => StatusCode::CONFLICT,
+        Some(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,242 @@
+use crate::models::ResourceId;
+use crate::{
+    app::DbPool,
+    models::{
+        ListError, ListParams, ListQuery, NewWebhookSubscription, WebhookDeliveryFilter,
+        WebhookError,
+    },
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{info, warn};
+
+use crate::services::WebhookService;
+
+/// Lists webhook subscriptions
+#[utoipa::path(
+    get,
+    operation_id = "get_all_webhook_subscriptions",
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    responses((status = 200, body = [WebhookSubscription])),
+    security(("bearer" = []))
+)]
+pub async fn get_all_subscriptions(
+    State(pool): State<DbPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(WebhookService::get_subscriptions(&pool).await.map_err(|e| {
+        warn!("{e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+    Ok(response)
+}
+
+/// Gets a webhook subscription
+#[utoipa::path(
+    get,
+    operation_id = "get_webhook_subscription",
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the subscription")),
+    responses(
+        (status = 200, body = WebhookSubscription),
+        (status = 404, description = "Unknown subscription"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_subscription(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        WebhookService::get_subscription(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::NOT_FOUND
+            })?,
+    );
+    Ok(response)
+}
+
+/// Subscribes a url to events
+///
+/// Every event of the given types is POSTed to the url as a `WebhookPayload`,
+/// signed with a hex encoded HMAC-SHA256 of the body in `X-Webhook-Signature`.
+/// Failed deliveries are retried with exponential backoff until they are
+/// given up on as dead.
+#[utoipa::path(
+    post,
+    operation_id = "create_webhook_subscription",
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    request_body = NewWebhookSubscription,
+    responses(
+        (status = 200, body = WebhookSubscription),
+        (status = 422, description = "Invalid url or no event types"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_subscription(
+    State(pool): State<DbPool>,
+    Json(subscription): Json<NewWebhookSubscription>,
+) -> Result<impl IntoResponse, StatusCode> {
+    info!("Received webhook subscription: {:?}", subscription.url);
+
+    let response = Json(
+        WebhookService::create_subscription(&pool, subscription)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                match e.downcast_ref::<WebhookError>() {
+                    Some(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                    None => StatusCode::INTERNAL_SERVER_ERROR,
+                }
+            })?,
+    );
+    Ok(response)
+}
+
+/// Replaces a webhook subscription
+#[utoipa::path(
+    put,
+    operation_id = "update_webhook_subscription",
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the subscription")),
+    request_body = NewWebhookSubscription,
+    responses(
+        (status = 200, body = WebhookSubscription),
+        (status = 404, description = "Unknown subscription"),
+        (status = 422, description = "Invalid url or no event types"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_subscription(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    Json(subscription): Json<NewWebhookSubscription>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        WebhookService::update_subscription(&pool, id, subscription)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                webhook_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Deletes a webhook subscription with its delivery log
+#[utoipa::path(
+    delete,
+    operation_id = "delete_webhook_subscription",
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the subscription")),
+    responses(
+        (status = 200),
+        (status = 404, description = "Unknown subscription"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_subscription(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        WebhookService::delete_subscription(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                StatusCode::NOT_FOUND
+            })?,
+    );
+    Ok(response)
+}
+
+/// Lists the deliveries of a webhook subscription
+#[utoipa::path(
+    get,
+    operation_id = "get_webhook_deliveries",
+    path = "/api/v1/admin/webhooks/{id}/deliveries",
+    tag = "admin",
+    params(
+        ("id" = i32, Path, description = "Id of the subscription"),
+        ListQuery,
+        WebhookDeliveryFilter
+    ),
+    responses(
+        (
+            status = 200,
+            body = [WebhookDelivery],
+            description = "Page of deliveries, counted in X-Total-Count"
+        ),
+        (status = 400, description = "Invalid pagination"),
+        (status = 404, description = "Unknown subscription"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_deliveries(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+    list_params: ListParams,
+    Query(filter): Query<WebhookDeliveryFilter>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let page = WebhookService::get_deliveries(&pool, id, &list_params, &filter)
+        .await
+        .map_err(|e| {
+            warn!("{e}");
+            match e.downcast_ref::<ListError>() {
+                Some(_) => StatusCode::BAD_REQUEST,
+                None => StatusCode::NOT_FOUND,
+            }
+        })?;
+    Ok(list_params.respond(page))
+}
+
+/// Retries a dead webhook delivery
+#[utoipa::path(
+    post,
+    operation_id = "retry_webhook_delivery",
+    path = "/api/v1/admin/webhooks/deliveries/{id}/retry",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the delivery")),
+    responses(
+        (status = 200, body = WebhookDelivery),
+        (status = 404, description = "Unknown delivery"),
+        (status = 409, description = "Delivery is not dead"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn retry_delivery(
+    State(pool): State<DbPool>,
+    ResourceId(id): ResourceId,
+) -> Result<impl IntoResponse, StatusCode> {
+    let response = Json(
+        WebhookService::retry_delivery(&pool, id)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                webhook_error_status(&e)
+            })?,
+    );
+    Ok(response)
+}
+
+/// Invalid subscriptions are unprocessable and only dead deliveries can
+/// be retried, anything else failing means there was no such row.
+fn webhook_error_status(e: &color_eyre::Report) -> StatusCode {
+    match e.downcast_ref::<WebhookError>() {
+        Some(WebhookError::InvalidUrl(_) | WebhookError::NoEventTypes) => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        Some(WebhookError::NotDead(_)) => StatusCode::CONFLICT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
@@ -1,14 +1,35 @@
 use async_trait::async_trait;
 use color_eyre::Result;
-use sqlx::postgres::PgPool;
-use std::env;
+use sqlx::postgres::{PgConnectOptions, PgPool};
+use sqlx::ConnectOptions;
+use std::{env, str::FromStr};
+use tokio::sync::OnceCell;
 
-use crate::services::{CustomerService, OrderService, ProductService, UserService};
+use crate::services::{
+    AddressService, CartService, CategoryService, CustomerService, DiscountService, ImageService,
+    JobQueue, OrderService, PaymentService, ProductService, RefundService, ShipmentService,
+    TaxService, UserService, VariantService, WebhookService,
+};
+
+static POOL: OnceCell<PgPool> = OnceCell::const_new();
 
 // TODO: use cfg_if to use different pools for sqlite and postgres
+/// Connects on the first call, every later call shares that pool.
+///
+/// sqlx pretty-prints the statements it logs before handing back the
+/// connection. For a multi-row insert of thousands of rows that takes
+/// minutes, with its transaction left open all along, so sqlx does not log
+/// statements. Services log the queries they build themselves.
 pub async fn get_pool() -> Result<PgPool> {
-    let database_url = env::var("DATABASE_URL")?;
-    Ok(PgPool::connect(&database_url).await?)
+    let pool = POOL
+        .get_or_try_init(|| async {
+            let database_url = env::var("DATABASE_URL")?;
+            let mut options = PgConnectOptions::from_str(&database_url)?;
+            options.disable_statement_logging();
+            Ok::<_, color_eyre::Report>(PgPool::connect_with(options).await?)
+        })
+        .await?;
+    Ok(pool.clone())
 }
 
 #[async_trait]
@@ -23,9 +44,21 @@ pub trait Clearable {
 
 pub struct DbMockData {
     pub product_service: ProductService,
+    pub category_service: CategoryService,
+    pub variant_service: VariantService,
+    pub image_service: ImageService,
     pub order_service: OrderService,
     pub customer_service: CustomerService,
+    pub discount_service: DiscountService,
+    pub tax_service: TaxService,
     pub user_service: UserService,
+    pub cart_service: CartService,
+    pub payment_service: PaymentService,
+    pub refund_service: RefundService,
+    pub address_service: AddressService,
+    pub shipment_service: ShipmentService,
+    pub webhook_service: WebhookService,
+    pub job_queue: JobQueue,
 }
 
 impl Default for DbMockData {
@@ -38,25 +71,56 @@ impl DbMockData {
     pub fn new() -> Self {
         DbMockData {
             product_service: ProductService {},
+            category_service: CategoryService {},
+            variant_service: VariantService {},
+            image_service: ImageService {},
             order_service: OrderService {},
             customer_service: CustomerService {},
+            discount_service: DiscountService {},
+            tax_service: TaxService {},
             user_service: UserService {},
+            cart_service: CartService {},
+            payment_service: PaymentService {},
+            refund_service: RefundService {},
+            address_service: AddressService {},
+            shipment_service: ShipmentService {},
+            webhook_service: WebhookService {},
+            job_queue: JobQueue {},
         }
     }
 
     pub async fn fill(&self) -> Result<()> {
         self.customer_service.fill_with_mocked_data().await?;
+        self.address_service.fill_with_mocked_data().await?;
+        self.category_service.fill_with_mocked_data().await?;
         self.product_service.fill_with_mocked_data().await?;
+        self.variant_service.fill_with_mocked_data().await?;
+        self.discount_service.fill_with_mocked_data().await?;
+        self.tax_service.fill_with_mocked_data().await?;
         self.order_service.fill_with_mocked_data().await?;
         self.user_service.fill_with_mocked_data().await?;
+        self.cart_service.fill_with_mocked_data().await?;
         Ok(())
     }
 
     pub async fn clear(&self) -> Result<()> {
+        self.cart_service.clear().await?;
+        self.refund_service.clear().await?;
+        self.shipment_service.clear().await?;
+        self.address_service.clear().await?;
+        self.payment_service.clear().await?;
         self.order_service.clear().await?;
+        self.discount_service.clear().await?;
+        self.tax_service.clear().await?;
+        self.user_service.clear().await?;
         self.customer_service.clear().await?;
+        self.variant_service.clear().await?;
+        self.image_service.clear().await?;
         self.product_service.clear().await?;
-        self.user_service.clear().await?;
+        self.category_service.clear().await?;
+        // last, as clearing orders and products queues events and jobs of its own
+        self.webhook_service.clear().await?;
+        self.job_queue.clear().await?;
         Ok(())
     }
 }
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use async_graphql::{dataloader::Loader, Error};
+use axum::http::StatusCode;
+use tracing::warn;
+
+use super::status_error;
+use crate::{
+    app::DbPool,
+    models::{Customer, OrderedProduct, Product},
+    services::{CustomerService, OrderService, ProductService},
+};
+
+/// Loads all products asked for while resolving a request in one query.
+pub struct ProductLoader {
+    pool: DbPool,
+}
+
+impl ProductLoader {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<i32> for ProductLoader {
+    type Value = Product;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Product>, Error> {
+        let products = ProductService::get_products_by_ids(&self.pool, ids)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                status_error(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+        Ok(products
+            .into_iter()
+            .map(|product| (product.id, product))
+            .collect())
+    }
+}
+
+/// Loads all customers asked for while resolving a request in one query.
+pub struct CustomerLoader {
+    pool: DbPool,
+}
+
+impl CustomerLoader {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<i32> for CustomerLoader {
+    type Value = Customer;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Customer>, Error> {
+        let customers = CustomerService::get_customers_by_ids(&self.pool, ids)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                status_error(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+        Ok(customers
+            .into_iter()
+            .map(|customer| (customer.id, customer))
+            .collect())
+    }
+}
+
+/// Loads the line items of all orders asked for while resolving a request in
+/// one query, keyed by order id.
+pub struct OrderProductsLoader {
+    pool: DbPool,
+}
+
+impl OrderProductsLoader {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<i32> for OrderProductsLoader {
+    type Value = Vec<OrderedProduct>;
+    type Error = Error;
+
+    async fn load(&self, order_ids: &[i32]) -> Result<HashMap<i32, Vec<OrderedProduct>>, Error> {
+        OrderService::get_ordered_products(&self.pool, order_ids)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                status_error(StatusCode::INTERNAL_SERVER_ERROR)
+            })
+    }
+}
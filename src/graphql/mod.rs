@@ -0,0 +1,123 @@
+mod loaders;
+mod mutation;
+mod query;
+
+use std::fmt::Display;
+
+use async_graphql::{
+    Context, EmptySubscription, Error, ErrorExtensions, Guard, OutputType, Schema, SimpleObject,
+};
+use axum::http::StatusCode;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+use crate::{
+    app::DbPool,
+    controllers::customer_controller,
+    models::{Claims, Customer, Order, Page, Product, Roles},
+};
+
+pub use loaders::{CustomerLoader, OrderProductsLoader, ProductLoader};
+pub use mutation::{MutationRoot, OrderInput, OrderItemInput};
+pub use query::QueryRoot;
+
+pub const GRAPHQL_PATH: &str = "/graphql";
+
+pub type ShopSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// The schema holds no data of its own. The pool, the claims of the user and
+/// fresh loaders are handed to every request, so nothing is cached between
+/// requests.
+pub fn build_schema() -> ShopSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription).finish()
+}
+
+/// One page of a list together with the number of all matching rows, as
+/// sent in `X-Total-Count` by REST.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "ProductPage", params(Product)))]
+#[graphql(concrete(name = "CustomerPage", params(Customer)))]
+#[graphql(concrete(name = "OrderPage", params(Order)))]
+pub struct ListPage<T: OutputType> {
+    pub items: Vec<T>,
+    pub total: i64,
+}
+
+impl<T: OutputType> From<Page<T>> for ListPage<T> {
+    fn from(page: Page<T>) -> Self {
+        Self {
+            items: page.items,
+            total: page.total,
+        }
+    }
+}
+
+/// Lets only users with one of the given roles resolve a field, the same way
+/// the role middlewares guard REST routes. Fields REST serves to both
+/// customers and admins take both roles.
+pub struct RoleGuard {
+    roles: Vec<Roles>,
+}
+
+impl RoleGuard {
+    pub fn new(role: Roles) -> Self {
+        Self { roles: vec![role] }
+    }
+
+    pub fn any(roles: impl IntoIterator<Item = Roles>) -> Self {
+        Self {
+            roles: roles.into_iter().collect(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let Some(claims) = ctx.data_opt::<Claims>() else {
+            return Err(status_error(StatusCode::UNAUTHORIZED));
+        };
+        if !self.roles.contains(&claims.role) {
+            warn!("{} is not one of {:?}", claims, self.roles);
+            return Err(status_error(StatusCode::FORBIDDEN));
+        }
+        Ok(())
+    }
+}
+
+/// Customer record of the user sending the request, looked up the first time
+/// a field needs it rather than once per order.
+#[derive(Default)]
+pub struct CallerCustomer(OnceCell<i32>);
+
+impl CallerCustomer {
+    pub(crate) async fn id(&self, pool: &DbPool, claims: &Claims) -> async_graphql::Result<i32> {
+        self.0
+            .get_or_try_init(|| customer_controller::get_customer_id(pool, claims))
+            .await
+            .copied()
+            .map_err(status_error)
+    }
+}
+
+/// Error carrying the status REST responds with in the same case, in the
+/// `status` extension.
+pub(crate) fn error_with_status(message: impl Display, status: StatusCode) -> Error {
+    Error::new(message.to_string()).extend_with(|_, extensions| {
+        extensions.set("status", status.as_u16());
+    })
+}
+
+pub(crate) fn status_error(status: StatusCode) -> Error {
+    error_with_status(status.canonical_reason().unwrap_or_default(), status)
+}
+
+/// Tells what went wrong only when it is the client's fault. Missing rows and
+/// server errors just get their status.
+pub(crate) fn service_error(e: &color_eyre::Report, status: StatusCode) -> Error {
+    if status.is_client_error() && status != StatusCode::NOT_FOUND {
+        error_with_status(e, status)
+    } else {
+        status_error(status)
+    }
+}
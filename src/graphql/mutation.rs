@@ -0,0 +1,180 @@
+use async_graphql::{Context, InputObject, Object, Result};
+use axum::http::StatusCode;
+use tracing::{info, warn};
+
+use super::{service_error, status_error, RoleGuard};
+use crate::{
+    app::DbPool,
+    controllers::{delete_error_status, order_controller, update_error_status},
+    models::*,
+    services::{CustomerService, OrderService, ProductService, RefundService},
+};
+
+/// Order to create, as in REST, but with one item per ordered product or
+/// variant.
+#[derive(InputObject, Debug)]
+pub struct OrderInput {
+    pub customer_id: i32,
+    #[graphql(default_with = "order_status::NEW.to_string()")]
+    pub status: String,
+    pub items: Vec<OrderItemInput>,
+    pub discount_code: Option<String>,
+    /// The customer's default shipping address if left out.
+    pub shipping_address_id: Option<i32>,
+}
+
+/// Products given without a variant are ordered in their default variant.
+#[derive(InputObject, Debug)]
+pub struct OrderItemInput {
+    pub product_id: i32,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+impl From<OrderInput> for OrderWithProducts {
+    fn from(input: OrderInput) -> Self {
+        let mut order = OrderWithProducts {
+            customer_id: input.customer_id,
+            status: input.status,
+            discount_code: input.discount_code,
+            shipping_address_id: input.shipping_address_id,
+            ..Default::default()
+        };
+        for item in input.items {
+            order.add_item(item.product_id, item.variant_id, item.quantity);
+        }
+        order
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Returns the id of the new product.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn create_product(&self, ctx: &Context<'_>, input: Product) -> Result<i32> {
+        let pool = ctx.data::<DbPool>()?;
+        info!("Received product: {:?}", input);
+        ProductService::create_product(pool, input).await.map_err(|e| {
+            warn!("{e}");
+            status_error(StatusCode::INTERNAL_SERVER_ERROR)
+        })
+    }
+
+    /// Fails when the product is no longer at `version`, if given, and
+    /// returns its new version.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn update_product(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: Product,
+        version: Option<i32>,
+    ) -> Result<i32> {
+        let pool = ctx.data::<DbPool>()?;
+        let product = Product { id, ..input };
+        ProductService::update_product(pool, product, version)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, update_error_status(&e))
+            })
+    }
+
+    /// Soft deletes the product unless `hard` is set.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn delete_product(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default)] hard: bool,
+    ) -> Result<bool> {
+        let pool = ctx.data::<DbPool>()?;
+        ProductService::delete_product(pool, id, hard)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, delete_error_status(&e))
+            })?;
+        Ok(true)
+    }
+
+    /// Returns the id of the new customer.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn create_customer(&self, ctx: &Context<'_>, input: Customer) -> Result<i32> {
+        let pool = ctx.data::<DbPool>()?;
+        info!("Received customer: {:?}", input);
+        CustomerService::create_customer(pool, input)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                status_error(StatusCode::INTERNAL_SERVER_ERROR)
+            })
+    }
+
+    /// Fails when the customer is no longer at `version`, if given, and
+    /// returns its new version.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn update_customer(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: Customer,
+        version: Option<i32>,
+    ) -> Result<i32> {
+        let pool = ctx.data::<DbPool>()?;
+        let customer = Customer { id, ..input };
+        CustomerService::update_customer(pool, customer, version)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, update_error_status(&e))
+            })
+    }
+
+    /// Soft deletes the customer unless `hard` is set.
+    #[graphql(guard = "RoleGuard::new(Roles::Admin)")]
+    async fn delete_customer(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default)] hard: bool,
+    ) -> Result<bool> {
+        let pool = ctx.data::<DbPool>()?;
+        CustomerService::delete_customer(pool, id, hard)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, delete_error_status(&e))
+            })?;
+        Ok(true)
+    }
+
+    /// Returns the id of the new order.
+    async fn create_order(&self, ctx: &Context<'_>, input: OrderInput) -> Result<i32> {
+        let pool = ctx.data::<DbPool>()?;
+        info!("Received order: {:?}", input);
+        OrderService::create_order(pool, input.into())
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, order_controller::order_error_status(&e))
+            })
+    }
+
+    /// Customers may cancel their own orders, until they are fulfilled.
+    #[graphql(guard = "RoleGuard::new(Roles::Customer)")]
+    async fn cancel_order(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        let pool = ctx.data::<DbPool>()?;
+        let claims = ctx.data::<Claims>()?;
+        order_controller::ensure_order_owner(pool, claims, id)
+            .await
+            .map_err(status_error)?;
+        RefundService::cancel_order(pool, id).await.map_err(|e| {
+            warn!("{e}");
+            service_error(&e, order_controller::order_error_status(&e))
+        })?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,179 @@
+use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Object, Result};
+use axum::http::StatusCode;
+use tracing::warn;
+
+use super::{
+    error_with_status, service_error, status_error, CallerCustomer, CustomerLoader, ListPage,
+    OrderProductsLoader, ProductLoader, RoleGuard,
+};
+use crate::{
+    app::DbPool,
+    controllers::list_error_status,
+    models::*,
+    services::{CustomerService, OrderService, ProductService},
+};
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn product(&self, ctx: &Context<'_>, id: i32) -> Result<Product> {
+        let pool = ctx.data::<DbPool>()?;
+        ProductService::get_product(pool, id).await.map_err(|e| {
+            warn!("{e}");
+            status_error(StatusCode::NOT_FOUND)
+        })
+    }
+
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        sort: Option<String>,
+        #[graphql(default)] filter: ProductFilter,
+    ) -> Result<ListPage<Product>> {
+        let pool = ctx.data::<DbPool>()?;
+        let list_params = list_params(limit, offset, sort)?;
+        let page = ProductService::get_products(pool, &list_params, &filter)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, list_error_status(&e))
+            })?;
+        Ok(page.into())
+    }
+
+    /// Best matches first, see the REST search for how products match.
+    async fn search_products(
+        &self,
+        ctx: &Context<'_>,
+        q: String,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        #[graphql(default)] filter: ProductFilter,
+    ) -> Result<ListPage<Product>> {
+        let pool = ctx.data::<DbPool>()?;
+        let list_params = list_params(limit, offset, None)?;
+        let page = ProductService::search_products(pool, &q, &list_params, &filter)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                match e.downcast_ref::<SearchError>() {
+                    Some(_) => service_error(&e, StatusCode::BAD_REQUEST),
+                    None => service_error(&e, list_error_status(&e)),
+                }
+            })?;
+        Ok(page.into())
+    }
+
+    #[graphql(guard = "RoleGuard::new(Roles::Customer)")]
+    async fn customer(&self, ctx: &Context<'_>, id: i32) -> Result<Customer> {
+        let pool = ctx.data::<DbPool>()?;
+        CustomerService::get_customer(pool, id).await.map_err(|e| {
+            warn!("{e}");
+            status_error(StatusCode::NOT_FOUND)
+        })
+    }
+
+    #[graphql(guard = "RoleGuard::new(Roles::Customer)")]
+    async fn customers(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        sort: Option<String>,
+        #[graphql(default)] filter: CustomerFilter,
+    ) -> Result<ListPage<Customer>> {
+        let pool = ctx.data::<DbPool>()?;
+        let list_params = list_params(limit, offset, sort)?;
+        let page = CustomerService::get_customers(pool, &list_params, &filter)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, list_error_status(&e))
+            })?;
+        Ok(page.into())
+    }
+
+    async fn order(&self, ctx: &Context<'_>, id: i32) -> Result<Order> {
+        let pool = ctx.data::<DbPool>()?;
+        OrderService::get_order(pool, id).await.map_err(|e| {
+            warn!("{e}");
+            status_error(StatusCode::NOT_FOUND)
+        })
+    }
+
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        sort: Option<String>,
+        #[graphql(default)] filter: OrderFilter,
+    ) -> Result<ListPage<Order>> {
+        let pool = ctx.data::<DbPool>()?;
+        let list_params = list_params(limit, offset, sort)?;
+        let page = OrderService::get_orders(pool, &list_params, &filter)
+            .await
+            .map_err(|e| {
+                warn!("{e}");
+                service_error(&e, list_error_status(&e))
+            })?;
+        Ok(ListPage {
+            items: page.items.into_iter().map(|listed| listed.order).collect(),
+            total: page.total,
+        })
+    }
+}
+
+/// Related rows are batched across all orders of a response, so listing
+/// orders with their customers and products takes a query per kind of row.
+#[ComplexObject]
+impl Order {
+    /// Admins see the customer of every order, customers only their own, so
+    /// customers have to filter lists of orders down to theirs to get it.
+    #[graphql(guard = "RoleGuard::any([Roles::Customer, Roles::Admin])")]
+    async fn customer(&self, ctx: &Context<'_>) -> Result<Option<Customer>> {
+        let claims = ctx.data::<Claims>()?;
+        if claims.role == Roles::Customer {
+            let pool = ctx.data::<DbPool>()?;
+            let customer_id = ctx.data::<CallerCustomer>()?.id(pool, claims).await?;
+            if customer_id != self.customer_id {
+                warn!("{} does not own order {}", claims, self.id);
+                return Err(status_error(StatusCode::FORBIDDEN));
+            }
+        }
+        ctx.data::<DataLoader<CustomerLoader>>()?
+            .load_one(self.customer_id)
+            .await
+    }
+
+    /// Line items with the name and SKU of what was ordered.
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<OrderedProduct>> {
+        let products = ctx
+            .data::<DataLoader<OrderProductsLoader>>()?
+            .load_one(self.id)
+            .await?;
+        Ok(products.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl OrderedProduct {
+    /// The product as it is now, which may have been deleted since.
+    async fn product(&self, ctx: &Context<'_>) -> Result<Option<Product>> {
+        ctx.data::<DataLoader<ProductLoader>>()?
+            .load_one(self.product_id)
+            .await
+    }
+}
+
+fn list_params(
+    limit: Option<i64>,
+    offset: Option<i64>,
+    sort: Option<String>,
+) -> Result<ListParams> {
+    ListParams::page(limit, offset, sort)
+        .map_err(|e| error_with_status(e, StatusCode::BAD_REQUEST))
+}
@@ -0,0 +1,33 @@
+use axum::{extract::State, Json};
+use tonic::{Request, Response, Status};
+
+use super::proto::{auth_server::Auth, AuthorizeRequest, AuthorizeResponse};
+use crate::{app::DbPool, controllers::user_controller, models::RequestUser};
+
+pub struct AuthRpc {
+    pool: DbPool,
+}
+
+impl AuthRpc {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[tonic::async_trait]
+impl Auth for AuthRpc {
+    /// Hands out the same tokens as REST, to be sent as `authorization`
+    /// metadata.
+    async fn authorize(
+        &self,
+        request: Request<AuthorizeRequest>,
+    ) -> Result<Response<AuthorizeResponse>, Status> {
+        let AuthorizeRequest { name, password } = request.into_inner();
+        let credentials = Json(RequestUser { name, password });
+        let Json(token) = user_controller::authorize(State(self.pool.clone()), credentials).await?;
+        Ok(Response::new(AuthorizeResponse {
+            token: token.token,
+            token_type: token.token_type,
+        }))
+    }
+}
@@ -0,0 +1,121 @@
+use axum::http::StatusCode;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use super::{
+    proto::{
+        self, customers_server::Customers, CreateCustomerRequest, CreateCustomerResponse,
+        DeleteCustomerRequest, DeleteCustomerResponse, GetCustomerRequest, ListCustomersRequest,
+        ListCustomersResponse,
+    },
+    require_role, service_status,
+};
+use crate::{
+    app::DbPool,
+    controllers::{delete_error_status, list_error_status},
+    models::{default_region, Customer, CustomerFilter, ListParams, Roles},
+    services::CustomerService,
+};
+
+pub struct CustomerRpc {
+    pool: DbPool,
+}
+
+impl CustomerRpc {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[tonic::async_trait]
+impl Customers for CustomerRpc {
+    async fn get_customer(
+        &self,
+        request: Request<GetCustomerRequest>,
+    ) -> Result<Response<proto::Customer>, Status> {
+        require_role(&request, Roles::Customer)?;
+        let GetCustomerRequest { id } = request.into_inner();
+        let customer = CustomerService::get_customer(&self.pool, id)
+            .await
+            .map_err(|e| service_status(&e, StatusCode::NOT_FOUND))?;
+        Ok(Response::new(customer.into()))
+    }
+
+    async fn list_customers(
+        &self,
+        request: Request<ListCustomersRequest>,
+    ) -> Result<Response<ListCustomersResponse>, Status> {
+        require_role(&request, Roles::Customer)?;
+        let request = request.into_inner();
+        let list_params = ListParams::page(request.limit, request.offset, request.sort)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let filter = CustomerFilter {
+            name: request.name,
+            region: request.region,
+            ..Default::default()
+        };
+        let page = CustomerService::get_customers(&self.pool, &list_params, &filter)
+            .await
+            .map_err(|e| service_status(&e, list_error_status(&e)))?;
+        Ok(Response::new(ListCustomersResponse {
+            customers: page.items.into_iter().map(Into::into).collect(),
+            total: page.total,
+        }))
+    }
+
+    async fn create_customer(
+        &self,
+        request: Request<CreateCustomerRequest>,
+    ) -> Result<Response<CreateCustomerResponse>, Status> {
+        require_role(&request, Roles::Admin)?;
+        let customer = request
+            .into_inner()
+            .customer
+            .ok_or_else(|| Status::invalid_argument("Missing customer"))?;
+        info!("Received customer: {:?}", customer);
+
+        let id = CustomerService::create_customer(&self.pool, customer.into())
+            .await
+            .map_err(|e| service_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+        Ok(Response::new(CreateCustomerResponse { id }))
+    }
+
+    async fn delete_customer(
+        &self,
+        request: Request<DeleteCustomerRequest>,
+    ) -> Result<Response<DeleteCustomerResponse>, Status> {
+        require_role(&request, Roles::Admin)?;
+        let DeleteCustomerRequest { id, hard } = request.into_inner();
+        CustomerService::delete_customer(&self.pool, id, hard)
+            .await
+            .map_err(|e| service_status(&e, delete_error_status(&e)))?;
+        Ok(Response::new(DeleteCustomerResponse {}))
+    }
+}
+
+impl From<Customer> for proto::Customer {
+    fn from(customer: Customer) -> Self {
+        Self {
+            id: customer.id,
+            name: customer.name,
+            address: customer.address,
+            region: customer.region,
+            version: customer.version,
+        }
+    }
+}
+
+impl From<proto::Customer> for Customer {
+    fn from(customer: proto::Customer) -> Self {
+        let region = match customer.region.is_empty() {
+            true => default_region(),
+            false => customer.region,
+        };
+        Customer {
+            name: customer.name,
+            address: customer.address,
+            region,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,149 @@
+mod auth;
+mod customers;
+mod orders;
+mod products;
+
+use axum::{http::StatusCode, Router};
+use tonic::{server::NamedService, Code, Request, Status};
+use tracing::warn;
+
+use crate::{
+    app::DbPool,
+    models::{AuthError, Claims, Currency, Money, Roles},
+};
+
+pub use auth::AuthRpc;
+pub use customers::CustomerRpc;
+pub use orders::OrderRpc;
+pub use products::ProductRpc;
+
+/// Messages and services generated from `proto/shop.proto`.
+pub mod proto {
+    tonic::include_proto!("shop.v1");
+}
+
+use proto::{
+    auth_server::AuthServer, customers_server::CustomersServer, orders_server::OrdersServer,
+    products_server::ProductsServer,
+};
+
+/// gRPC services, served next to the REST API on the same port. gRPC paths
+/// are named after the service, e.g. `/shop.v1.Products/GetProduct`, so they
+/// never clash with REST routes. Clients have to speak HTTP/2 right away,
+/// as gRPC clients do.
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route_service(
+            &service_path::<AuthServer<AuthRpc>>(),
+            AuthServer::new(AuthRpc::new(pool.clone())),
+        )
+        .route_service(
+            &service_path::<ProductsServer<ProductRpc>>(),
+            ProductsServer::new(ProductRpc::new(pool.clone())),
+        )
+        .route_service(
+            &service_path::<CustomersServer<CustomerRpc>>(),
+            CustomersServer::new(CustomerRpc::new(pool.clone())),
+        )
+        .route_service(
+            &service_path::<OrdersServer<OrderRpc>>(),
+            OrdersServer::new(OrderRpc::new(pool)),
+        )
+}
+
+fn service_path<S: NamedService>() -> String {
+    format!("/{}/*method", S::NAME)
+}
+
+/// Reads the claims of the bearer token in the `authorization` metadata and
+/// lets only users with the given role through, like the role middlewares
+/// of REST routes.
+// returns the same `Status` as every other gRPC call
+#[allow(clippy::result_large_err)]
+pub(crate) fn require_role<T>(request: &Request<T>, role: Roles) -> Result<Claims, Status> {
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthError::InvalidToken)?;
+    let claims = Claims::decode(token)?;
+    if claims.role != role {
+        warn!("{} is not {:?}", claims, role);
+        return Err(Status::permission_denied("Forbidden"));
+    }
+    Ok(claims)
+}
+
+impl From<AuthError> for Status {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::WrongCredentials => Status::unauthenticated("Wrong credentials"),
+            AuthError::MissingCredentials => Status::invalid_argument("Missing credentials"),
+            AuthError::TokenCreation => Status::internal("Token creation error"),
+            AuthError::InvalidToken => Status::unauthenticated("Invalid token"),
+        }
+    }
+}
+
+/// Status matching the one REST responds with in the same case.
+pub(crate) fn status(status: StatusCode) -> Status {
+    let code = match status {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::FORBIDDEN => Code::PermissionDenied,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => Code::FailedPrecondition,
+        _ => Code::Internal,
+    };
+    Status::new(code, status.canonical_reason().unwrap_or_default())
+}
+
+/// Tells what went wrong only when it is the client's fault. Missing rows and
+/// server errors just get their status.
+pub(crate) fn service_status(e: &color_eyre::Report, code: StatusCode) -> Status {
+    warn!("{e}");
+    if code.is_client_error() && code != StatusCode::NOT_FOUND {
+        Status::new(status(code).code(), e.to_string())
+    } else {
+        status(code)
+    }
+}
+
+impl From<Currency> for proto::Currency {
+    fn from(currency: Currency) -> Self {
+        match currency {
+            Currency::Pln => proto::Currency::Pln,
+            Currency::Eur => proto::Currency::Eur,
+            Currency::Usd => proto::Currency::Usd,
+            Currency::Gbp => proto::Currency::Gbp,
+        }
+    }
+}
+
+impl From<Money> for proto::Money {
+    fn from(money: Money) -> Self {
+        Self {
+            cents: money.cents,
+            currency: proto::Currency::from(money.currency).into(),
+        }
+    }
+}
+
+impl TryFrom<proto::Money> for Money {
+    type Error = Status;
+
+    fn try_from(money: proto::Money) -> Result<Self, Status> {
+        let currency = match proto::Currency::try_from(money.currency) {
+            Ok(proto::Currency::Pln) => Currency::Pln,
+            Ok(proto::Currency::Eur) => Currency::Eur,
+            Ok(proto::Currency::Usd) => Currency::Usd,
+            Ok(proto::Currency::Gbp) => Currency::Gbp,
+            Ok(proto::Currency::Unspecified) | Err(_) => {
+                let message = format!("Unknown currency {}", money.currency);
+                return Err(Status::invalid_argument(message));
+            }
+        };
+        Ok(Money::new(money.cents, currency))
+    }
+}
@@ -0,0 +1,153 @@
+use axum::http::StatusCode;
+use chrono::NaiveDateTime;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use super::{
+    proto::{
+        self, orders_server::Orders, CancelOrderRequest, CancelOrderResponse,
+        CreateOrderRequest, CreateOrderResponse, GetOrderRequest, ListOrdersRequest,
+        ListOrdersResponse,
+    },
+    require_role, service_status, status,
+};
+use crate::{
+    app::DbPool,
+    controllers::{list_error_status, order_controller},
+    models::*,
+    services::{OrderService, RefundService},
+};
+
+pub struct OrderRpc {
+    pool: DbPool,
+}
+
+impl OrderRpc {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[tonic::async_trait]
+impl Orders for OrderRpc {
+    async fn get_order(
+        &self,
+        request: Request<GetOrderRequest>,
+    ) -> Result<Response<proto::Order>, Status> {
+        let GetOrderRequest { id } = request.into_inner();
+        let order = OrderService::get_order(&self.pool, id)
+            .await
+            .map_err(|e| service_status(&e, StatusCode::NOT_FOUND))?;
+        let mut lines = OrderService::get_ordered_products(&self.pool, &[id])
+            .await
+            .map_err(|e| service_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+        Ok(Response::new(to_proto_order(
+            order,
+            lines.remove(&id).unwrap_or_default(),
+        )))
+    }
+
+    /// Line items come with the orders, in the same query.
+    async fn list_orders(
+        &self,
+        request: Request<ListOrdersRequest>,
+    ) -> Result<Response<ListOrdersResponse>, Status> {
+        let request = request.into_inner();
+        let list_params = ListParams::page(request.limit, request.offset, request.sort)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let filter = OrderFilter {
+            status: request.status,
+            customer_id: request.customer_id,
+            include: Some(OrderInclude::Products),
+            ..Default::default()
+        };
+        let page = OrderService::get_orders(&self.pool, &list_params, &filter)
+            .await
+            .map_err(|e| service_status(&e, list_error_status(&e)))?;
+        Ok(Response::new(ListOrdersResponse {
+            orders: page
+                .items
+                .into_iter()
+                .map(|listed| {
+                    let lines = listed.products.map(|products| products.0);
+                    to_proto_order(listed.order, lines.unwrap_or_default())
+                })
+                .collect(),
+            total: page.total,
+        }))
+    }
+
+    async fn create_order(
+        &self,
+        request: Request<CreateOrderRequest>,
+    ) -> Result<Response<CreateOrderResponse>, Status> {
+        let request = request.into_inner();
+        info!("Received order: {:?}", request);
+
+        let status = match request.status.is_empty() {
+            true => order_status::NEW.to_string(),
+            false => request.status,
+        };
+        let mut order = OrderWithProducts {
+            customer_id: request.customer_id,
+            status,
+            discount_code: request.discount_code,
+            shipping_address_id: request.shipping_address_id,
+            ..Default::default()
+        };
+        for item in request.items {
+            order.add_item(item.product_id, item.variant_id, item.quantity);
+        }
+        let id = OrderService::create_order(&self.pool, order)
+            .await
+            .map_err(|e| service_status(&e, order_controller::order_error_status(&e)))?;
+        Ok(Response::new(CreateOrderResponse { id }))
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<CancelOrderRequest>,
+    ) -> Result<Response<CancelOrderResponse>, Status> {
+        let claims = require_role(&request, Roles::Customer)?;
+        let CancelOrderRequest { id } = request.into_inner();
+        order_controller::ensure_order_owner(&self.pool, &claims, id)
+            .await
+            .map_err(status)?;
+        RefundService::cancel_order(&self.pool, id)
+            .await
+            .map_err(|e| service_status(&e, order_controller::order_error_status(&e)))?;
+        Ok(Response::new(CancelOrderResponse {}))
+    }
+}
+
+fn to_proto_order(order: Order, lines: Vec<OrderedProduct>) -> proto::Order {
+    proto::Order {
+        id: order.id,
+        customer_id: order.customer_id,
+        status: order.status,
+        created_at: Some(to_timestamp(order.created_at)),
+        version: order.version,
+        lines: lines.into_iter().map(Into::into).collect(),
+    }
+}
+
+fn to_timestamp(date_time: NaiveDateTime) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: date_time.timestamp(),
+        nanos: date_time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<OrderedProduct> for proto::OrderLine {
+    fn from(line: OrderedProduct) -> Self {
+        Self {
+            product_id: line.product_id,
+            variant_id: line.variant_id,
+            name: line.name,
+            sku: line.sku,
+            quantity: line.quantity,
+            unit_price: Some(line.unit_price.into()),
+            refunded_quantity: line.refunded_quantity,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use axum::http::StatusCode;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use super::{
+    proto::{
+        self, products_server::Products, CreateProductRequest, CreateProductResponse,
+        DeleteProductRequest, DeleteProductResponse, GetProductRequest, ListProductsRequest,
+        ListProductsResponse,
+    },
+    require_role, service_status,
+};
+use crate::{
+    app::DbPool,
+    controllers::{delete_error_status, list_error_status},
+    models::{default_tax_category, ListParams, Product, ProductFilter, Roles},
+    services::ProductService,
+};
+
+pub struct ProductRpc {
+    pool: DbPool,
+}
+
+impl ProductRpc {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[tonic::async_trait]
+impl Products for ProductRpc {
+    async fn get_product(
+        &self,
+        request: Request<GetProductRequest>,
+    ) -> Result<Response<proto::Product>, Status> {
+        let GetProductRequest { id } = request.into_inner();
+        let product = ProductService::get_product(&self.pool, id)
+            .await
+            .map_err(|e| service_status(&e, StatusCode::NOT_FOUND))?;
+        Ok(Response::new(product.into()))
+    }
+
+    async fn list_products(
+        &self,
+        request: Request<ListProductsRequest>,
+    ) -> Result<Response<ListProductsResponse>, Status> {
+        let request = request.into_inner();
+        let list_params = ListParams::page(request.limit, request.offset, request.sort)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let filter = ProductFilter {
+            min_price: request.min_price,
+            max_price: request.max_price,
+            available: request.available,
+            category_id: request.category_id,
+            ..Default::default()
+        };
+        let page = ProductService::get_products(&self.pool, &list_params, &filter)
+            .await
+            .map_err(|e| service_status(&e, list_error_status(&e)))?;
+        Ok(Response::new(ListProductsResponse {
+            products: page.items.into_iter().map(Into::into).collect(),
+            total: page.total,
+        }))
+    }
+
+    async fn create_product(
+        &self,
+        request: Request<CreateProductRequest>,
+    ) -> Result<Response<CreateProductResponse>, Status> {
+        require_role(&request, Roles::Admin)?;
+        let product = request
+            .into_inner()
+            .product
+            .ok_or_else(|| Status::invalid_argument("Missing product"))?;
+        info!("Received product: {:?}", product);
+
+        let id = ProductService::create_product(&self.pool, product.try_into()?)
+            .await
+            .map_err(|e| service_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+        Ok(Response::new(CreateProductResponse { id }))
+    }
+
+    async fn delete_product(
+        &self,
+        request: Request<DeleteProductRequest>,
+    ) -> Result<Response<DeleteProductResponse>, Status> {
+        require_role(&request, Roles::Admin)?;
+        let DeleteProductRequest { id, hard } = request.into_inner();
+        ProductService::delete_product(&self.pool, id, hard)
+            .await
+            .map_err(|e| service_status(&e, delete_error_status(&e)))?;
+        Ok(Response::new(DeleteProductResponse {}))
+    }
+}
+
+impl From<Product> for proto::Product {
+    fn from(product: Product) -> Self {
+        Self {
+            id: product.id,
+            name: product.name,
+            price: Some(product.price.into()),
+            available: product.available,
+            tax_category: product.tax_category,
+            stock: product.stock,
+            description: product.description,
+            tags: product.tags,
+            category_id: product.category_id,
+            version: product.version,
+        }
+    }
+}
+
+impl TryFrom<proto::Product> for Product {
+    type Error = Status;
+
+    fn try_from(product: proto::Product) -> Result<Self, Status> {
+        let price = product
+            .price
+            .ok_or_else(|| Status::invalid_argument("Missing price"))?;
+        let tax_category = match product.tax_category.is_empty() {
+            true => default_tax_category(),
+            false => product.tax_category,
+        };
+        Ok(Product {
+            name: product.name,
+            price: price.try_into()?,
+            available: product.available,
+            tax_category,
+            stock: product.stock,
+            description: product.description,
+            tags: product.tags,
+            category_id: product.category_id,
+            ..Default::default()
+        })
+    }
+}
@@ -1,7 +1,10 @@
 pub mod controllers;
 pub mod db_actions;
+pub mod graphql;
+pub mod grpc;
 pub mod models;
 pub mod services;
 pub mod setup;
 pub mod app;
 pub mod middleware;
+pub mod openapi;
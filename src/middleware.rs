@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use crate::models::{Claims, Roles};
 use axum::{
-    http::{Request, StatusCode},
+    http::{header, HeaderValue, Request, StatusCode, Uri},
     middleware::Next,
     response::Response,
 };
@@ -53,3 +53,72 @@ where
     }
     Ok(next.run(request).await)
 }
+
+pub const API_VERSIONS: [u32; 1] = [1];
+const VERSIONED_MEDIA_TYPE: (&str, &str) = ("application/vnd.shop.v", "+json");
+
+/// Serves unversioned `/api` paths from the version named in the `Accept`
+/// header, e.g. `application/vnd.shop.v1+json`. Versions in the path win,
+/// and requests asking for no version at all get the deprecated routes.
+/// Has to run before routing, so it wraps the whole router.
+pub async fn middleware_negotiate_version<B>(
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, StatusCode> {
+    let Some(rest) = request.uri().path().strip_prefix("/api/") else {
+        return Ok(next.run(request).await);
+    };
+    let versioned = rest
+        .split('/')
+        .next()
+        .and_then(|segment| segment.strip_prefix('v'))
+        .is_some_and(|version| version.parse::<u32>().is_ok());
+    if versioned {
+        return Ok(next.run(request).await);
+    }
+
+    let version = request
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter_map(|media_type| {
+            let media_type = media_type.split(';').next()?.trim();
+            media_type
+                .strip_prefix(VERSIONED_MEDIA_TYPE.0)?
+                .strip_suffix(VERSIONED_MEDIA_TYPE.1)
+        })
+        .next()
+        .map(str::to_string);
+    let Some(version) = version else {
+        return Ok(next.run(request).await);
+    };
+    if !version
+        .parse()
+        .is_ok_and(|version: u32| API_VERSIONS.contains(&version))
+    {
+        warn!("Requested unknown API version {}", version);
+        return Err(StatusCode::NOT_ACCEPTABLE);
+    }
+
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("/api/v{}/{}?{}", version, rest, query),
+        None => format!("/api/v{}/{}", version, rest),
+    };
+    *request.uri_mut() = Uri::try_from(path_and_query).map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(next.run(request).await)
+}
+
+/// Marks responses of the unversioned routes as deprecated and points
+/// clients at the version replacing them.
+pub async fn middleware_deprecated<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.append(
+        header::LINK,
+        HeaderValue::from_static("</api/v1>; rel=\"successor-version\""),
+    );
+    response
+}
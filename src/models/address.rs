@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "address_kind", rename_all = "lowercase")]
+pub enum AddressKind {
+    Billing,
+    Shipping,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Address {
+    #[serde(default)]
+    pub id: i32,
+    #[serde(default)]
+    pub customer_id: i32,
+    pub kind: AddressKind,
+    pub recipient: String,
+    pub line1: String,
+    #[serde(default)]
+    pub line2: Option<String>,
+    pub city: String,
+    pub postal_code: String,
+    /// ISO 3166-1 alpha-2 country code, e.g. `PL`.
+    pub country: String,
+    /// Default addresses are used when an order does not name one.
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// Address an order is shipped to, as it was when the order was placed.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct ShippingAddress {
+    pub recipient: String,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+}
+
+impl From<Address> for ShippingAddress {
+    fn from(address: Address) -> Self {
+        Self {
+            recipient: address.recipient,
+            line1: address.line1,
+            line2: address.line2,
+            city: address.city,
+            postal_code: address.postal_code,
+            country: address.country,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AddressError {
+    UnknownAddress(i32),
+    NotShippingAddress(i32),
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::UnknownAddress(id) => {
+                write!(f, "Address with id {} does not belong to the customer", id)
+            }
+            AddressError::NotShippingAddress(id) => {
+                write!(f, "Address with id {} is not a shipping address", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{default_region, default_tax_category, Currency, Customer, Money, Product};
+
+/// Imports are read into memory whole, so they get a limit of their own.
+pub const MAX_IMPORT_BYTES: usize = 32 * 1024 * 1024;
+
+/// File format of bulk imports and exports.
+#[derive(Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkFormat {
+    /// Comma separated values with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl BulkFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "text/csv",
+            BulkFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "csv",
+            BulkFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// Parameters such as `charset` are ignored.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "text/csv" => Some(BulkFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(BulkFormat::Ndjson)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// Validates and counts the rows without keeping any of them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, IntoParams, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: BulkFormat,
+}
+
+/// Row of a product import or export. Flat, so the same columns work in CSV
+/// and JSON Lines.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProductRecord {
+    /// Updates the product with this id, creates a new product when empty.
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub name: String,
+    pub price_cents: i64,
+    pub currency: Currency,
+    pub available: bool,
+    #[serde(default = "default_tax_category")]
+    pub tax_category: String,
+    #[serde(default)]
+    pub stock: Option<i32>,
+    #[serde(default)]
+    pub description: String,
+    /// Tags separated by `|`.
+    #[serde(default)]
+    pub tags: String,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+}
+
+impl From<Product> for ProductRecord {
+    fn from(product: Product) -> Self {
+        ProductRecord {
+            id: Some(product.id),
+            name: product.name,
+            price_cents: product.price.cents,
+            currency: product.price.currency,
+            available: product.available,
+            tax_category: product.tax_category,
+            stock: product.stock,
+            description: product.description,
+            tags: product.tags.join("|"),
+            category_id: product.category_id,
+        }
+    }
+}
+
+impl From<ProductRecord> for Product {
+    /// New products get id 0 until they are inserted.
+    fn from(record: ProductRecord) -> Self {
+        Product {
+            id: record.id.unwrap_or_default(),
+            name: record.name,
+            price: Money::new(record.price_cents, record.currency),
+            available: record.available,
+            tax_category: record.tax_category,
+            stock: record.stock,
+            description: record.description,
+            tags: record
+                .tags
+                .split('|')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            category_id: record.category_id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Row of a customer import or export.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CustomerRecord {
+    /// Updates the customer with this id, creates a new customer when empty.
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub name: String,
+    pub address: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+}
+
+impl From<Customer> for CustomerRecord {
+    fn from(customer: Customer) -> Self {
+        CustomerRecord {
+            id: Some(customer.id),
+            name: customer.name,
+            address: customer.address,
+            region: customer.region,
+        }
+    }
+}
+
+impl From<CustomerRecord> for Customer {
+    /// New customers get id 0 until they are inserted.
+    fn from(record: CustomerRecord) -> Self {
+        Customer {
+            id: record.id.unwrap_or_default(),
+            name: record.name,
+            address: record.address,
+            region: record.region,
+            ..Default::default()
+        }
+    }
+}
+
+/// Row that was left out of an import. Rows are counted from 1, without the
+/// CSV header and blank lines.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+/// Outcome of an import. Valid rows are kept even when others fail.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub errors: Vec<RowError>,
+}
@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Money;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct CartItem {
+    pub product_id: i32,
+    /// Variant of the product, its default variant if left out.
+    #[serde(default)]
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+/// New quantity of a product already in the cart.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct CartItemQuantity {
+    pub quantity: i32,
+}
+
+/// Cart line priced with the current price of the product, unlike order
+/// lines which keep the price from the moment the order was placed.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct CartLine {
+    pub product_id: i32,
+    pub variant_id: i32,
+    pub sku: String,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub line_total: Money,
+    pub available: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct Cart {
+    pub customer_id: i32,
+    pub lines: Vec<CartLine>,
+    pub subtotal: Option<Money>,
+    /// Whether every variant in the cart can currently be ordered.
+    pub available: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Default)]
+pub struct CheckoutRequest {
+    #[serde(default)]
+    pub discount_code: Option<String>,
+    #[serde(default)]
+    pub shipping_address_id: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum CartError {
+    InvalidQuantity,
+    EmptyCart,
+    ProductUnavailable(i32),
+    NotInCart(i32),
+}
+
+impl Display for CartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CartError::InvalidQuantity => write!(f, "Quantity must be positive"),
+            CartError::EmptyCart => write!(f, "Cart is empty"),
+            CartError::ProductUnavailable(product_id) => {
+                write!(f, "Product with id {} is not available", product_id)
+            }
+            CartError::NotInCart(product_id) => {
+                write!(f, "Product with id {} is not in the cart", product_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartError {}
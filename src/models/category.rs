@@ -0,0 +1,36 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    /// Category this one is nested in, `None` for top level categories.
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum CategoryError {
+    Cycle(i32),
+    NotEmpty(i32),
+}
+
+impl Display for CategoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CategoryError::Cycle(id) => {
+                write!(f, "Category with id {} cannot be nested in itself", id)
+            }
+            CategoryError::NotEmpty(id) => write!(
+                f,
+                "Category with id {} still has products or subcategories",
+                id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CategoryError {}
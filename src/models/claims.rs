@@ -29,6 +29,14 @@ impl Claims {
     pub fn new(name: String, role: Roles, exp: usize) -> Self {
         Self { name, role, exp }
     }
+
+    /// Reads the claims of a bearer token, which has to be signed with our
+    /// key and not expired.
+    pub fn decode(token: &str) -> Result<Self, AuthError> {
+        let token_data = decode::<Claims>(token, &KEYS.decoding, &Validation::default())
+            .map_err(|_| AuthError::InvalidToken)?;
+        Ok(token_data.claims)
+    }
 }
 
 #[async_trait]
@@ -45,9 +53,6 @@ where
             .await
             .map_err(|_| AuthError::InvalidToken)?;
         // Decode the user data
-        let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(token_data.claims)
+        Self::decode(bearer.token())
     }
 }
@@ -1,8 +1,31 @@
+use async_graphql::{InputObject, SimpleObject};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Default)]
+use super::default_region;
+
+#[derive(
+    Serialize, Deserialize, ToSchema, SimpleObject, InputObject, Debug, Clone, PartialEq, Eq,
+    Hash, Default, sqlx::FromRow,
+)]
+#[graphql(input_name = "CustomerInput")]
 pub struct Customer {
+    #[graphql(skip_input)]
     pub id: i32,
     pub name: String,
     pub address: String,
+    /// Region the customer is taxed in when their orders have no shipping
+    /// address to tell it, e.g. `PL`.
+    #[serde(default = "default_region")]
+    #[graphql(default_with = "default_region()")]
+    pub region: String,
+    /// When the customer was soft deleted.
+    #[serde(default, skip_deserializing)]
+    #[graphql(skip_input)]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Bumped on every write, sent as the ETag of the row.
+    #[serde(default, skip_deserializing)]
+    #[graphql(skip_input)]
+    pub version: i32,
 }
@@ -0,0 +1,30 @@
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum DeleteError {
+    /// The row is still referenced by rows of the named kind, so it can
+    /// only be soft deleted.
+    InUse {
+        resource: &'static str,
+        id: i32,
+        referenced_by: &'static str,
+    },
+}
+
+impl Display for DeleteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteError::InUse {
+                resource,
+                id,
+                referenced_by,
+            } => write!(
+                f,
+                "{} with id {} is still referenced by {} and cannot be hard deleted",
+                resource, id, referenced_by
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeleteError {}
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Money;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "discount_kind", rename_all = "lowercase")]
+pub enum DiscountKind {
+    Percentage,
+    Fixed,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct DiscountCode {
+    pub id: i32,
+    pub code: String,
+    pub kind: DiscountKind,
+    pub percent: Option<i32>,
+    pub amount: Option<Money>,
+    pub valid_from: Option<NaiveDateTime>,
+    pub valid_until: Option<NaiveDateTime>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub min_order_value: Option<Money>,
+    /// Products the discount is restricted to, empty means every product.
+    #[serde(default)]
+    pub product_ids: Vec<i32>,
+}
+
+/// Reasons for which a discount code cannot be applied to an order.
+#[derive(Debug)]
+pub enum DiscountError {
+    UnknownCode,
+    NotYetValid,
+    Expired,
+    UsageLimitReached,
+    CustomerUsageLimitReached,
+    MinOrderValueNotReached,
+    NoEligibleProducts,
+    CurrencyMismatch,
+}
+
+impl Display for DiscountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            DiscountError::UnknownCode => "Unknown discount code",
+            DiscountError::NotYetValid => "Discount code is not valid yet",
+            DiscountError::Expired => "Discount code has expired",
+            DiscountError::UsageLimitReached => "Discount code usage limit reached",
+            DiscountError::CustomerUsageLimitReached => {
+                "Discount code usage limit reached for this customer"
+            }
+            DiscountError::MinOrderValueNotReached => "Minimum order value not reached",
+            DiscountError::NoEligibleProducts => "No products in order eligible for discount",
+            DiscountError::CurrencyMismatch => "Discount currency differs from order currency",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for DiscountError {}
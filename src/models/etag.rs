@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// Strong entity tag of a row version, e.g. `"3"`.
+pub fn etag(version: i32) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("quoted numbers are valid headers")
+}
+
+/// Version an update is made against, taken from the `If-Match` header which
+/// every update has to send. `*` matches any version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfMatch(pub Option<i32>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    /// Clients hold a single version of a row, so anything but one strong
+    /// entity tag of ours or `*` cannot match and fails the precondition.
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts
+            .headers
+            .get(header::IF_MATCH)
+            .ok_or(StatusCode::PRECONDITION_REQUIRED)?
+            .to_str()
+            .map_err(|_| StatusCode::PRECONDITION_FAILED)?
+            .trim();
+        if value == "*" {
+            return Ok(IfMatch(None));
+        }
+        value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .and_then(|version| version.parse().ok())
+            .map(|version| IfMatch(Some(version)))
+            .ok_or(StatusCode::PRECONDITION_FAILED)
+    }
+}
+
+/// Entity tags from the `If-None-Match` header, compared weakly as reads
+/// only need the same content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IfNoneMatch(Vec<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let tags = parts
+            .headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().trim_start_matches("W/").to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        Ok(IfNoneMatch(tags))
+    }
+}
+
+impl IfNoneMatch {
+    pub fn matches(&self, etag: &HeaderValue) -> bool {
+        self.0.iter().any(|tag| tag == "*" || tag.as_bytes() == etag.as_bytes())
+    }
+
+    /// Responds with the body and its ETag, or with 304 Not Modified when the
+    /// client already holds that representation.
+    pub fn respond<T: Serialize>(&self, etag: HeaderValue, body: T) -> Response {
+        if self.matches(&etag) {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+        }
+        ([(header::ETAG, etag)], Json(body)).into_response()
+    }
+}
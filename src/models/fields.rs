@@ -0,0 +1,156 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{etag, Customer, Page, Product};
+
+/// Query parameter `Fields` are read from.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FieldsQuery {
+    /// Members to keep in the response, e.g. `id,name`.
+    fields: Option<String>,
+}
+
+/// Members to keep in a response, from `?fields=id,name`. Applies to the
+/// resource itself, or to every item of a list. Resources are sent whole
+/// when no fields are asked for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(Option<Vec<String>>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Fields
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(raw) = Query::<FieldsQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Fields(raw.fields.map(|fields| {
+            fields
+                .split(',')
+                .map(|field| field.trim().to_string())
+                .filter(|field| !field.is_empty())
+                .collect()
+        })))
+    }
+}
+
+impl Fields {
+    /// Serializes `resource` with only the requested members. Fields the
+    /// resource does not have are ignored.
+    pub fn select<T: Serialize>(&self, resource: &T) -> Result<Value, StatusCode> {
+        let mut value =
+            serde_json::to_value(resource).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let (Some(fields), Value::Object(members)) = (&self.0, &mut value) {
+            members.retain(|member, _| fields.contains(member));
+        }
+        Ok(value)
+    }
+
+    /// Entity tag of a resource at `version` as `selected`. A trimmed body is
+    /// a representation of its own, so it is tagged with the members it kept,
+    /// e.g. `"3;id;name"`, rather than with the tag of the whole resource.
+    pub fn etag(&self, version: i32, selected: &Value) -> HeaderValue {
+        match (&self.0, selected) {
+            (Some(_), Value::Object(members)) => {
+                let members: Vec<&str> = members.keys().map(String::as_str).collect();
+                HeaderValue::from_str(&format!("\"{};{}\"", version, members.join(";")))
+                    .expect("member names are valid headers")
+            }
+            _ => etag(version),
+        }
+    }
+
+    pub fn select_page<T: Serialize>(&self, page: Page<T>) -> Result<Page<Value>, StatusCode> {
+        Ok(Page {
+            items: page
+                .items
+                .iter()
+                .map(|item| self.select(item))
+                .collect::<Result<_, _>>()?,
+            total: page.total,
+            next: page.next,
+        })
+    }
+}
+
+/// Resource an order refers to that can be embedded in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expansion {
+    Customer,
+    Products,
+}
+
+/// Query parameter `Expand` is read from.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpandQuery {
+    /// Any of `customer` and `products`, separated by commas.
+    expand: Option<String>,
+}
+
+/// Related resources to embed in orders, from `?expand=customer,products`,
+/// so clients do not have to fetch every one of them on their own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expand(pub Vec<Expansion>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Expand
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(raw) = Query::<ExpandQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut expansions = Vec::new();
+        for name in raw.expand.iter().flat_map(|expand| expand.split(',')) {
+            let expansion = match name.trim() {
+                "customer" => Expansion::Customer,
+                "products" => Expansion::Products,
+                "" => continue,
+                name => {
+                    return Err((StatusCode::BAD_REQUEST, format!("Cannot expand {}", name))
+                        .into_response())
+                }
+            };
+            if !expansions.contains(&expansion) {
+                expansions.push(expansion);
+            }
+        }
+        Ok(Expand(expansions))
+    }
+}
+
+impl Expand {
+    pub fn contains(&self, expansion: Expansion) -> bool {
+        self.0.contains(&expansion)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Resources embedded in an order under `expanded`. Only the expansions
+/// that were asked for are present.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Default)]
+pub struct OrderExpansion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer: Option<Customer>,
+    /// Every product in the order, including ones deleted since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub products: Option<Vec<Product>>,
+}
@@ -0,0 +1,59 @@
+use std::fmt::Display;
+
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Largest image accepted for upload, in bytes.
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Largest width and height of an uploaded image, in pixels.
+pub const MAX_IMAGE_DIMENSION: u32 = 8000;
+/// Thumbnails fit into a square of this many pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+pub const IMAGE_CONTENT_TYPES: [&str; 3] = ["image/png", "image/jpeg", "image/webp"];
+
+#[derive(Serialize, Deserialize, ToSchema, SimpleObject, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct ProductImage {
+    pub id: i32,
+    pub product_id: i32,
+    pub url: String,
+    pub thumbnail_url: String,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub position: i32,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    UnknownProduct(i32),
+    NoImage,
+    TooLarge(usize),
+    UnsupportedType(String),
+    InvalidImage(String),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::UnknownProduct(product_id) => {
+                write!(f, "Product with id {} does not exist", product_id)
+            }
+            ImageError::NoImage => write!(f, "No image was uploaded"),
+            ImageError::TooLarge(size) => write!(
+                f,
+                "Image of {} bytes exceeds the limit of {} bytes",
+                size, MAX_IMAGE_BYTES
+            ),
+            ImageError::UnsupportedType(content_type) => write!(
+                f,
+                "Unsupported image type {}, expected one of {}",
+                content_type,
+                IMAGE_CONTENT_TYPES.join(", ")
+            ),
+            ImageError::InvalidImage(reason) => write!(f, "Invalid image: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "job_status", rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for `run_at`, to be run for the first time or retried.
+    Queued,
+    Running,
+    Succeeded,
+    /// Given up on after the last attempt failed.
+    Failed,
+}
+
+/// Job as stored in the queue.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, sqlx::FromRow)]
+pub struct QueuedJob {
+    pub id: i32,
+    /// Type of the job, telling which handler runs it.
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    /// When the job is run next, while it is queued.
+    pub run_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    NotFailed(i32),
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::NotFailed(id) => {
+                write!(f, "Only failed jobs can be retried, {} is not", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
@@ -0,0 +1,363 @@
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, OriginalUri, Query},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Postgres, QueryBuilder};
+use utoipa::IntoParams;
+
+pub const DEFAULT_LIMIT: i64 = 100;
+pub const MAX_LIMIT: i64 = 1000;
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortField {
+    pub field: String,
+    pub order: SortOrder,
+}
+
+/// Column a list can be sorted by: the name clients use, the SQL expression
+/// and the SQL type of the expression, e.g. `("price", "(price).cents", "bigint")`.
+pub type SortColumn<'a> = (&'a str, &'a str, &'a str);
+
+/// One page of a list together with the number of all matching rows.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    /// Sort key of the last item when more items follow, the page after
+    /// this one starts behind it.
+    #[serde(skip)]
+    pub next: Option<Vec<Value>>,
+}
+
+/// Query parameters `ListParams` are read from.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// Number of items in the page, at most 1000.
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Position handed out in `Link` headers, replaces `offset`.
+    cursor: Option<String>,
+    /// Fields to sort by, e.g. `name:asc,price:desc`.
+    sort: Option<String>,
+}
+
+/// Pagination and sorting shared by all list endpoints, taken from the
+/// `limit`, `offset`, `cursor` and `sort` query parameters. Cursors are
+/// handed out in `Link` headers and hold the sort key of the last row of a
+/// page, so the next page starts right behind that row even when rows are
+/// added or removed in between. Clients follow them without knowing how
+/// they are built.
+/// `sort=name:asc,price:desc` sorts by the given fields, ascending when the
+/// order is left out.
+#[derive(Debug, Clone)]
+pub struct ListParams {
+    pub limit: i64,
+    pub offset: i64,
+    pub sort: Vec<SortField>,
+    /// Sort key of the row the page starts behind, taken from the cursor.
+    after: Option<Vec<Value>>,
+    path: String,
+    query: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ListError {
+    InvalidLimit(i64),
+    InvalidOffset(i64),
+    InvalidCursor,
+    InvalidSort(String),
+    UnknownSortField(String),
+}
+
+impl Display for ListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListError::InvalidLimit(limit) => {
+                write!(f, "Limit must be between 1 and {}, got {}", MAX_LIMIT, limit)
+            }
+            ListError::InvalidOffset(offset) => {
+                write!(f, "Offset must not be negative, got {}", offset)
+            }
+            ListError::InvalidCursor => write!(f, "Invalid cursor"),
+            ListError::InvalidSort(sort) => write!(f, "Invalid sort order {}", sort),
+            ListError::UnknownSortField(field) => write!(f, "Cannot sort by {}", field),
+        }
+    }
+}
+
+impl std::error::Error for ListError {}
+
+impl IntoResponse for ListError {
+    fn into_response(self) -> axum::response::Response {
+        (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ListParams
+where
+    S: Send + Sync,
+{
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(raw) = Query::<ListQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        // nested routers only see the rest of the path, links need all of it
+        let uri = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.clone(),
+            None => parts.uri.clone(),
+        };
+
+        Self::new(raw, uri.path().to_string(), uri.query().map(str::to_string))
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+impl ListParams {
+    fn new(raw: ListQuery, path: String, query: Option<String>) -> Result<Self, ListError> {
+        let limit = raw.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(ListError::InvalidLimit(limit));
+        }
+        let after = raw.cursor.as_deref().map(Self::decode_cursor).transpose()?;
+        let offset = match after {
+            Some(_) => 0,
+            None => raw.offset.unwrap_or_default(),
+        };
+        if offset < 0 {
+            return Err(ListError::InvalidOffset(offset));
+        }
+
+        let mut sort = Vec::new();
+        for sort_field in raw.sort.iter().flat_map(|sort| sort.split(',')) {
+            let (field, order) = match sort_field.split_once(':') {
+                Some((field, "asc")) => (field, SortOrder::Asc),
+                Some((field, "desc")) => (field, SortOrder::Desc),
+                Some(_) => return Err(ListError::InvalidSort(sort_field.to_string())),
+                None => (sort_field, SortOrder::Asc),
+            };
+            if field.is_empty() {
+                return Err(ListError::InvalidSort(sort_field.to_string()));
+            }
+            sort.push(SortField {
+                field: field.to_string(),
+                order,
+            });
+        }
+
+        Ok(Self {
+            limit,
+            offset,
+            sort,
+            after,
+            path,
+            query,
+        })
+    }
+
+    /// Resolves the requested sort fields to the SQL expressions, types and
+    /// orders rows are sorted by. `columns` lists the fields clients may sort
+    /// by. Rows are always ordered by `id` last, so pages never overlap.
+    fn sort_keys<'a>(
+        &self,
+        columns: &[SortColumn<'a>],
+    ) -> Result<Vec<(&'a str, &'a str, SortOrder)>, ListError> {
+        let mut sort_keys = Vec::with_capacity(self.sort.len() + 1);
+        for sort_field in self.sort.iter() {
+            let (_, column, column_type) = columns
+                .iter()
+                .find(|(field, _, _)| *field == sort_field.field)
+                .ok_or_else(|| ListError::UnknownSortField(sort_field.field.clone()))?;
+            sort_keys.push((*column, *column_type, sort_field.order));
+        }
+        if !self.sort.iter().any(|sort_field| sort_field.field == "id") {
+            sort_keys.push(("id", "integer", SortOrder::Asc));
+        }
+
+        Ok(sort_keys)
+    }
+
+    /// Builds the `order by` clause out of the requested sort fields.
+    pub fn order_by(&self, columns: &[SortColumn]) -> Result<String, ListError> {
+        let order_by: Vec<String> = self
+            .sort_keys(columns)?
+            .into_iter()
+            .map(|(column, _, order)| match order {
+                SortOrder::Asc => format!("{} asc", column),
+                SortOrder::Desc => format!("{} desc", column),
+            })
+            .collect();
+
+        Ok(format!(" order by {}", order_by.join(", ")))
+    }
+
+    /// SQL expression of the sort key of a row, the cursor of the page
+    /// after it is built out of it.
+    pub fn sort_key(&self, columns: &[SortColumn]) -> Result<String, ListError> {
+        let sort_key: Vec<&str> = self
+            .sort_keys(columns)?
+            .into_iter()
+            .map(|(column, _, _)| column)
+            .collect();
+
+        Ok(format!("jsonb_build_array({})", sort_key.join(", ")))
+    }
+
+    /// Appends a filter keeping the rows sorted behind the row of the cursor,
+    /// if there is one. Sort orders may differ between fields and nullable
+    /// fields sort nulls last, so instead of comparing rows the filter takes
+    /// the rows equal to the cursor up to some field and behind it on that one.
+    pub fn push_after(
+        &self,
+        query_builder: &mut QueryBuilder<Postgres>,
+        columns: &[SortColumn],
+    ) -> Result<(), ListError> {
+        let Some(after) = &self.after else {
+            return Ok(());
+        };
+        let sort_keys = self.sort_keys(columns)?;
+        if after.len() != sort_keys.len() {
+            return Err(ListError::InvalidCursor);
+        }
+
+        query_builder.push(" and (");
+        for (i, ((column, column_type, order), value)) in sort_keys.iter().zip(after).enumerate() {
+            if i > 0 {
+                query_builder.push(" or ");
+            }
+            query_builder.push("(");
+            for ((column, column_type, _), value) in sort_keys.iter().zip(after).take(i) {
+                match value {
+                    Value::Null => query_builder.push(format!("{} is null", column)),
+                    value => query_builder
+                        .push(format!("{} = ", column))
+                        .push_bind(Self::cursor_value(value))
+                        .push(format!("::{}", column_type)),
+                };
+                query_builder.push(" and ");
+            }
+            match (order, value) {
+                (SortOrder::Asc, Value::Null) => query_builder.push("false"),
+                (SortOrder::Asc, value) => query_builder
+                    .push(format!("({} > ", column))
+                    .push_bind(Self::cursor_value(value))
+                    .push(format!("::{} or {} is null)", column_type, column)),
+                (SortOrder::Desc, Value::Null) => {
+                    query_builder.push(format!("{} is not null", column))
+                }
+                (SortOrder::Desc, value) => query_builder
+                    .push(format!("{} < ", column))
+                    .push_bind(Self::cursor_value(value))
+                    .push(format!("::{}", column_type)),
+            };
+            query_builder.push(")");
+        }
+        query_builder.push(")");
+
+        Ok(())
+    }
+
+    /// Whether the page starts behind the row of a cursor.
+    pub fn has_cursor(&self) -> bool {
+        self.after.is_some()
+    }
+
+    /// Cursor values are bound as text and cast to the type of their column.
+    fn cursor_value(value: &Value) -> String {
+        match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        }
+    }
+
+    /// The same page of a list sorted by `field` alone, for lists clients
+    /// cannot choose the order of.
+    pub fn sorted_by(&self, field: &str, order: SortOrder) -> Self {
+        Self {
+            sort: vec![SortField {
+                field: field.to_string(),
+                order,
+            }],
+            ..self.clone()
+        }
+    }
+
+    /// Pagination and sorting passed as arguments instead of query
+    /// parameters, as in GraphQL. Pages taken this way are never linked to.
+    pub fn page(
+        limit: Option<i64>,
+        offset: Option<i64>,
+        sort: Option<String>,
+    ) -> Result<Self, ListError> {
+        let raw = ListQuery {
+            limit,
+            offset,
+            cursor: None,
+            sort,
+        };
+        Self::new(raw, String::new(), None)
+    }
+
+    /// Responds with the items of the page as a JSON array, the number of
+    /// all items in `X-Total-Count` and links to the first and the next page
+    /// in `Link`. Cursors only lead forward, there are no links back.
+    pub fn respond<T: Serialize>(&self, page: Page<T>) -> impl IntoResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(page.total));
+
+        let mut links = vec![self.link(None, "first")];
+        if let Some(next) = &page.next {
+            links.push(self.link(Some(next), "next"));
+        }
+        if let Ok(link) = HeaderValue::from_str(&links.join(", ")) {
+            headers.insert(header::LINK, link);
+        }
+
+        (headers, Json(page.items))
+    }
+
+    fn link(&self, after: Option<&[Value]>, rel: &str) -> String {
+        let mut query: Vec<String> = self
+            .query
+            .iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or_default();
+                !pair.is_empty() && !["limit", "offset", "cursor"].contains(&key)
+            })
+            .map(str::to_string)
+            .collect();
+        query.push(format!("limit={}", self.limit));
+        if let Some(after) = after {
+            query.push(format!("cursor={}", Self::encode_cursor(after)));
+        }
+
+        format!("<{}?{}>; rel=\"{}\"", self.path, query.join("&"), rel)
+    }
+
+    fn encode_cursor(after: &[Value]) -> String {
+        hex::encode(Value::from(after).to_string())
+    }
+
+    fn decode_cursor(cursor: &str) -> Result<Vec<Value>, ListError> {
+        let cursor = hex::decode(cursor).map_err(|_| ListError::InvalidCursor)?;
+        serde_json::from_slice(&cursor).map_err(|_| ListError::InvalidCursor)
+    }
+}
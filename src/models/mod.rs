@@ -1,19 +1,84 @@
+mod address;
+mod bulk;
+mod cart;
+mod category;
 mod claims;
 mod customer;
+mod deletion;
+mod discount;
+mod etag;
+mod fields;
+mod image;
+mod job;
 mod keys;
+mod list_params;
+mod money;
 mod order;
 mod params;
+mod patch;
+mod payment;
 mod product;
+mod refund;
+mod resource_id;
+mod shipment;
+mod tax;
 mod token;
 mod user;
+mod variant;
+mod version;
+mod webhook;
 
+pub use address::{Address, AddressError, AddressKind, ShippingAddress};
+pub use bulk::{
+    BulkFormat, CustomerRecord, ExportParams, ImportParams, ImportReport, ProductRecord, RowError,
+    MAX_IMPORT_BYTES,
+};
+pub use cart::{Cart, CartError, CartItem, CartItemQuantity, CartLine, CheckoutRequest};
+pub use category::{Category, CategoryError};
 pub use claims::Claims;
 pub use customer::Customer;
+pub use deletion::DeleteError;
+pub use discount::{DiscountCode, DiscountError, DiscountKind};
+pub use etag::{etag, IfMatch, IfNoneMatch};
+pub use fields::{Expand, ExpandQuery, Expansion, Fields, FieldsQuery, OrderExpansion};
+pub use image::{
+    ImageError, ProductImage, IMAGE_CONTENT_TYPES, MAX_IMAGE_BYTES, MAX_IMAGE_DIMENSION,
+    THUMBNAIL_SIZE,
+};
+pub use job::{JobError, JobStatus, QueuedJob};
 pub use keys::Keys;
+pub use list_params::{
+    ListError, ListParams, ListQuery, Page, SortColumn, SortField, SortOrder, TOTAL_COUNT_HEADER,
+};
+pub use money::{Currency, Money};
+pub use order::order_status;
+pub use order::ListedOrder;
 pub use order::Order;
+pub use order::OrderEvent;
+pub use order::OrderEventKind;
+pub use order::OrderLine;
+pub use order::OrderedProduct;
 pub use order::OrderWithProducts;
 pub use order::ProductInOrder;
-pub use params::QueryIdParam;
-pub use product::Product;
+pub use params::{
+    CartItemParams, CustomerFilter, DeleteParams, JobFilter, OrderFilter, OrderInclude,
+    ProductFilter, QueryIdParam, SearchParams, WebhookDeliveryFilter,
+};
+pub use patch::{
+    json_patch, merge_patch, Patch, PatchError, PatchOperation, JSON_PATCH_CONTENT_TYPE,
+    MERGE_PATCH_CONTENT_TYPE,
+};
+pub use payment::{PaymentError, PaymentEvent, PaymentIntent, PaymentRequest, PaymentStatus};
+pub use product::{Product, SearchError, StockError};
+pub use refund::{Refund, RefundError, RefundLine, RefundRequest};
+pub use resource_id::ResourceId;
+pub use shipment::{NewShipment, Shipment, ShipmentError, ShipmentLine};
+pub use tax::{default_region, default_tax_category, OrderTaxLine, TaxCategory, TaxError, TaxRate};
 pub use token::TokenResponse;
-pub use user::{AuthError, RequestUser, Roles, User};
+pub use user::{AuthError, AuthErrorResponse, RequestUser, Roles, User};
+pub use variant::{Attribute, AttributeKind, Variant, VariantError};
+pub use version::VersionError;
+pub use webhook::{
+    NewWebhookSubscription, WebhookDelivery, WebhookDeliveryStatus, WebhookError, WebhookEventType,
+    WebhookPayload, WebhookSubscription,
+};
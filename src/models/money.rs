@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use async_graphql::{Enum, InputObject, SimpleObject};
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Serialize, Deserialize, ToSchema, Enum, Debug, Clone, Copy, PartialEq, Eq, Hash, Default,
+    sqlx::Type,
+)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(type_name = "currency", rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Pln,
+    Eur,
+    Usd,
+    Gbp,
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+            Currency::Pln => "PLN",
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+            Currency::Gbp => "GBP",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+/// Amount of money stored as integer cents, so no rounding ever happens on
+/// prices or totals.
+#[derive(
+    Serialize, Deserialize, ToSchema, SimpleObject, InputObject, Debug, Clone, Copy, PartialEq,
+    Eq, Hash, Default, sqlx::Type,
+)]
+#[graphql(input_name = "MoneyInput")]
+#[sqlx(type_name = "money_amount")]
+pub struct Money {
+    pub cents: i64,
+    pub currency: Currency,
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        write!(
+            f,
+            "{}{}.{:02} {}",
+            sign,
+            cents / 100,
+            cents % 100,
+            self.currency
+        )
+    }
+}
+
+impl Money {
+    pub fn new(cents: i64, currency: Currency) -> Self {
+        Self { cents, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(0, currency)
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money> {
+        if self.currency != other.currency {
+            return Err(eyre!(
+                "Cannot add {} and {}: currencies differ",
+                self,
+                other
+            ));
+        }
+        let cents = self
+            .cents
+            .checked_add(other.cents)
+            .ok_or_else(|| eyre!("Overflow when adding {} and {}", self, other))?;
+        Ok(Self::new(cents, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money> {
+        self.checked_add(Money::new(-other.cents, other.currency))
+    }
+
+    /// Returns `percent` percent of the amount, rounded down to whole cents.
+    pub fn percentage(self, percent: i32) -> Result<Money> {
+        let cents = self
+            .cents
+            .checked_mul(percent as i64)
+            .ok_or_else(|| eyre!("Overflow when taking {}% of {}", percent, self))?
+            / 100;
+        Ok(Self::new(cents, self.currency))
+    }
+
+    /// Applies a rate given in basis points, rounding half away from zero.
+    pub fn apply_rate(self, basis_points: i32) -> Result<Money> {
+        let scaled = self
+            .cents
+            .checked_mul(basis_points as i64)
+            .ok_or_else(|| eyre!("Overflow when applying rate {} to {}", basis_points, self))?;
+        let cents = (scaled + scaled.signum() * 5_000) / 10_000;
+        Ok(Self::new(cents, self.currency))
+    }
+
+    pub fn checked_mul(self, quantity: i32) -> Result<Money> {
+        let cents = self
+            .cents
+            .checked_mul(quantity as i64)
+            .ok_or_else(|| eyre!("Overflow when multiplying {} by {}", self, quantity))?;
+        Ok(Self::new(cents, self.currency))
+    }
+}
@@ -1,28 +1,179 @@
 use std::collections::HashMap;
 
+use async_graphql::SimpleObject;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash)]
+use super::{Money, OrderTaxLine, ShippingAddress};
+
+/// Statuses the server moves orders into by itself. Clients may still use
+/// any other status for their own bookkeeping.
+pub mod order_status {
+    pub const NEW: &str = "New";
+    pub const PAID: &str = "Paid";
+    pub const PAYMENT_FAILED: &str = "Payment failed";
+    pub const REFUNDED: &str = "Refunded";
+    pub const PARTIALLY_REFUNDED: &str = "Partially refunded";
+    pub const CANCELLED: &str = "Cancelled";
+    pub const PARTIALLY_SHIPPED: &str = "Partially shipped";
+    pub const SHIPPED: &str = "Shipped";
+
+    /// Orders that were not handed over for fulfilment yet.
+    pub const CANCELLABLE: [&str; 3] = [NEW, PAYMENT_FAILED, PAID];
+
+    /// Paid orders with products left to send.
+    pub const SHIPPABLE: [&str; 3] = [PAID, PARTIALLY_REFUNDED, PARTIALLY_SHIPPED];
+
+    /// Orders whose products went back to stock, changing their lines would
+    /// take or return those products a second time.
+    pub const RETURNED: [&str; 3] = [CANCELLED, REFUNDED, PARTIALLY_REFUNDED];
+}
+
+#[derive(
+    Serialize, Deserialize, ToSchema, SimpleObject, Debug, Default, PartialEq, Eq, Hash,
+    sqlx::FromRow,
+)]
+#[graphql(complex)]
 pub struct Order {
     pub id: i32,
     pub customer_id: i32,
     pub status: String,
     pub created_at: NaiveDateTime,
+    /// When the order was soft deleted.
+    #[serde(default, skip_deserializing)]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Bumped on every write to the order or its lines, shipping address or
+    /// taxes, sent as the ETag of the row.
+    #[serde(default, skip_deserializing)]
+    pub version: i32,
+}
+
+/// Order as listed, with its line items when they were asked for.
+#[derive(Serialize, Deserialize, ToSchema, Debug, sqlx::FromRow)]
+pub struct ListedOrder {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub order: Order,
+    /// Only listed with `include=products`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    #[schema(value_type = Option<Vec<OrderedProduct>>)]
+    pub products: Option<sqlx::types::Json<Vec<OrderedProduct>>>,
+}
+
+/// Line item of a listed order together with the product and variant it is
+/// for, which may have been deleted since.
+#[derive(Serialize, Deserialize, ToSchema, SimpleObject, Debug, Clone, PartialEq, Eq)]
+#[graphql(complex)]
+pub struct OrderedProduct {
+    pub product_id: i32,
+    pub variant_id: i32,
+    pub name: String,
+    pub sku: String,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub refunded_quantity: i32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEventKind {
+    OrderCreated,
+    OrderStatusChanged,
+}
+
+impl OrderEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderEventKind::OrderCreated => "order_created",
+            OrderEventKind::OrderStatusChanged => "order_status_changed",
+        }
+    }
+}
+
+/// Change of an order pushed to clients watching orders, as sent by the
+/// database whenever an order is created or its status changes.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct OrderEvent {
+    pub event: OrderEventKind,
+    pub order_id: i32,
+    pub customer_id: i32,
+    pub status: String,
+    /// Only set when the status changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProductInOrder {
     pub product_id: i32,
+    pub variant_id: i32,
     pub order_id: i32,
     pub quantity: i32,
+    pub unit_price: Money,
+    #[serde(default)]
+    pub refunded_quantity: i32,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+/// Line item of an order priced with the unit price captured when the
+/// product was added to the order.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct OrderLine {
+    pub product_id: i32,
+    pub variant_id: i32,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub line_total: Money,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug)]
 pub struct OrderWithProducts {
     pub id: i32,
     pub customer_id: i32,
     pub status: String,
     pub created_at: NaiveDateTime,
+    /// Quantities per product id. Products given here are ordered in their
+    /// default variant.
+    #[serde(default)]
     pub products: HashMap<i32, i32>,
+    /// Quantities of any other variants, per variant id.
+    #[serde(default)]
+    pub variants: HashMap<i32, i32>,
+    /// Code to redeem when the order is created, ignored on updates.
+    #[serde(default)]
+    pub discount_code: Option<String>,
+    /// Address to ship to when the order is created, the customer's default
+    /// shipping address if left out. Ignored on updates.
+    #[serde(default, skip_serializing)]
+    pub shipping_address_id: Option<i32>,
+    #[serde(default, skip_deserializing)]
+    pub shipping_address: Option<ShippingAddress>,
+    #[serde(default, skip_deserializing)]
+    pub lines: Vec<OrderLine>,
+    #[serde(default, skip_deserializing)]
+    pub subtotal: Option<Money>,
+    #[serde(default, skip_deserializing)]
+    pub discount: Option<Money>,
+    #[serde(default, skip_deserializing)]
+    pub taxes: Vec<OrderTaxLine>,
+    #[serde(default, skip_deserializing)]
+    pub tax: Option<Money>,
+    #[serde(default, skip_deserializing)]
+    pub total: Option<Money>,
+    /// Bumped on every write to the order or its lines, shipping address or
+    /// taxes, sent as the ETag of the row.
+    #[serde(default, skip_deserializing)]
+    pub version: i32,
+}
+
+impl OrderWithProducts {
+    /// Adds pieces of a product to a new order, in its default variant
+    /// unless a variant is given.
+    pub fn add_item(&mut self, product_id: i32, variant_id: Option<i32>, quantity: i32) {
+        match variant_id {
+            Some(variant_id) => *self.variants.entry(variant_id).or_default() += quantity,
+            None => *self.products.entry(product_id).or_default() += quantity,
+        }
+    }
 }
@@ -1,6 +1,99 @@
+use async_graphql::InputObject;
+use chrono::NaiveDateTime;
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use super::{Currency, JobStatus, WebhookDeliveryStatus};
 
 #[derive(Deserialize)]
 pub struct QueryIdParam {
     pub id: i32,
 }
+
+/// Rows are soft deleted unless `hard` is set, which removes them for good
+/// when nothing refers to them anymore.
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteParams {
+    #[serde(default)]
+    pub hard: bool,
+}
+
+/// Narrows a cart line, identified by its product, down to one variant.
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct CartItemParams {
+    #[serde(default)]
+    pub variant_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    pub q: String,
+}
+
+/// Filters of the product list. Prices are given in cents.
+#[derive(Deserialize, IntoParams, InputObject, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct ProductFilter {
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+    pub currency: Option<Currency>,
+    pub available: Option<bool>,
+    /// Matches products of the category and all of its subcategories.
+    pub category_id: Option<i32>,
+    /// Lists soft deleted rows too.
+    #[serde(default)]
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Deserialize, IntoParams, InputObject, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct CustomerFilter {
+    /// Matches customers whose name contains the given text.
+    pub name: Option<String>,
+    pub region: Option<String>,
+    /// Lists soft deleted rows too.
+    #[serde(default)]
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Deserialize, IntoParams, InputObject, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct OrderFilter {
+    pub status: Option<String>,
+    pub customer_id: Option<i32>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    /// Lists soft deleted rows too.
+    #[serde(default)]
+    #[graphql(default)]
+    pub include_deleted: bool,
+    /// Embeds related rows in every listed order. Line items are fields of
+    /// their own in GraphQL.
+    #[graphql(skip)]
+    pub include: Option<OrderInclude>,
+}
+
+#[derive(Deserialize, IntoParams, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct JobFilter {
+    pub status: Option<JobStatus>,
+    pub kind: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct WebhookDeliveryFilter {
+    pub status: Option<WebhookDeliveryStatus>,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderInclude {
+    /// Line items with the name and SKU of what was ordered.
+    Products,
+}
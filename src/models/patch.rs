@@ -0,0 +1,296 @@
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use axum::{
+    body::{Bytes, HttpBody},
+    extract::FromRequest,
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Operation of a JSON Patch (RFC 6902). Paths are JSON Pointers (RFC 6901).
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// Body of a PATCH request, told apart by its content type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    /// JSON Merge Patch (RFC 7396), also assumed for plain JSON bodies.
+    Merge(Value),
+    /// JSON Patch (RFC 6902), applied all or nothing.
+    Json(Vec<PatchOperation>),
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    /// Carries the pointer that is not a valid JSON Pointer.
+    InvalidPointer(String),
+    /// Carries the pointer that does not lead to a value.
+    PathNotFound(String),
+    /// Carries the pointer whose value did not pass a `test` operation.
+    TestFailed(String),
+    /// The patched document does not describe a valid resource.
+    InvalidResult(String),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::InvalidPointer(pointer) => write!(f, "Invalid JSON pointer {:?}", pointer),
+            PatchError::PathNotFound(pointer) => write!(f, "Nothing found at {:?}", pointer),
+            PatchError::TestFailed(pointer) => write!(f, "Test of {:?} failed", pointer),
+            PatchError::InvalidResult(e) => write!(f, "Patched resource is invalid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for Patch
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let patch = match content_type.as_str() {
+            "application/json" | MERGE_PATCH_CONTENT_TYPE => {
+                serde_json::from_slice(&body).map(Patch::Merge)
+            }
+            JSON_PATCH_CONTENT_TYPE => serde_json::from_slice(&body).map(Patch::Json),
+            _ => return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()),
+        };
+        patch.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())
+    }
+}
+
+impl Patch {
+    /// Applies the patch to the JSON form of `resource` and reads the result
+    /// back. Fields the resource does not read from clients keep their
+    /// defaults, whatever the patch did to them.
+    pub fn apply_to<T: Serialize + DeserializeOwned>(&self, resource: &T) -> Result<T, PatchError> {
+        let mut document = serde_json::to_value(resource)
+            .map_err(|e| PatchError::InvalidResult(e.to_string()))?;
+        self.apply(&mut document)?;
+        serde_json::from_value(document).map_err(|e| PatchError::InvalidResult(e.to_string()))
+    }
+
+    pub fn apply(&self, document: &mut Value) -> Result<(), PatchError> {
+        match self {
+            Patch::Merge(patch) => {
+                merge_patch(document, patch);
+                Ok(())
+            }
+            Patch::Json(operations) => json_patch(document, operations),
+        }
+    }
+}
+
+/// Applies a JSON Merge Patch, where `null` removes a member and objects are
+/// merged member by member. Cannot fail, any JSON is a valid merge patch.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Applies the operations of a JSON Patch in order. The document is left
+/// untouched when any of them fails.
+pub fn json_patch(document: &mut Value, operations: &[PatchOperation]) -> Result<(), PatchError> {
+    let mut patched = document.clone();
+    for operation in operations {
+        match operation {
+            PatchOperation::Add { path, value } => add(&mut patched, path, value.clone())?,
+            PatchOperation::Remove { path } => {
+                remove(&mut patched, path)?;
+            }
+            PatchOperation::Replace { path, value } => {
+                let tokens = parse_pointer(path)?;
+                *get_mut(&mut patched, &tokens)
+                    .ok_or_else(|| PatchError::PathNotFound(path.clone()))? = value.clone();
+            }
+            PatchOperation::Move { from, path } => {
+                // a value cannot be moved into one of its own children
+                if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                    return Err(PatchError::InvalidPointer(path.clone()));
+                }
+                let value = remove(&mut patched, from)?;
+                add(&mut patched, path, value)?;
+            }
+            PatchOperation::Copy { from, path } => {
+                let tokens = parse_pointer(from)?;
+                let value = get(&patched, &tokens)
+                    .ok_or_else(|| PatchError::PathNotFound(from.clone()))?
+                    .clone();
+                add(&mut patched, path, value)?;
+            }
+            PatchOperation::Test { path, value } => {
+                let tokens = parse_pointer(path)?;
+                let current =
+                    get(&patched, &tokens).ok_or_else(|| PatchError::PathNotFound(path.clone()))?;
+                if !json_eq(current, value) {
+                    return Err(PatchError::TestFailed(path.clone()));
+                }
+            }
+        }
+    }
+    *document = patched;
+    Ok(())
+}
+
+/// Splits a JSON Pointer into its unescaped reference tokens.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, PatchError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let invalid = || PatchError::InvalidPointer(pointer.to_string());
+    let rest = pointer.strip_prefix('/').ok_or_else(invalid)?;
+    rest.split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => match chars.next() {
+                        Some('0') => unescaped.push('~'),
+                        Some('1') => unescaped.push('/'),
+                        _ => return Err(invalid()),
+                    },
+                    c => unescaped.push(c),
+                }
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// Array indices are plain decimal numbers without leading zeros.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|byte| byte.is_ascii_digit())
+        || (token.starts_with('0') && token.len() > 1)
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn get<'a>(document: &'a Value, tokens: &[String]) -> Option<&'a Value> {
+    tokens.iter().try_fold(document, |value, token| match value {
+        Value::Object(map) => map.get(token),
+        Value::Array(array) => array.get(parse_index(token)?),
+        _ => None,
+    })
+}
+
+fn get_mut<'a>(document: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens.iter().try_fold(document, |value, token| match value {
+        Value::Object(map) => map.get_mut(token),
+        Value::Array(array) => array.get_mut(parse_index(token)?),
+        _ => None,
+    })
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    let tokens = parse_pointer(path)?;
+    let not_found = || PatchError::PathNotFound(path.to_string());
+    let Some((last, parent)) = tokens.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+    match get_mut(document, parent).ok_or_else(not_found)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(array) if last == "-" => array.push(value),
+        Value::Array(array) => {
+            let index = parse_index(last)
+                .filter(|index| *index <= array.len())
+                .ok_or_else(not_found)?;
+            array.insert(index, value);
+        }
+        _ => return Err(not_found()),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let tokens = parse_pointer(path)?;
+    let not_found = || PatchError::PathNotFound(path.to_string());
+    // the document itself has no parent to be removed from
+    let (last, parent) = tokens
+        .split_last()
+        .ok_or_else(|| PatchError::InvalidPointer(path.to_string()))?;
+    match get_mut(document, parent).ok_or_else(not_found)? {
+        Value::Object(map) => map.remove(last).ok_or_else(not_found),
+        Value::Array(array) => {
+            let index = parse_index(last)
+                .filter(|index| *index < array.len())
+                .ok_or_else(not_found)?;
+            Ok(array.remove(index))
+        }
+        _ => Err(not_found()),
+    }
+}
+
+/// Numbers are equal when their values are, however they were written.
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => a.as_f64() == b.as_f64(),
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| json_eq(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Money;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "payment_status", rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+    Refunded,
+}
+
+impl PaymentStatus {
+    /// Payments settle exactly once and only settled payments are refunded.
+    pub fn can_become(self, next: PaymentStatus) -> bool {
+        matches!(
+            (self, next),
+            (PaymentStatus::Pending, PaymentStatus::Succeeded)
+                | (PaymentStatus::Pending, PaymentStatus::Failed)
+                | (PaymentStatus::Succeeded, PaymentStatus::Refunded)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct PaymentIntent {
+    pub id: i32,
+    pub order_id: i32,
+    pub provider: String,
+    pub provider_reference: String,
+    pub amount: Money,
+    pub status: PaymentStatus,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct PaymentRequest {
+    pub order_id: i32,
+}
+
+/// Payment status change reported by a provider through its webhook.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct PaymentEvent {
+    pub provider_reference: String,
+    pub status: PaymentStatus,
+}
+
+#[derive(Debug)]
+pub enum PaymentError {
+    InvalidSignature,
+    NothingToPay,
+    InvalidTransition {
+        from: PaymentStatus,
+        to: PaymentStatus,
+    },
+}
+
+impl Display for PaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentError::InvalidSignature => write!(f, "Invalid webhook signature"),
+            PaymentError::NothingToPay => write!(f, "Order has nothing to pay for"),
+            PaymentError::InvalidTransition { from, to } => {
+                write!(f, "Payment cannot go from {:?} to {:?}", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
@@ -1,9 +1,103 @@
+use std::fmt::Display;
+
+use async_graphql::{InputObject, SimpleObject};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, Row};
+use utoipa::ToSchema;
+
+use super::{default_tax_category, Money, ProductImage};
 
-#[derive(Serialize, Deserialize, Debug, Hash, PartialEq, Eq, Default)]
+#[derive(
+    Serialize, Deserialize, ToSchema, SimpleObject, InputObject, Debug, Clone, Hash, PartialEq,
+    Eq, Default,
+)]
+#[graphql(input_name = "ProductInput")]
 pub struct Product {
+    #[graphql(skip_input)]
     pub id: i32,
     pub name: String,
-    pub price: i32,
+    pub price: Money,
     pub available: bool,
+    #[serde(default = "default_tax_category")]
+    #[graphql(default_with = "default_tax_category()")]
+    pub tax_category: String,
+    /// Pieces of the default variant left in stock, `None` when stock is not
+    /// tracked.
+    #[serde(default)]
+    pub stock: Option<i32>,
+    #[serde(default)]
+    #[graphql(default)]
+    pub description: String,
+    /// Free form labels, searched together with the name and description.
+    #[serde(default)]
+    #[graphql(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    /// When the product was soft deleted.
+    #[serde(default, skip_deserializing)]
+    #[graphql(skip_input)]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Bumped on every write to the product, its variants or images, sent as
+    /// the ETag of the row.
+    #[serde(default, skip_deserializing)]
+    #[graphql(skip_input)]
+    pub version: i32,
+    /// Filled in separately, products are read without their images.
+    #[serde(default, skip_deserializing)]
+    #[graphql(skip_input)]
+    pub images: Vec<ProductImage>,
+}
+
+impl<'r> FromRow<'r, PgRow> for Product {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            price: row.try_get("price")?,
+            available: row.try_get("available")?,
+            tax_category: row.try_get("tax_category")?,
+            stock: row.try_get("stock")?,
+            description: row.try_get("description")?,
+            tags: row.try_get("tags")?,
+            category_id: row.try_get("category_id")?,
+            deleted_at: row.try_get("deleted_at")?,
+            version: row.try_get("version")?,
+            images: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum StockError {
+    /// Carries the id of the variant.
+    OutOfStock(i32),
+}
+
+impl Display for StockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StockError::OutOfStock(variant_id) => {
+                write!(f, "Not enough stock of variant with id {}", variant_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StockError {}
+
+#[derive(Debug)]
+pub enum SearchError {
+    EmptyQuery,
 }
+
+impl Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchError::EmptyQuery => write!(f, "Search query has no words"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
@@ -0,0 +1,84 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Money;
+
+fn default_restock() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct RefundRequest {
+    /// Quantities to refund per product id, meaning its default variant.
+    /// Empty together with `variants` refunds everything that was not
+    /// refunded yet.
+    #[serde(default)]
+    pub products: HashMap<i32, i32>,
+    /// Quantities to refund per variant id.
+    #[serde(default)]
+    pub variants: HashMap<i32, i32>,
+    #[serde(default = "default_restock")]
+    pub restock: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct RefundLine {
+    pub product_id: i32,
+    pub variant_id: i32,
+    pub quantity: i32,
+    pub amount: Money,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Refund {
+    pub id: i32,
+    pub order_id: i32,
+    pub payment_intent_id: Option<i32>,
+    pub amount: Money,
+    pub reason: String,
+    pub restocked: bool,
+    pub created_at: NaiveDateTime,
+    pub lines: Vec<RefundLine>,
+}
+
+#[derive(Debug)]
+pub enum RefundError {
+    NotCancellable(String),
+    NotUpdatable(String),
+    NotPaid,
+    InvalidQuantity(i32),
+    BelowRefunded(i32),
+    NothingToRefund,
+}
+
+impl Display for RefundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefundError::NotCancellable(status) => {
+                write!(f, "Orders with status {} cannot be cancelled", status)
+            }
+            RefundError::NotUpdatable(status) => {
+                write!(f, "Orders with status {} cannot be updated", status)
+            }
+            RefundError::NotPaid => write!(f, "Order has no settled payment to refund"),
+            RefundError::InvalidQuantity(variant_id) => write!(
+                f,
+                "Invalid refund quantity for variant with id {}",
+                variant_id
+            ),
+            RefundError::BelowRefunded(variant_id) => write!(
+                f,
+                "Quantity of variant with id {} is below its refunded quantity",
+                variant_id
+            ),
+            RefundError::NothingToRefund => write!(f, "Nothing left to refund"),
+        }
+    }
+}
+
+impl std::error::Error for RefundError {}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, Query},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use super::QueryIdParam;
+
+/// Id of the resource a request is about, from the `{id}` segment of the
+/// versioned routes or the `id` query parameter of the deprecated ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceId(pub i32);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ResourceId
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let path_id = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Path(params)| params.get("id").cloned());
+        if let Some(id) = path_id {
+            return id.parse().map(ResourceId).map_err(|_| {
+                (StatusCode::BAD_REQUEST, format!("Invalid id {:?}", id)).into_response()
+            });
+        }
+
+        let Query(QueryIdParam { id }) = Query::<QueryIdParam>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(ResourceId(id))
+    }
+}
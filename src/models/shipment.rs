@@ -0,0 +1,62 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct ShipmentLine {
+    pub product_id: i32,
+    pub variant_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Shipment {
+    pub id: i32,
+    pub order_id: i32,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub shipped_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub lines: Vec<ShipmentLine>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct NewShipment {
+    pub carrier: String,
+    pub tracking_number: String,
+    /// Quantities shipped per product id, meaning its default variant.
+    /// Empty together with `variants` ships everything that was not shipped
+    /// yet.
+    #[serde(default)]
+    pub products: HashMap<i32, i32>,
+    /// Quantities shipped per variant id.
+    #[serde(default)]
+    pub variants: HashMap<i32, i32>,
+}
+
+#[derive(Debug)]
+pub enum ShipmentError {
+    NotShippable(String),
+    InvalidQuantity(i32),
+    NothingToShip,
+}
+
+impl Display for ShipmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShipmentError::NotShippable(status) => {
+                write!(f, "Orders with status {} cannot be shipped", status)
+            }
+            ShipmentError::InvalidQuantity(variant_id) => write!(
+                f,
+                "Invalid shipment quantity for variant with id {}",
+                variant_id
+            ),
+            ShipmentError::NothingToShip => write!(f, "Nothing left to ship"),
+        }
+    }
+}
+
+impl std::error::Error for ShipmentError {}
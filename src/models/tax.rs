@@ -0,0 +1,53 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Money;
+
+pub fn default_tax_category() -> String {
+    "standard".to_string()
+}
+
+pub fn default_region() -> String {
+    "PL".to_string()
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct TaxCategory {
+    pub code: String,
+    pub name: String,
+}
+
+/// Rate of a tax category in a region, in basis points (2300 is 23%).
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct TaxRate {
+    pub id: i32,
+    pub region: String,
+    pub tax_category: String,
+    pub rate_basis_points: i32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct OrderTaxLine {
+    pub tax_category: String,
+    pub region: String,
+    pub rate_basis_points: i32,
+    pub taxable_amount: Money,
+    pub tax: Money,
+}
+
+#[derive(Debug)]
+pub enum TaxError {
+    UnknownRate(i32),
+}
+
+impl Display for TaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaxError::UnknownRate(id) => write!(f, "Tax rate with id {} does not exist", id),
+        }
+    }
+}
+
+impl std::error::Error for TaxError {}
@@ -4,9 +4,9 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
 pub struct RequestUser {
     pub name: String,
     pub password: String,
@@ -17,6 +17,13 @@ pub struct User {
     pub name: String,
     pub passwd_hash: String,
     pub role: Roles,
+    pub customer_id: Option<i32>,
+}
+
+/// Body of responses to requests that failed authentication.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct AuthErrorResponse {
+    pub error: String,
 }
 
 #[derive(Debug)]
@@ -35,9 +42,9 @@ impl IntoResponse for AuthError {
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error"),
             AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
         };
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let body = Json(AuthErrorResponse {
+            error: error_message.to_string(),
+        });
         (status, body).into_response()
     }
 }
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::Money;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "attribute_kind", rename_all = "lowercase")]
+pub enum AttributeKind {
+    Text,
+    Number,
+    Boolean,
+}
+
+/// Property variants of a product differ in, e.g. size or colour.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub id: i32,
+    /// Name the attribute is referred to by in variants, e.g. `size`.
+    pub code: String,
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+impl AttributeKind {
+    /// Converts a JSON value of this kind into its stored text form.
+    pub fn to_text(self, value: &Value) -> Option<String> {
+        match (self, value) {
+            (AttributeKind::Text, Value::String(text)) => Some(text.clone()),
+            (AttributeKind::Number, Value::Number(number)) => Some(number.to_string()),
+            (AttributeKind::Boolean, Value::Bool(boolean)) => Some(boolean.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn from_text(self, text: &str) -> Value {
+        match self {
+            AttributeKind::Text => Value::String(text.to_string()),
+            AttributeKind::Number => text
+                .parse::<serde_json::Number>()
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            AttributeKind::Boolean => Value::Bool(text == "true"),
+        }
+    }
+}
+
+/// Orderable version of a product with its own SKU and stock.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Variant {
+    #[serde(default)]
+    pub id: i32,
+    pub product_id: i32,
+    pub sku: String,
+    /// Price of the variant, `None` to sell it at the product's price.
+    #[serde(default)]
+    pub price: Option<Money>,
+    /// Pieces left in stock, `None` when stock is not tracked.
+    #[serde(default)]
+    pub stock: Option<i32>,
+    #[serde(default = "default_available")]
+    pub available: bool,
+    #[serde(default, skip_deserializing)]
+    pub is_default: bool,
+    /// Attribute values by attribute code, typed according to the attribute.
+    #[serde(default)]
+    pub attributes: HashMap<String, Value>,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+#[derive(Debug)]
+pub enum VariantError {
+    UnknownProduct(i32),
+    UnknownVariant(i32),
+    UnknownAttribute(String),
+    InvalidAttributeValue(String),
+    DefaultVariant(i32),
+}
+
+impl Display for VariantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariantError::UnknownProduct(id) => write!(f, "Product with id {} does not exist", id),
+            VariantError::UnknownVariant(id) => write!(f, "Variant with id {} does not exist", id),
+            VariantError::UnknownAttribute(code) => write!(f, "Unknown attribute {}", code),
+            VariantError::InvalidAttributeValue(code) => {
+                write!(f, "Value of attribute {} has the wrong type", code)
+            }
+            VariantError::DefaultVariant(id) => {
+                write!(f, "Variant with id {} is the default variant of its product", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VariantError {}
@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum VersionError {
+    /// The row was changed after the version the update was made against.
+    Mismatch {
+        resource: &'static str,
+        id: i32,
+        expected: i32,
+        current: i32,
+    },
+}
+
+impl Display for VersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionError::Mismatch {
+                resource,
+                id,
+                expected,
+                current,
+            } => write!(
+                f,
+                "{} with id {} is at version {}, not {}",
+                resource, id, current, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VersionError {}
@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use utoipa::ToSchema;
+
+/// Changes webhooks can be subscribed to.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(type_name = "webhook_event_type")]
+pub enum WebhookEventType {
+    #[serde(rename = "order.created")]
+    #[sqlx(rename = "order.created")]
+    OrderCreated,
+    #[serde(rename = "order.status_changed")]
+    #[sqlx(rename = "order.status_changed")]
+    OrderStatusChanged,
+    #[serde(rename = "product.created")]
+    #[sqlx(rename = "product.created")]
+    ProductCreated,
+    #[serde(rename = "product.updated")]
+    #[sqlx(rename = "product.updated")]
+    ProductUpdated,
+    /// Sent for soft deletes too.
+    #[serde(rename = "product.deleted")]
+    #[sqlx(rename = "product.deleted")]
+    ProductDeleted,
+}
+
+impl PgHasArrayType for WebhookEventType {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_webhook_event_type")
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// Gave up on after the last attempt failed.
+    Dead,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, sqlx::FromRow)]
+pub struct WebhookSubscription {
+    pub id: i32,
+    pub url: String,
+    pub event_types: Vec<WebhookEventType>,
+    /// Key of the HMAC-SHA256 signature sent with every payload.
+    pub secret: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+/// Subscription to create, or to replace an existing one with.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct NewWebhookSubscription {
+    pub url: String,
+    pub event_types: Vec<WebhookEventType>,
+    /// Generated for new subscriptions and kept for existing ones when left
+    /// out.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Attempts to send one event to one subscription.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub subscription_id: i32,
+    pub event_id: i32,
+    pub event_type: WebhookEventType,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    /// When the delivery is attempted next, while it is pending.
+    pub next_attempt_at: NaiveDateTime,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    /// Status the receiver responded with to the last attempt.
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Body POSTed to subscribers. `data` holds the row that changed as stored,
+/// with `previous_status` added to order status changes. Every attempt of a
+/// delivery sends the same body, so receivers can tell repeated deliveries
+/// apart by `id`.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct WebhookPayload {
+    pub id: i32,
+    #[serde(rename = "type")]
+    pub event_type: WebhookEventType,
+    pub created_at: NaiveDateTime,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub enum WebhookError {
+    InvalidUrl(String),
+    NoEventTypes,
+    NotDead(i32),
+}
+
+impl Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::InvalidUrl(url) => write!(f, "Invalid webhook url {}", url),
+            WebhookError::NoEventTypes => write!(f, "Webhooks need at least one event type"),
+            WebhookError::NotDead(id) => {
+                write!(f, "Only dead deliveries can be retried, {} is not", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
@@ -0,0 +1,217 @@
+//! OpenAPI document of version 1 of the API, served at [`OPENAPI_PATH`] and
+//! browsable at [`DOCS_PATH`].
+//!
+//! Schemas come from the models and operations from the `#[utoipa::path]`
+//! of their handlers. Every operation `V1Routes` serves has to be listed in
+//! `paths`, which the `test_openapi_matches_routes` test checks both ways.
+
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::controllers::{
+    cart_controller, category_controller, customer_controller, discount_controller,
+    image_controller, job_controller, order_controller, order_event_controller,
+    payment_controller, product_controller, shipment_controller, tax_controller, user_controller,
+    variant_controller, webhook_controller,
+};
+use crate::models::{
+    Address, AddressKind, Attribute, AttributeKind, AuthErrorResponse, BulkFormat, Cart,
+    CartItem, CartItemQuantity, CartLine, Category, CheckoutRequest, Currency, Customer,
+    DiscountCode, DiscountKind, ImportReport, JobStatus, ListedOrder, Money, NewShipment,
+    NewWebhookSubscription, Order, OrderEvent, OrderEventKind, OrderExpansion, OrderInclude,
+    OrderLine, OrderTaxLine, OrderWithProducts, OrderedProduct, PatchOperation, PaymentEvent,
+    PaymentIntent, PaymentRequest, PaymentStatus, Product, ProductImage, QueuedJob, Refund,
+    RefundLine, RefundRequest, RequestUser, RowError, Shipment, ShipmentLine, ShippingAddress,
+    TaxCategory, TaxRate, TokenResponse, Variant, WebhookDelivery, WebhookDeliveryStatus,
+    WebhookEventType, WebhookPayload, WebhookSubscription,
+};
+
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+pub const DOCS_PATH: &str = "/api/docs";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Shop API", version = "1"),
+    paths(
+        product_controller::get_all_products,
+        product_controller::search_products,
+        product_controller::get_product,
+        variant_controller::get_product_variants,
+        image_controller::get_product_images,
+        category_controller::get_all_categories,
+        category_controller::get_category,
+        order_controller::get_all_orders,
+        order_controller::create_order,
+        order_controller::get_order,
+        order_controller::cancel_order,
+        shipment_controller::get_own_order_shipments,
+        order_event_controller::stream_order_events,
+        order_event_controller::order_events_socket,
+        customer_controller::get_all_customers,
+        customer_controller::get_customer,
+        customer_controller::get_addresses,
+        customer_controller::create_address,
+        customer_controller::update_address,
+        customer_controller::delete_address,
+        cart_controller::get_cart,
+        cart_controller::add_item,
+        cart_controller::update_item,
+        cart_controller::remove_item,
+        cart_controller::checkout,
+        payment_controller::create_payment_intent,
+        payment_controller::get_payment_intent,
+        payment_controller::webhook,
+        user_controller::create_user,
+        user_controller::authorize,
+        image_controller::get_media,
+        product_controller::create_product,
+        product_controller::update_product,
+        product_controller::partial_update_product,
+        product_controller::delete_product,
+        product_controller::restore_product,
+        product_controller::import_products,
+        product_controller::export_products,
+        image_controller::upload_product_images,
+        image_controller::delete_product_image,
+        variant_controller::create_variant,
+        variant_controller::update_variant,
+        variant_controller::delete_variant,
+        variant_controller::get_all_attributes,
+        variant_controller::create_attribute,
+        category_controller::create_category,
+        category_controller::update_category,
+        category_controller::delete_category,
+        customer_controller::create_customer,
+        customer_controller::update_customer,
+        customer_controller::partial_update_customer,
+        customer_controller::delete_customer,
+        customer_controller::restore_customer,
+        customer_controller::import_customers,
+        customer_controller::export_customers,
+        customer_controller::get_customer_addresses,
+        order_controller::update_order,
+        order_controller::partial_update_order,
+        order_controller::delete_order,
+        order_controller::restore_order,
+        order_controller::admin_cancel_order,
+        order_controller::get_order_refunds,
+        order_controller::refund_order,
+        shipment_controller::get_order_shipments,
+        shipment_controller::create_shipment,
+        shipment_controller::mark_delivered,
+        discount_controller::get_all_discount_codes,
+        discount_controller::create_discount_code,
+        discount_controller::get_discount_code,
+        discount_controller::update_discount_code,
+        discount_controller::delete_discount_code,
+        tax_controller::get_all_tax_categories,
+        tax_controller::create_tax_category,
+        tax_controller::get_all_tax_rates,
+        tax_controller::create_tax_rate,
+        tax_controller::get_tax_rate,
+        tax_controller::update_tax_rate,
+        tax_controller::delete_tax_rate,
+        webhook_controller::get_all_subscriptions,
+        webhook_controller::create_subscription,
+        webhook_controller::get_subscription,
+        webhook_controller::update_subscription,
+        webhook_controller::delete_subscription,
+        webhook_controller::get_deliveries,
+        webhook_controller::retry_delivery,
+        job_controller::get_all_jobs,
+        job_controller::get_job,
+        job_controller::retry_job,
+    ),
+    components(schemas(
+        Address,
+        AddressKind,
+        Attribute,
+        AttributeKind,
+        AuthErrorResponse,
+        BulkFormat,
+        Cart,
+        CartItem,
+        CartItemQuantity,
+        CartLine,
+        Category,
+        CheckoutRequest,
+        Currency,
+        Customer,
+        DiscountCode,
+        DiscountKind,
+        ImportReport,
+        JobStatus,
+        ListedOrder,
+        Money,
+        NewShipment,
+        NewWebhookSubscription,
+        Order,
+        OrderEvent,
+        OrderEventKind,
+        OrderExpansion,
+        OrderInclude,
+        OrderLine,
+        OrderTaxLine,
+        OrderWithProducts,
+        OrderedProduct,
+        PatchOperation,
+        PaymentEvent,
+        PaymentIntent,
+        PaymentRequest,
+        PaymentStatus,
+        Product,
+        ProductImage,
+        QueuedJob,
+        Refund,
+        RefundLine,
+        RefundRequest,
+        RequestUser,
+        RowError,
+        Shipment,
+        ShipmentLine,
+        ShippingAddress,
+        TaxCategory,
+        TaxRate,
+        TokenResponse,
+        Variant,
+        WebhookDelivery,
+        WebhookDeliveryStatus,
+        WebhookEventType,
+        WebhookPayload,
+        WebhookSubscription,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "products"),
+        (name = "categories"),
+        (name = "orders"),
+        (name = "customers"),
+        (name = "cart"),
+        (name = "payments"),
+        (name = "users"),
+        (name = "admin", description = "Requires a token of an admin"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearer` scheme operations refer to, for the tokens handed
+/// out by `/api/v1/users/authorize`.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
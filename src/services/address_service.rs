@@ -0,0 +1,241 @@
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+use crate::models::{Address, AddressError, AddressKind, ShippingAddress};
+use async_trait::async_trait;
+use color_eyre::Result;
+use sqlx::{PgConnection, PgPool};
+
+pub struct AddressService;
+
+#[async_trait]
+impl MockFillable for AddressService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let new_addresses = [
+            (1, "Customer 1", "ul. Marszałkowska 1", "Warszawa", "00-001", "PL"),
+            (2, "Customer 2", "Unter den Linden 1", "Berlin", "10117", "DE"),
+        ];
+
+        let pool = get_pool().await?;
+        for (customer_id, recipient, line1, city, postal_code, country) in new_addresses {
+            Self::create_address(
+                &pool,
+                customer_id,
+                Address {
+                    id: 0,
+                    customer_id,
+                    kind: AddressKind::Shipping,
+                    recipient: recipient.to_string(),
+                    line1: line1.to_string(),
+                    line2: None,
+                    city: city.to_string(),
+                    postal_code: postal_code.to_string(),
+                    country: country.to_string(),
+                    is_default: true,
+                },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for AddressService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from order_shipping_addresses")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from customer_addresses")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl AddressService {
+    /// The first address of a kind becomes the default one, a new default
+    /// replaces the previous one.
+    pub async fn create_address(pool: &PgPool, customer_id: i32, new_address: Address) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        let has_default = sqlx::query_scalar!(
+            r#"select exists(select 1 from customer_addresses
+            where customer_id = $1 and kind = $2 and is_default) as "exists!""#,
+            customer_id,
+            new_address.kind as AddressKind
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        let is_default = new_address.is_default || !has_default;
+        if is_default {
+            Self::unset_default(&mut tx, customer_id, new_address.kind).await?;
+        }
+
+        let new_address_row: (i32,) = sqlx::query_as(
+            "insert into customer_addresses (customer_id, kind, recipient, line1, line2, city, \
+            postal_code, country, is_default) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning id",
+        )
+        .bind(customer_id)
+        .bind(new_address.kind)
+        .bind(new_address.recipient)
+        .bind(new_address.line1)
+        .bind(new_address.line2)
+        .bind(new_address.city)
+        .bind(new_address.postal_code)
+        .bind(new_address.country)
+        .bind(is_default)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(new_address_row.0)
+    }
+
+    pub async fn get_addresses(pool: &PgPool, customer_id: i32) -> Result<Vec<Address>> {
+        Ok(sqlx::query_as!(
+            Address,
+            r#"select id, customer_id, kind as "kind: AddressKind", recipient, line1, line2, city,
+            postal_code, country, is_default
+            from customer_addresses where customer_id = $1 order by id"#,
+            customer_id
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    pub async fn update_address(pool: &PgPool, customer_id: i32, updated_address: Address) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        if updated_address.is_default {
+            Self::unset_default(&mut tx, customer_id, updated_address.kind).await?;
+        }
+
+        let updated = sqlx::query!(
+            "update customer_addresses set kind = $1, recipient = $2, line1 = $3, line2 = $4, \
+            city = $5, postal_code = $6, country = $7, is_default = $8 \
+            where id = $9 and customer_id = $10",
+            updated_address.kind as AddressKind,
+            updated_address.recipient,
+            updated_address.line1,
+            updated_address.line2,
+            updated_address.city,
+            updated_address.postal_code,
+            updated_address.country,
+            updated_address.is_default,
+            updated_address.id,
+            customer_id
+        )
+        .execute(&mut tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(AddressError::UnknownAddress(updated_address.id).into());
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Orders keep their own copy of the address, so deleting it is always safe.
+    pub async fn delete_address(pool: &PgPool, customer_id: i32, id: i32) -> Result<()> {
+        let deleted = sqlx::query!(
+            "delete from customer_addresses where id = $1 and customer_id = $2",
+            id,
+            customer_id
+        )
+        .execute(pool)
+        .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(AddressError::UnknownAddress(id).into());
+        }
+
+        Ok(())
+    }
+
+    /// Copies the given or, without one, the default shipping address of the
+    /// customer onto the order. Orders of customers without any shipping
+    /// address are left without one.
+    pub async fn snapshot_shipping_address(
+        conn: &mut PgConnection,
+        order_id: i32,
+        customer_id: i32,
+        address_id: Option<i32>,
+    ) -> Result<()> {
+        let address = match address_id {
+            Some(address_id) => {
+                let address = sqlx::query_as!(
+                    Address,
+                    r#"select id, customer_id, kind as "kind: AddressKind", recipient, line1,
+                    line2, city, postal_code, country, is_default
+                    from customer_addresses where id = $1 and customer_id = $2"#,
+                    address_id,
+                    customer_id
+                )
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or(AddressError::UnknownAddress(address_id))?;
+                if address.kind != AddressKind::Shipping {
+                    return Err(AddressError::NotShippingAddress(address_id).into());
+                }
+                Some(address)
+            }
+            None => {
+                sqlx::query_as!(
+                    Address,
+                    r#"select id, customer_id, kind as "kind: AddressKind", recipient, line1,
+                    line2, city, postal_code, country, is_default
+                    from customer_addresses
+                    where customer_id = $1 and kind = 'shipping' and is_default"#,
+                    customer_id
+                )
+                .fetch_optional(&mut *conn)
+                .await?
+            }
+        };
+
+        if let Some(address) = address {
+            let address = ShippingAddress::from(address);
+            sqlx::query!(
+                "insert into order_shipping_addresses \
+                (order_id, recipient, line1, line2, city, postal_code, country) \
+                values ($1, $2, $3, $4, $5, $6, $7)",
+                order_id,
+                address.recipient,
+                address.line1,
+                address.line2,
+                address.city,
+                address.postal_code,
+                address.country
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_shipping_address(
+        pool: &PgPool,
+        order_id: i32,
+    ) -> Result<Option<ShippingAddress>> {
+        Ok(sqlx::query_as!(
+            ShippingAddress,
+            "select recipient, line1, line2, city, postal_code, country \
+            from order_shipping_addresses where order_id = $1",
+            order_id
+        )
+        .fetch_optional(pool)
+        .await?)
+    }
+
+    async fn unset_default(conn: &mut PgConnection, customer_id: i32, kind: AddressKind) -> Result<()> {
+        sqlx::query!(
+            "update customer_addresses set is_default = false \
+            where customer_id = $1 and kind = $2 and is_default",
+            customer_id,
+            kind as AddressKind
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+
+/// Stored file together with the type of its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob {
+    pub data: Vec<u8>,
+    pub content_type: String,
+}
+
+/// Storage for uploaded files. Keys are relative, `/` separated paths.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Stores `data` under `key`, replacing whatever was stored there before.
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()>;
+
+    /// Returns `None` when nothing is stored under `key`.
+    async fn get(&self, key: &str) -> Result<Option<Blob>>;
+
+    /// Deleting a key that holds nothing is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Address clients download the blob from.
+    fn url(&self, key: &str) -> String;
+}
+
+/// Keeps blobs as files below a directory. The server hands them out itself,
+/// so `public_url` has to point at its media route.
+pub struct LocalBlobStore {
+    root: PathBuf,
+    public_url: String,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>, public_url: &str) -> Self {
+        Self {
+            root: root.into(),
+            public_url: public_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Keys come from clients when blobs are downloaded, so only plain
+    /// relative paths are allowed to keep them inside of the root directory.
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let key = Path::new(key);
+        let is_plain = key.components().count() > 0
+            && key
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        is_plain.then(|| self.root.join(key))
+    }
+
+    fn content_type(path: &Path) -> &'static str {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("webp") => "image/webp",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<()> {
+        let path = self
+            .path(key)
+            .ok_or_else(|| eyre!("Invalid blob key {}", key))?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Blob>> {
+        let Some(path) = self.path(key) else {
+            return Ok(None);
+        };
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(Blob {
+                data,
+                content_type: Self::content_type(&path).to_string(),
+            })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let Some(path) = self.path(key) else {
+            return Ok(());
+        };
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+pub struct S3Config {
+    /// Base address of the service, e.g. `https://s3.eu-central-1.amazonaws.com`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Address blobs are downloaded from, followed by their keys.
+    pub public_url: String,
+}
+
+/// Keeps blobs in a bucket of any S3 compatible service, addressed path
+/// style and authenticated with AWS Signature Version 4.
+pub struct S3BlobStore {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+impl S3BlobStore {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config: S3Config {
+                endpoint: config.endpoint.trim_end_matches('/').to_string(),
+                public_url: config.public_url.trim_end_matches('/').to_string(),
+                ..config
+            },
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let path = format!("/{}/{}", self.config.bucket, Self::encode_key(key));
+        let url = reqwest::Url::parse(&format!("{}{}", self.config.endpoint, path))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut signing_key = format!("AWS4{}", self.config.secret_access_key).into_bytes();
+        for part in [date.as_str(), &self.config.region, "s3", "aws4_request"] {
+            signing_key = Self::hmac(&signing_key, part.as_bytes());
+        }
+        let signature = hex::encode(Self::hmac(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+        Ok(request.body(body).send().await?)
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Percent encodes everything but unreserved characters and separators,
+    /// the way signed paths have to be encoded.
+    fn encode_key(key: &str) -> String {
+        key.bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("%{:02X}", byte),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()> {
+        let response = self.send(Method::PUT, key, data, Some(content_type)).await?;
+        if !response.status().is_success() {
+            return Err(eyre!(
+                "Storing {} failed with {}: {}",
+                key,
+                response.status(),
+                response.text().await?
+            ));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Blob>> {
+        let response = self.send(Method::GET, key, Vec::new(), None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(eyre!("Fetching {} failed with {}", key, response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        Ok(Some(Blob {
+            data: response.bytes().await?.to_vec(),
+            content_type,
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let response = self.send(Method::DELETE, key, Vec::new(), None).await?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(eyre!("Deleting {} failed with {}", key, response.status()));
+        }
+        Ok(())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.config.public_url, Self::encode_key(key))
+    }
+}
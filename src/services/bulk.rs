@@ -0,0 +1,141 @@
+use std::future::Future;
+
+use axum::body::Bytes;
+use color_eyre::Result;
+use futures_util::{stream, Stream};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::models::{BulkFormat, RowError};
+
+/// Rows read from the database at once while exporting.
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+/// Reads every row of an import together with its number, keeping the rows
+/// that cannot be read as errors so the rest can still be imported.
+pub(crate) fn parse_records<T: DeserializeOwned>(
+    format: BulkFormat,
+    body: &[u8],
+) -> Vec<Result<(usize, T), RowError>> {
+    let row_error = |row: usize, message: String| RowError { row, message };
+    match format {
+        BulkFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body)
+            .deserialize()
+            .enumerate()
+            .map(|(index, record)| {
+                record
+                    .map(|record| (index + 1, record))
+                    .map_err(|e| row_error(index + 1, e.to_string()))
+            })
+            .collect(),
+        // blank lines are skipped, but still counted so rows are numbered by line
+        BulkFormat::Ndjson => body
+            .split(|byte| *byte == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|(index, line)| {
+                serde_json::from_slice(line)
+                    .map(|record| (index + 1, record))
+                    .map_err(|e| row_error(index + 1, e.to_string()))
+            })
+            .collect(),
+    }
+}
+
+/// Separates the rows to import from the rows that cannot be imported, with
+/// `validate` telling why a row is invalid.
+pub(crate) fn validate_records<T>(
+    records: Vec<Result<(usize, T), RowError>>,
+    mut validate: impl FnMut(&T) -> Option<String>,
+) -> (Vec<T>, Vec<RowError>) {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    for record in records {
+        match record.map(|(row, record)| (row, validate(&record), record)) {
+            Ok((_, None, record)) => valid.push(record),
+            Ok((row, Some(message), _)) => errors.push(RowError { row, message }),
+            Err(e) => errors.push(e),
+        }
+    }
+    (valid, errors)
+}
+
+/// Writes one batch of an export, the CSV header only along with the first.
+fn write_records<T: Serialize>(format: BulkFormat, records: &[T], first: bool) -> Result<Bytes> {
+    let mut data = Vec::new();
+    match format {
+        BulkFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(first)
+                .from_writer(&mut data);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        BulkFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut data, record)?;
+                data.push(b'\n');
+            }
+        }
+    }
+    Ok(data.into())
+}
+
+/// Header line of a CSV export. Serde only tells the names of the fields
+/// while serializing a record, so it is taken from a default one.
+fn csv_header<T: Serialize + Default>() -> Result<Bytes> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.serialize(T::default())?;
+    let data = writer.into_inner()?;
+    let header_end = data
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(data.len(), |newline| newline + 1);
+    Ok(Bytes::copy_from_slice(&data[..header_end]))
+}
+
+/// Streams a table ordered by id in batches, so exports only ever hold one
+/// batch in memory. `fetch_batch` is given the last id exported so far and
+/// the size of the batch, and returns the next rows with their ids.
+pub(crate) fn export_stream<T, F, Fut>(
+    format: BulkFormat,
+    fetch_batch: F,
+) -> impl Stream<Item = Result<Bytes>>
+where
+    T: Serialize + Default,
+    F: FnMut(i32, i64) -> Fut,
+    Fut: Future<Output = Result<Vec<(i32, T)>>>,
+{
+    // no last id means the table is exhausted
+    stream::unfold(
+        (fetch_batch, Some(0), true),
+        move |(mut fetch_batch, last_id, first)| async move {
+            let last_id = last_id?;
+            let batch = match fetch_batch(last_id, EXPORT_BATCH_SIZE).await {
+                Ok(batch) => batch,
+                Err(e) => return Some((Err(e), (fetch_batch, None, false))),
+            };
+            if batch.is_empty() {
+                // an empty CSV export still tells the columns
+                return match (format, first) {
+                    (BulkFormat::Csv, true) => {
+                        Some((csv_header::<T>(), (fetch_batch, None, false)))
+                    }
+                    _ => None,
+                };
+            }
+            let next_id = match batch.len() as i64 == EXPORT_BATCH_SIZE {
+                true => batch.last().map(|(id, _)| *id),
+                false => None,
+            };
+            let records: Vec<T> = batch.into_iter().map(|(_, record)| record).collect();
+            Some((
+                write_records(format, &records, first),
+                (fetch_batch, next_id, false),
+            ))
+        },
+    )
+}
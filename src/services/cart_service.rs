@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+use crate::models::{
+    order_status, Cart, CartError, CartItem, CartLine, CheckoutRequest, Money, OrderWithProducts,
+    VariantError,
+};
+use async_trait::async_trait;
+use chrono::Local;
+use color_eyre::Result;
+use sqlx::PgPool;
+
+use super::order_service::OrderService;
+use super::user_service::UserService;
+
+pub struct CartService;
+
+#[async_trait]
+impl MockFillable for CartService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        let customer_id = UserService::get_customer_id(&pool, "example_customer").await?;
+        Self::add_item(
+            &pool,
+            customer_id,
+            CartItem {
+                product_id: 1,
+                variant_id: None,
+                quantity: 2,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for CartService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from cart_items").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl CartService {
+    /// Returns the cart priced with current variant prices and availability.
+    pub async fn get_cart(pool: &PgPool, customer_id: i32) -> Result<Cart> {
+        let rows = sqlx::query!(
+            r#"select cart_items.product_id, cart_items.variant_id, product_variants.sku,
+            products.name, cart_items.quantity,
+            coalesce(product_variants.price, products.price) as "price!: Money",
+            products.available and product_variants.available
+            and products.deleted_at is null as "available!"
+            from cart_items join products on products.id = cart_items.product_id
+            join product_variants on product_variants.id = cart_items.variant_id
+            where cart_items.customer_id = $1 order by cart_items.added_at, cart_items.variant_id"#,
+            customer_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut lines = Vec::with_capacity(rows.len());
+        let mut subtotal: Option<Money> = None;
+        for row in rows {
+            let line_total = row.price.checked_mul(row.quantity)?;
+            subtotal = Some(match subtotal {
+                Some(subtotal) => subtotal.checked_add(line_total)?,
+                None => line_total,
+            });
+            lines.push(CartLine {
+                product_id: row.product_id,
+                variant_id: row.variant_id,
+                sku: row.sku,
+                name: row.name,
+                quantity: row.quantity,
+                unit_price: row.price,
+                line_total,
+                available: row.available,
+            });
+        }
+
+        Ok(Cart {
+            customer_id,
+            available: lines.iter().all(|line| line.available),
+            lines,
+            subtotal,
+        })
+    }
+
+    /// Adds `item.quantity` pieces of the variant on top of what is already
+    /// in the cart.
+    pub async fn add_item(pool: &PgPool, customer_id: i32, item: CartItem) -> Result<()> {
+        if item.quantity <= 0 {
+            return Err(CartError::InvalidQuantity.into());
+        }
+
+        let variant_id = Self::get_variant_id(pool, &item).await?;
+        sqlx::query!(
+            "insert into cart_items (customer_id, product_id, variant_id, quantity) \
+            values ($1, $2, $3, $4) \
+            on conflict (customer_id, variant_id) \
+            do update set quantity = cart_items.quantity + excluded.quantity",
+            customer_id,
+            item.product_id,
+            variant_id,
+            item.quantity
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_item(pool: &PgPool, customer_id: i32, item: CartItem) -> Result<()> {
+        if item.quantity <= 0 {
+            return Err(CartError::InvalidQuantity.into());
+        }
+
+        let variant_id = Self::get_variant_id(pool, &item).await?;
+        let updated = sqlx::query!(
+            "update cart_items set quantity = $1 where customer_id = $2 and variant_id = $3",
+            item.quantity,
+            customer_id,
+            variant_id
+        )
+        .execute(pool)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(CartError::NotInCart(item.product_id).into());
+        }
+
+        Ok(())
+    }
+
+    /// Takes the variant out of the cart, or every variant of the product
+    /// when no variant is given.
+    pub async fn remove_item(
+        pool: &PgPool,
+        customer_id: i32,
+        product_id: i32,
+        variant_id: Option<i32>,
+    ) -> Result<()> {
+        sqlx::query!(
+            "delete from cart_items where customer_id = $1 and product_id = $2 \
+            and ($3::int is null or variant_id = $3)",
+            customer_id,
+            product_id,
+            variant_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Turns the cart into a new order and empties it in one transaction, so
+    /// either both happen or neither does.
+    pub async fn checkout(
+        pool: &PgPool,
+        customer_id: i32,
+        checkout: CheckoutRequest,
+    ) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+
+        let items = sqlx::query!(
+            r#"select cart_items.product_id, cart_items.variant_id, cart_items.quantity,
+            products.available and product_variants.available
+            and products.deleted_at is null as "available!"
+            from cart_items join products on products.id = cart_items.product_id
+            join product_variants on product_variants.id = cart_items.variant_id
+            where cart_items.customer_id = $1 for update of cart_items"#,
+            customer_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        if items.is_empty() {
+            return Err(CartError::EmptyCart.into());
+        }
+        if let Some(item) = items.iter().find(|item| !item.available) {
+            return Err(CartError::ProductUnavailable(item.product_id).into());
+        }
+
+        let variants: HashMap<i32, i32> = items
+            .iter()
+            .map(|item| (item.variant_id, item.quantity))
+            .collect();
+        let new_order = OrderWithProducts {
+            customer_id,
+            status: order_status::NEW.to_string(),
+            created_at: Local::now().naive_local(),
+            variants,
+            discount_code: checkout.discount_code,
+            shipping_address_id: checkout.shipping_address_id,
+            ..Default::default()
+        };
+        let order_id = OrderService::create_order_in_transaction(&mut tx, new_order).await?;
+
+        sqlx::query!("delete from cart_items where customer_id = $1", customer_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(order_id)
+    }
+
+    /// Returns the variant the item refers to, which has to belong to the
+    /// product of the item.
+    async fn get_variant_id(pool: &PgPool, item: &CartItem) -> Result<i32> {
+        let variant_id = sqlx::query_scalar!(
+            "select product_variants.id from product_variants \
+            join products on products.id = product_id \
+            where product_id = $1 and products.deleted_at is null \
+            and ($2::int is null and is_default or product_variants.id = $2)",
+            item.product_id,
+            item.variant_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        match (variant_id, item.variant_id) {
+            (Some(variant_id), _) => Ok(variant_id),
+            (None, Some(variant_id)) => Err(VariantError::UnknownVariant(variant_id).into()),
+            (None, None) => Err(VariantError::UnknownProduct(item.product_id).into()),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+use crate::models::{Category, CategoryError};
+use async_trait::async_trait;
+use color_eyre::{eyre::eyre, Result};
+use sqlx::PgPool;
+
+use super::sync_id_sequence;
+
+pub struct CategoryService;
+
+#[async_trait]
+impl MockFillable for CategoryService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let new_categories = [
+            (1, "Clothing", None),
+            (2, "T-shirts", Some(1)),
+            (3, "Books", None),
+            (4, "Cookbooks", Some(3)),
+        ];
+
+        let pool = get_pool().await?;
+        for (id, name, parent_id) in new_categories {
+            sqlx::query!(
+                "insert into categories (id, name, parent_id) values ($1, $2, $3)",
+                id,
+                name,
+                parent_id
+            )
+            .execute(&pool)
+            .await?;
+        }
+        sync_id_sequence(&pool, "categories").await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for CategoryService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("update categories set parent_id = null")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from categories").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl CategoryService {
+    pub async fn create_category(pool: &PgPool, new_category: Category) -> Result<i32> {
+        let new_category_row: (i32,) =
+            sqlx::query_as("insert into categories (name, parent_id) values ($1, $2) returning id")
+                .bind(new_category.name)
+                .bind(new_category.parent_id)
+                .fetch_one(pool)
+                .await?;
+
+        Ok(new_category_row.0)
+    }
+
+    pub async fn get_category(pool: &PgPool, id: i32) -> Result<Category> {
+        Ok(sqlx::query_as!(
+            Category,
+            "select id, name, parent_id from categories where id = $1",
+            id
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    pub async fn get_all_categories(pool: &PgPool) -> Result<Vec<Category>> {
+        Ok(sqlx::query_as!(
+            Category,
+            "select id, name, parent_id from categories order by id"
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Moving a category below itself or one of its subcategories is
+    /// rejected, so the hierarchy stays a tree.
+    pub async fn update_category(pool: &PgPool, updated_category: Category) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        if let Some(parent_id) = updated_category.parent_id {
+            let creates_cycle = sqlx::query_scalar!(
+                r#"with recursive ancestors as (
+                    select id, parent_id from categories where id = $1
+                    union all
+                    select categories.id, categories.parent_id from categories
+                    join ancestors on categories.id = ancestors.parent_id
+                )
+                select exists(select 1 from ancestors where id = $2) as "exists!""#,
+                parent_id,
+                updated_category.id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+            if creates_cycle {
+                return Err(CategoryError::Cycle(updated_category.id).into());
+            }
+        }
+
+        sqlx::query!(
+            "update categories set name = $1, parent_id = $2 where id = $3",
+            updated_category.name,
+            updated_category.parent_id,
+            updated_category.id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_category(pool: &PgPool, id: i32) -> Result<()> {
+        let in_use = sqlx::query_scalar!(
+            r#"select exists(select 1 from categories where parent_id = $1)
+            or exists(select 1 from products where category_id = $1) as "in_use!""#,
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        if in_use {
+            return Err(CategoryError::NotEmpty(id).into());
+        }
+
+        let deleted = sqlx::query!("delete from categories where id = $1", id)
+            .execute(pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!("Category with id {} does not exist", id));
+        }
+
+        Ok(())
+    }
+}
@@ -1,10 +1,20 @@
-use super::PG_LIMIT;
+use super::bulk::{export_stream, parse_records, validate_records};
+use super::{
+    bulk_insert, fetch_page, insert_statements, lock_version, restore, soft_delete,
+    sync_id_sequence,
+};
 use crate::db_actions::{get_pool, Clearable, MockFillable};
-use color_eyre::Result;
-use sqlx::{PgPool, QueryBuilder};
+use axum::body::Bytes;
+use color_eyre::{eyre::eyre, Result};
+use futures_util::Stream;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashSet;
 use tracing::info;
 
-use crate::models::Customer;
+use crate::models::{
+    BulkFormat, Customer, CustomerFilter, CustomerRecord, DeleteError, ImportReport, ListParams,
+    Page,
+};
 use async_trait::async_trait;
 
 pub struct CustomerService;
@@ -26,11 +36,17 @@ impl MockFillable for CustomerService {
                 id: 1,
                 name: "Customer 1".to_string(),
                 address: "Address 1".to_string(),
+                region: "PL".to_string(),
+                deleted_at: None,
+                version: 1,
             },
             Customer {
                 id: 2,
                 name: "Customer 2".to_string(),
                 address: "Address 2".to_string(),
+                region: "DE".to_string(),
+                deleted_at: None,
+                version: 1,
             },
         ];
 
@@ -52,9 +68,10 @@ impl Clearable for CustomerService {
 impl CustomerService {
     pub async fn create_customer(pool: &PgPool, new_customer: Customer) -> Result<i32> {
         let new_customer_row: (i32,) =
-            sqlx::query_as("insert into customers (name, address) values ($1, $2) returning id")
+            sqlx::query_as("insert into customers (name, address, region) values ($1, $2, $3) returning id")
                 .bind(new_customer.name)
                 .bind(new_customer.address)
+                .bind(new_customer.region)
                 .fetch_one(pool)
                 .await?;
 
@@ -62,65 +79,286 @@ impl CustomerService {
     }
 
     pub async fn create_customers(pool: &PgPool, new_customers: &[Customer], with_id: bool) -> Result<()> {
-        let mut query_builder = match with_id {
+        let mut tx = pool.begin().await?;
+        match with_id {
             true => {
-                let mut query_builder = QueryBuilder::new("insert into customers (id, name, address) ");
-                query_builder.push_values(
-                    new_customers.iter().take(PG_LIMIT as usize / 3),
+                bulk_insert(
+                    &mut tx,
+                    "customers",
+                    &["id", "name", "address", "region"],
+                    new_customers,
                     |mut builder, customer| {
                         builder
                             .push_bind(customer.id)
                             .push_bind(&customer.name)
-                            .push_bind(&customer.address);
+                            .push_bind(&customer.address)
+                            .push_bind(&customer.region);
                     },
-                );
-                query_builder
+                )
+                .await?;
+                sync_id_sequence(&mut tx, "customers").await?;
             }
             false => {
-                let mut query_builder = QueryBuilder::new("insert into customers (name, address) ");
-                query_builder.push_values(
-                    new_customers.iter().take(PG_LIMIT as usize / 2),
+                bulk_insert(
+                    &mut tx,
+                    "customers",
+                    &["name", "address", "region"],
+                    new_customers,
                     |mut builder, customer| {
                         builder
                             .push_bind(&customer.name)
-                            .push_bind(&customer.address);
+                            .push_bind(&customer.address)
+                            .push_bind(&customer.region);
                     },
-                );
-                query_builder
+                )
+                .await?;
             }
-        };
-
-        info!("Executing group insert query: {}", query_builder.sql());
-        let query = query_builder.build();
-        query.execute(pool).await?;
+        }
+        tx.commit().await?;
 
         Ok(())
     }
 
     pub async fn get_customer(pool: &PgPool, id: i32) -> Result<Customer> {
         Ok(
-            sqlx::query_as!(Customer, "select * from customers where id = $1", id)
+            sqlx::query_as!(
+                Customer,
+                "select * from customers where id = $1 and deleted_at is null",
+                id
+            )
                 .fetch_one(pool)
                 .await?,
         )
     }
 
+    /// Soft deleted customers are included, as orders still refer to them.
+    pub async fn get_customers_by_ids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Customer>> {
+        Ok(sqlx::query_as!(Customer, "select * from customers where id = any($1)", ids)
+            .fetch_all(pool)
+            .await?)
+    }
+
     pub async fn get_all_customers(pool: &PgPool) -> Result<Vec<Customer>> {
-        Ok(sqlx::query_as!(Customer, "select * from customers")
+        Ok(sqlx::query_as!(Customer, "select * from customers where deleted_at is null")
             .fetch_all(pool)
             .await?)
     }
 
-    pub async fn update_customer(pool: &PgPool, updated_customer: Customer) -> Result<()> {
-        sqlx::query!(
-            "update customers set name = $1, address = $2 where id = $3",
+    pub async fn get_customers(
+        pool: &PgPool,
+        list_params: &ListParams,
+        filter: &CustomerFilter,
+    ) -> Result<Page<Customer>> {
+        fetch_page(
+            pool,
+            "customers",
+            "id, name, address, region, deleted_at, version",
+            &[
+                ("id", "id", "integer"),
+                ("name", "name", "text"),
+                ("region", "region", "text"),
+            ],
+            list_params,
+            |query_builder| {
+                if !filter.include_deleted {
+                    query_builder.push(" and deleted_at is null");
+                }
+                if let Some(name) = &filter.name {
+                    query_builder
+                        .push(" and name ilike '%' || ")
+                        .push_bind(name.clone())
+                        .push(" || '%'");
+                }
+                if let Some(region) = &filter.region {
+                    query_builder.push(" and region = ").push_bind(region.clone());
+                }
+            },
+        )
+        .await
+    }
+
+    /// Updates the customer if it is still at `version`, or at any version
+    /// when `None`, and returns its new version.
+    pub async fn update_customer(
+        pool: &PgPool,
+        updated_customer: Customer,
+        version: Option<i32>,
+    ) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        lock_version(&mut tx, "customers", "Customer", updated_customer.id, version).await?;
+        let new_version = sqlx::query_scalar!(
+            "update customers set name = $1, address = $2, region = $3 where id = $4 \
+            returning version",
             updated_customer.name,
             updated_customer.address,
+            updated_customer.region,
             updated_customer.id
         )
-        .execute(pool)
+        .fetch_one(&mut tx)
         .await?;
+        tx.commit().await?;
+
+        Ok(new_version)
+    }
+
+    /// Hard deletes take the addresses and the cart along and are refused
+    /// for customers with orders or user accounts.
+    pub async fn delete_customer(pool: &PgPool, id: i32, hard: bool) -> Result<()> {
+        if !hard {
+            return soft_delete(pool, "customers", id).await;
+        }
+
+        let mut tx = pool.begin().await?;
+        let references = sqlx::query!(
+            r#"select exists(select 1 from orders where customer_id = $1) as "ordered!",
+            exists(select 1 from users where customer_id = $1) as "has_user!""#,
+            id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        let referenced_by = match (references.ordered, references.has_user) {
+            (true, _) => Some("orders"),
+            (_, true) => Some("user accounts"),
+            _ => None,
+        };
+        if let Some(referenced_by) = referenced_by {
+            return Err(DeleteError::InUse {
+                resource: "Customer",
+                id,
+                referenced_by,
+            }
+            .into());
+        }
+
+        sqlx::query!("delete from cart_items where customer_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("delete from customer_addresses where customer_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        let deleted = sqlx::query!("delete from customers where id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!("Customer with id {} does not exist", id));
+        }
+        tx.commit().await?;
 
         Ok(())
     }
+
+    pub async fn restore_customer(pool: &PgPool, id: i32) -> Result<()> {
+        restore(pool, "customers", id).await
+    }
+
+    /// Creates customers for rows without an id or with an id that is not
+    /// taken yet and updates the others. Invalid rows are reported and left
+    /// out, and nothing is kept on a dry run.
+    pub async fn import_customers(
+        pool: &PgPool,
+        format: BulkFormat,
+        body: &[u8],
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let records = parse_records::<CustomerRecord>(format, body);
+        let total = records.len();
+        let mut seen_ids = HashSet::new();
+        let (records, errors) = validate_records(records, |record| match record {
+            record if record.name.trim().is_empty() => Some("Name must not be empty".to_string()),
+            record if record.region.trim().is_empty() => {
+                Some("Region must not be empty".to_string())
+            }
+            CustomerRecord { id: Some(id), .. } if *id <= 0 => Some(format!("Invalid id {}", id)),
+            CustomerRecord { id: Some(id), .. } if !seen_ids.insert(*id) => {
+                Some(format!("Customer {} appears more than once", id))
+            }
+            _ => None,
+        });
+        let customers: Vec<Customer> = records.into_iter().map(Customer::from).collect();
+
+        let mut tx = pool.begin().await?;
+        let (created, updated) = Self::upsert_customers(&mut tx, &customers).await?;
+        match dry_run {
+            true => tx.rollback().await?,
+            false => tx.commit().await?,
+        }
+        info!(
+            "Imported {} new and {} updated customers, {} rows failed",
+            created,
+            updated,
+            errors.len()
+        );
+
+        Ok(ImportReport {
+            dry_run,
+            total,
+            created,
+            updated,
+            errors,
+        })
+    }
+
+    /// Streams every customer that is not soft deleted, ordered by id.
+    pub fn export_customers(pool: PgPool, format: BulkFormat) -> impl Stream<Item = Result<Bytes>> {
+        export_stream(format, move |last_id, limit| {
+            let pool = pool.clone();
+            async move {
+                let customers = sqlx::query_as!(
+                    Customer,
+                    "select * from customers where deleted_at is null and id > $1 \
+                    order by id limit $2",
+                    last_id,
+                    limit
+                )
+                .fetch_all(&pool)
+                .await?;
+                Ok(customers
+                    .into_iter()
+                    .map(|customer| (customer.id, CustomerRecord::from(customer)))
+                    .collect())
+            }
+        })
+    }
+
+    /// Inserts the customers with id 0 under new ids and upserts the others
+    /// by id, returning how many were created and how many updated.
+    async fn upsert_customers(
+        conn: &mut PgConnection,
+        customers: &[Customer],
+    ) -> Result<(usize, usize)> {
+        let mut created = 0;
+        let mut updated = 0;
+        let statements = insert_statements(
+            "customers",
+            &["id", "name", "address", "region"],
+            customers,
+            |mut builder, customer| {
+                match customer.id {
+                    0 => builder.push("nextval(pg_get_serial_sequence('customers', 'id'))"),
+                    id => builder.push_bind(id),
+                };
+                builder
+                    .push_bind(&customer.name)
+                    .push_bind(&customer.address)
+                    .push_bind(&customer.region);
+            },
+        );
+        for mut query_builder in statements {
+            // rows that were inserted rather than updated have no xmax yet
+            query_builder.push(
+                " on conflict (id) do update set name = excluded.name, \
+                address = excluded.address, region = excluded.region returning xmax = 0",
+            );
+            let upserted: Vec<(bool,)> = query_builder
+                .build_query_as()
+                .fetch_all(&mut *conn)
+                .await?;
+            let inserted = upserted.iter().filter(|(inserted,)| *inserted).count();
+            created += inserted;
+            updated += upserted.len() - inserted;
+        }
+        sync_id_sequence(&mut *conn, "customers").await?;
+
+        Ok((created, updated))
+    }
 }
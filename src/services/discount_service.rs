@@ -0,0 +1,332 @@
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+
+use super::bulk_insert;
+use crate::models::{
+    order_status, Currency, DiscountCode, DiscountError, DiscountKind, Money, OrderLine,
+};
+use async_trait::async_trait;
+use chrono::Local;
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{PgConnection, PgPool};
+
+pub struct DiscountService;
+
+#[async_trait]
+impl MockFillable for DiscountService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let new_discount_codes = [
+            DiscountCode {
+                id: 0,
+                code: "WELCOME10".to_string(),
+                kind: DiscountKind::Percentage,
+                percent: Some(10),
+                amount: None,
+                valid_from: None,
+                valid_until: None,
+                max_uses: None,
+                max_uses_per_customer: Some(1),
+                min_order_value: None,
+                product_ids: vec![],
+            },
+            DiscountCode {
+                id: 0,
+                code: "PRODUCT1MINUS5".to_string(),
+                kind: DiscountKind::Fixed,
+                percent: None,
+                amount: Some(Money::new(500, Currency::Pln)),
+                valid_from: None,
+                valid_until: None,
+                max_uses: Some(100),
+                max_uses_per_customer: None,
+                min_order_value: Some(Money::new(2000, Currency::Pln)),
+                product_ids: vec![1],
+            },
+        ];
+
+        let pool = get_pool().await?;
+        for discount_code in new_discount_codes {
+            Self::create_discount_code(&pool, discount_code).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for DiscountService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from discount_codes_products")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from discount_codes")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl DiscountService {
+    pub async fn create_discount_code(pool: &PgPool, new_discount_code: DiscountCode) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        let new_discount_code_row: (i32,) = sqlx::query_as(
+            "insert into discount_codes (code, kind, percent, amount, valid_from, valid_until, \
+            max_uses, max_uses_per_customer, min_order_value) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning id",
+        )
+        .bind(&new_discount_code.code)
+        .bind(new_discount_code.kind)
+        .bind(new_discount_code.percent)
+        .bind(new_discount_code.amount)
+        .bind(new_discount_code.valid_from)
+        .bind(new_discount_code.valid_until)
+        .bind(new_discount_code.max_uses)
+        .bind(new_discount_code.max_uses_per_customer)
+        .bind(new_discount_code.min_order_value)
+        .fetch_one(&mut tx)
+        .await?;
+        let discount_code_id = new_discount_code_row.0;
+
+        Self::insert_products(&mut tx, discount_code_id, &new_discount_code.product_ids).await?;
+        tx.commit().await?;
+
+        Ok(discount_code_id)
+    }
+
+    pub async fn get_discount_code(pool: &PgPool, id: i32) -> Result<DiscountCode> {
+        Ok(sqlx::query_as!(
+            DiscountCode,
+            r#"select id, code, kind as "kind: DiscountKind", percent, amount as "amount: Money",
+            valid_from, valid_until, max_uses, max_uses_per_customer,
+            min_order_value as "min_order_value: Money",
+            array(select product_id from discount_codes_products where discount_code_id = id)
+                as "product_ids!"
+            from discount_codes where id = $1"#,
+            id
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    /// Reads the discount code inside the transaction of `conn`, keeping it
+    /// from changing until the transaction ends.
+    pub async fn lock_discount_code(conn: &mut PgConnection, id: i32) -> Result<DiscountCode> {
+        Ok(sqlx::query_as!(
+            DiscountCode,
+            r#"select id, code, kind as "kind: DiscountKind", percent, amount as "amount: Money",
+            valid_from, valid_until, max_uses, max_uses_per_customer,
+            min_order_value as "min_order_value: Money",
+            array(select product_id from discount_codes_products where discount_code_id = id)
+                as "product_ids!"
+            from discount_codes where id = $1 for update"#,
+            id
+        )
+        .fetch_one(conn)
+        .await?)
+    }
+
+    pub async fn get_all_discount_codes(pool: &PgPool) -> Result<Vec<DiscountCode>> {
+        Ok(sqlx::query_as!(
+            DiscountCode,
+            r#"select id, code, kind as "kind: DiscountKind", percent, amount as "amount: Money",
+            valid_from, valid_until, max_uses, max_uses_per_customer,
+            min_order_value as "min_order_value: Money",
+            array(select product_id from discount_codes_products where discount_code_id = id)
+                as "product_ids!"
+            from discount_codes"#
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    pub async fn update_discount_code(pool: &PgPool, updated_discount_code: DiscountCode) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "update discount_codes set code = $1, kind = $2, percent = $3, amount = $4, \
+            valid_from = $5, valid_until = $6, max_uses = $7, max_uses_per_customer = $8, \
+            min_order_value = $9 where id = $10",
+            updated_discount_code.code,
+            updated_discount_code.kind as DiscountKind,
+            updated_discount_code.percent,
+            updated_discount_code.amount as Option<Money>,
+            updated_discount_code.valid_from,
+            updated_discount_code.valid_until,
+            updated_discount_code.max_uses,
+            updated_discount_code.max_uses_per_customer,
+            updated_discount_code.min_order_value as Option<Money>,
+            updated_discount_code.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "delete from discount_codes_products where discount_code_id = $1",
+            updated_discount_code.id
+        )
+        .execute(&mut tx)
+        .await?;
+        Self::insert_products(
+            &mut tx,
+            updated_discount_code.id,
+            &updated_discount_code.product_ids,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Codes that were already redeemed by an order are kept, so that the
+    /// discount stored on the order still points to its origin.
+    pub async fn delete_discount_code(pool: &PgPool, id: i32) -> Result<()> {
+        let deleted = sqlx::query!(
+            "delete from discount_codes where id = $1 \
+            and not exists (select 1 from orders where discount_code_id = $1)",
+            id
+        )
+        .execute(pool)
+        .await?;
+
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!(
+                "Discount code with id {} does not exist or was already used",
+                id
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `code` can be redeemed by the customer for an order made of
+    /// `lines` and returns the id of the code together with the discount.
+    /// Locks the code row, so the usage limits hold under concurrent orders.
+    /// Cancelled and deleted orders do not count as uses.
+    pub async fn redeem(
+        conn: &mut PgConnection,
+        code: &str,
+        customer_id: i32,
+        lines: &[OrderLine],
+        subtotal: Money,
+    ) -> Result<(i32, Money)> {
+        let discount_code = sqlx::query_as!(
+            DiscountCode,
+            r#"select id, code, kind as "kind: DiscountKind", percent, amount as "amount: Money",
+            valid_from, valid_until, max_uses, max_uses_per_customer,
+            min_order_value as "min_order_value: Money",
+            array(select product_id from discount_codes_products where discount_code_id = id)
+                as "product_ids!"
+            from discount_codes where code = $1 for update"#,
+            code
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(DiscountError::UnknownCode)?;
+
+        let now = Local::now().naive_local();
+        if discount_code.valid_from.is_some_and(|valid_from| now < valid_from) {
+            return Err(DiscountError::NotYetValid.into());
+        }
+        if discount_code.valid_until.is_some_and(|valid_until| now > valid_until) {
+            return Err(DiscountError::Expired.into());
+        }
+
+        if let Some(max_uses) = discount_code.max_uses {
+            let uses = sqlx::query_scalar!(
+                r#"select count(*) as "count!" from orders
+                where discount_code_id = $1 and status <> $2 and deleted_at is null"#,
+                discount_code.id,
+                order_status::CANCELLED
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            if uses >= max_uses as i64 {
+                return Err(DiscountError::UsageLimitReached.into());
+            }
+        }
+        if let Some(max_uses_per_customer) = discount_code.max_uses_per_customer {
+            let uses = sqlx::query_scalar!(
+                r#"select count(*) as "count!" from orders
+                where discount_code_id = $1 and customer_id = $2
+                and status <> $3 and deleted_at is null"#,
+                discount_code.id,
+                customer_id,
+                order_status::CANCELLED
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            if uses >= max_uses_per_customer as i64 {
+                return Err(DiscountError::CustomerUsageLimitReached.into());
+            }
+        }
+
+        if let Some(min_order_value) = discount_code.min_order_value {
+            if min_order_value.currency != subtotal.currency {
+                return Err(DiscountError::CurrencyMismatch.into());
+            }
+            if subtotal.cents < min_order_value.cents {
+                return Err(DiscountError::MinOrderValueNotReached.into());
+            }
+        }
+
+        if !lines.iter().any(|line| Self::is_eligible(&discount_code, line)) {
+            return Err(DiscountError::NoEligibleProducts.into());
+        }
+
+        let discount = Self::discount_amount(&discount_code, lines, subtotal.currency)?;
+        Ok((discount_code.id, discount))
+    }
+
+    /// Computes the discount for `lines` without checking validity or usage
+    /// limits. Fixed discounts never exceed the value of eligible products.
+    pub fn discount_amount(
+        discount_code: &DiscountCode,
+        lines: &[OrderLine],
+        currency: Currency,
+    ) -> Result<Money> {
+        let mut eligible_subtotal = Money::zero(currency);
+        for line in lines.iter().filter(|line| Self::is_eligible(discount_code, line)) {
+            eligible_subtotal = eligible_subtotal.checked_add(line.line_total)?;
+        }
+
+        let discount = match discount_code.kind {
+            DiscountKind::Percentage => {
+                eligible_subtotal.percentage(discount_code.percent.unwrap_or_default())?
+            }
+            DiscountKind::Fixed => {
+                let amount = discount_code.amount.unwrap_or(Money::zero(currency));
+                if amount.currency != currency {
+                    return Err(DiscountError::CurrencyMismatch.into());
+                }
+                Money::new(amount.cents.min(eligible_subtotal.cents), currency)
+            }
+        };
+
+        Ok(discount)
+    }
+
+    fn is_eligible(discount_code: &DiscountCode, line: &OrderLine) -> bool {
+        discount_code.product_ids.is_empty() || discount_code.product_ids.contains(&line.product_id)
+    }
+
+    async fn insert_products(
+        conn: &mut PgConnection,
+        discount_code_id: i32,
+        product_ids: &[i32],
+    ) -> Result<()> {
+        if product_ids.is_empty() {
+            return Ok(());
+        }
+
+        bulk_insert(
+            conn,
+            "discount_codes_products",
+            &["discount_code_id", "product_id"],
+            product_ids,
+            |mut builder, product_id| {
+                builder.push_bind(discount_code_id).push_bind(*product_id);
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{
+    ImageError, Product, ProductImage, MAX_IMAGE_BYTES, MAX_IMAGE_DIMENSION, THUMBNAIL_SIZE,
+};
+use crate::setup::BLOB_STORE;
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use image::{io::Limits, io::Reader, ImageFormat};
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+pub struct ImageService;
+
+/// Uploaded image that passed validation, with its thumbnail.
+struct ProcessedImage {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    thumbnail: Vec<u8>,
+}
+
+#[async_trait]
+impl Clearable for ImageService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        let keys = sqlx::query!("select blob_key, thumbnail_key from product_images")
+            .fetch_all(&pool)
+            .await?;
+        sqlx::query!("delete from product_images")
+            .execute(&pool)
+            .await?;
+        for key in keys {
+            BLOB_STORE.delete(&key.blob_key).await?;
+            BLOB_STORE.delete(&key.thumbnail_key).await?;
+        }
+        Ok(())
+    }
+}
+
+impl ImageService {
+    /// Validates the image, stores it with a thumbnail and appends it to the
+    /// images of the product.
+    pub async fn add_product_image(
+        pool: &PgPool,
+        product_id: i32,
+        data: Vec<u8>,
+    ) -> Result<ProductImage> {
+        let exists = sqlx::query_scalar!(
+            r#"select exists(select 1 from products where id = $1) as "exists!""#,
+            product_id
+        )
+        .fetch_one(pool)
+        .await?;
+        if !exists {
+            return Err(ImageError::UnknownProduct(product_id).into());
+        }
+
+        let image = Self::process(&data)?;
+        let content_type = image.format.to_mime_type();
+        let name = format!(
+            "products/{}/{}",
+            product_id,
+            Utc::now().timestamp_nanos()
+        );
+        let blob_key = format!("{}.{}", name, image.format.extensions_str()[0]);
+        let thumbnail_key = format!("{}_thumb.png", name);
+
+        BLOB_STORE.put(&blob_key, data, content_type).await?;
+        BLOB_STORE
+            .put(&thumbnail_key, image.thumbnail, ImageFormat::Png.to_mime_type())
+            .await?;
+
+        let inserted = sqlx::query!(
+            "insert into product_images \
+            (product_id, blob_key, thumbnail_key, content_type, width, height, position) \
+            values ($1, $2, $3, $4, $5, $6, \
+            (select coalesce(max(position) + 1, 0) from product_images where product_id = $1)) \
+            returning id, position",
+            product_id,
+            blob_key,
+            thumbnail_key,
+            content_type,
+            image.width as i32,
+            image.height as i32
+        )
+        .fetch_one(pool)
+        .await;
+        let inserted = match inserted {
+            Ok(inserted) => inserted,
+            Err(e) => {
+                // nothing points to the blobs without the row
+                BLOB_STORE.delete(&blob_key).await?;
+                BLOB_STORE.delete(&thumbnail_key).await?;
+                return Err(e.into());
+            }
+        };
+        info!("Stored image {} of product {}", blob_key, product_id);
+
+        Ok(ProductImage {
+            id: inserted.id,
+            product_id,
+            url: BLOB_STORE.url(&blob_key),
+            thumbnail_url: BLOB_STORE.url(&thumbnail_key),
+            content_type: content_type.to_string(),
+            width: image.width as i32,
+            height: image.height as i32,
+            position: inserted.position,
+        })
+    }
+
+    /// Returns the images of every product, in their display order.
+    pub async fn get_product_images(
+        pool: &PgPool,
+        product_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<ProductImage>>> {
+        let rows = sqlx::query!(
+            "select id, product_id, blob_key, thumbnail_key, content_type, width, height, position \
+            from product_images where product_id = any($1) order by product_id, position, id",
+            product_ids
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut images: HashMap<i32, Vec<ProductImage>> = HashMap::new();
+        for row in rows {
+            images.entry(row.product_id).or_default().push(ProductImage {
+                id: row.id,
+                product_id: row.product_id,
+                url: BLOB_STORE.url(&row.blob_key),
+                thumbnail_url: BLOB_STORE.url(&row.thumbnail_key),
+                content_type: row.content_type,
+                width: row.width,
+                height: row.height,
+                position: row.position,
+            });
+        }
+        Ok(images)
+    }
+
+    /// Fills in the images of the products with one query for all of them.
+    pub async fn attach_images(pool: &PgPool, products: &mut [Product]) -> Result<()> {
+        let product_ids: Vec<i32> = products.iter().map(|product| product.id).collect();
+        let mut images = Self::get_product_images(pool, &product_ids).await?;
+        for product in products.iter_mut() {
+            product.images = images.remove(&product.id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    pub async fn delete_product_image(pool: &PgPool, id: i32) -> Result<()> {
+        let deleted = sqlx::query!(
+            "delete from product_images where id = $1 returning blob_key, thumbnail_key",
+            id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| eyre!("Image with id {} does not exist", id))?;
+
+        Self::delete_blobs([deleted.blob_key, deleted.thumbnail_key]).await;
+        Ok(())
+    }
+
+    /// Deletes blobs of images that are already gone for clients, so
+    /// failures only leave some unused blobs behind.
+    pub async fn delete_blobs(keys: impl IntoIterator<Item = String>) {
+        for key in keys {
+            if let Err(e) = BLOB_STORE.delete(&key).await {
+                warn!("Failed to delete blob {}: {}", key, e);
+            }
+        }
+    }
+
+    /// Accepts PNG, JPEG and WebP images within the size limits, recognized
+    /// by their content rather than the type claimed by the client.
+    fn process(data: &[u8]) -> Result<ProcessedImage, ImageError> {
+        if data.is_empty() {
+            return Err(ImageError::NoImage);
+        }
+        if data.len() > MAX_IMAGE_BYTES {
+            return Err(ImageError::TooLarge(data.len()));
+        }
+        let format = image::guess_format(data)
+            .map_err(|_| ImageError::UnsupportedType("unknown".to_string()))?;
+        if !matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+        ) {
+            return Err(ImageError::UnsupportedType(
+                format.to_mime_type().to_string(),
+            ));
+        }
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+        limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+        let mut reader = Reader::with_format(Cursor::new(data), format);
+        reader.limits(limits);
+        let image = reader
+            .decode()
+            .map_err(|e| ImageError::InvalidImage(e.to_string()))?;
+
+        let mut thumbnail = Vec::new();
+        image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Png)
+            .map_err(|e| ImageError::InvalidImage(e.to_string()))?;
+
+        Ok(ProcessedImage {
+            format,
+            width: image.width(),
+            height: image.height(),
+            thumbnail,
+        })
+    }
+}
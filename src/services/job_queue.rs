@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use color_eyre::{eyre::eyre, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{JobError, JobFilter, ListParams, Page, QueuedJob};
+
+use super::fetch_page;
+
+/// How long a job may run. Jobs still running after that are failed, and
+/// jobs of workers that died are picked up again once their lease is over.
+const JOB_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// How often workers look for due jobs when there was nothing to do.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Jobs one worker runs at the same time.
+const CONCURRENCY: usize = 16;
+
+/// How long succeeded jobs are kept around for inspection.
+const JOB_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const JOB_COLUMNS: &str =
+    "id, kind, payload, status, attempts, run_at, last_error, created_at, finished_at";
+
+/// Work done in the background, stored in the queue as its JSON. Jobs run at
+/// least once, so they have to cope with being run again after a worker died
+/// halfway through.
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Name jobs of the type are queued under.
+    const KIND: &'static str;
+
+    /// Attempts after which the job is given up on.
+    const MAX_ATTEMPTS: i32 = 5;
+
+    async fn run(&self, pool: &PgPool) -> Result<()>;
+
+    /// How long to wait before the next attempt after `attempts` failed
+    /// ones. Doubles with every failure, starting at 10 seconds and going up
+    /// to an hour.
+    fn retry_delay(attempts: i32) -> Duration {
+        let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+        (Duration::from_secs(10) * 2u32.pow(doublings)).min(Duration::from_secs(60 * 60))
+    }
+
+    /// Called after the last attempt failed with `error`.
+    async fn give_up(&self, _pool: &PgPool, _error: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct JobQueue;
+
+#[async_trait]
+impl Clearable for JobQueue {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from jobs").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl JobQueue {
+    /// Queues the job to run right away. Pass the transaction of the change
+    /// the job is for to queue it only if the change is committed.
+    pub async fn enqueue<'c, J: Job>(executor: impl PgExecutor<'c>, job: &J) -> Result<i32> {
+        Ok(sqlx::query_scalar!(
+            "insert into jobs (kind, payload) values ($1, $2) returning id",
+            J::KIND,
+            serde_json::to_value(job)?
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+
+    /// Queues the job to run once `run_at` has come.
+    pub async fn schedule<'c, J: Job>(
+        executor: impl PgExecutor<'c>,
+        job: &J,
+        run_at: NaiveDateTime,
+    ) -> Result<i32> {
+        Ok(sqlx::query_scalar!(
+            "insert into jobs (kind, payload, run_at) values ($1, $2, $3) returning id",
+            J::KIND,
+            serde_json::to_value(job)?,
+            run_at
+        )
+        .fetch_one(executor)
+        .await?)
+    }
+
+    pub async fn get_job(pool: &PgPool, id: i32) -> Result<QueuedJob> {
+        sqlx::query_as(&format!("select {JOB_COLUMNS} from jobs where id = $1"))
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| eyre!("Job with id {} does not exist", id))
+    }
+
+    pub async fn get_jobs(
+        pool: &PgPool,
+        list_params: &ListParams,
+        filter: &JobFilter,
+    ) -> Result<Page<QueuedJob>> {
+        fetch_page(
+            pool,
+            "jobs",
+            JOB_COLUMNS,
+            &[
+                ("id", "id", "integer"),
+                ("kind", "kind", "text"),
+                ("status", "status", "job_status"),
+                ("attempts", "attempts", "integer"),
+                ("run_at", "run_at", "timestamp"),
+                ("created_at", "created_at", "timestamp"),
+            ],
+            list_params,
+            |query_builder| {
+                if let Some(status) = filter.status {
+                    query_builder.push(" and status = ").push_bind(status);
+                }
+                if let Some(kind) = &filter.kind {
+                    query_builder.push(" and kind = ").push_bind(kind.clone());
+                }
+            },
+        )
+        .await
+    }
+
+    /// Queues a failed job again, with as many attempts left as a new one.
+    pub async fn retry_job(pool: &PgPool, id: i32) -> Result<QueuedJob> {
+        let retried: Option<QueuedJob> = sqlx::query_as(&format!(
+            "update jobs set status = 'queued', attempts = 0, run_at = now(), finished_at = null \
+            where id = $1 and status = 'failed' returning {JOB_COLUMNS}"
+        ))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(job) = retried {
+            return Ok(job);
+        }
+
+        Self::get_job(pool, id).await?;
+        Err(JobError::NotFailed(id).into())
+    }
+}
+
+/// Runs jobs of one type, knowing their type only by the name they were
+/// queued under.
+#[async_trait]
+trait JobHandler: Send + Sync {
+    fn max_attempts(&self) -> i32;
+
+    fn retry_delay(&self, attempts: i32) -> Duration;
+
+    async fn run(&self, pool: &PgPool, payload: serde_json::Value) -> Result<()>;
+
+    async fn give_up(&self, pool: &PgPool, payload: serde_json::Value, error: &str)
+        -> Result<()>;
+}
+
+struct TypedHandler<J>(PhantomData<fn() -> J>);
+
+#[async_trait]
+impl<J: Job> JobHandler for TypedHandler<J> {
+    fn max_attempts(&self) -> i32 {
+        J::MAX_ATTEMPTS
+    }
+
+    fn retry_delay(&self, attempts: i32) -> Duration {
+        J::retry_delay(attempts)
+    }
+
+    async fn run(&self, pool: &PgPool, payload: serde_json::Value) -> Result<()> {
+        let job: J = serde_json::from_value(payload)?;
+        job.run(pool).await
+    }
+
+    async fn give_up(
+        &self,
+        pool: &PgPool,
+        payload: serde_json::Value,
+        error: &str,
+    ) -> Result<()> {
+        let job: J = serde_json::from_value(payload)?;
+        job.give_up(pool, error).await
+    }
+}
+
+/// Job taken off the queue by a worker.
+#[derive(sqlx::FromRow)]
+struct ClaimedJob {
+    id: i32,
+    kind: String,
+    payload: serde_json::Value,
+    attempts: i32,
+}
+
+/// Runs queued jobs of the registered types. Any number of workers, in this
+/// instance or others, can run side by side, each job is claimed by one of
+/// them at a time.
+pub struct JobWorker {
+    pool: PgPool,
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+}
+
+impl JobWorker {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Lets the worker run jobs of type `J`. Jobs of types no worker knows
+    /// stay queued.
+    pub fn register<J: Job>(mut self) -> Self {
+        self.handlers
+            .insert(J::KIND, Arc::new(TypedHandler::<J>(PhantomData)));
+        self
+    }
+
+    /// Runs jobs for as long as the server runs. Failing to reach the
+    /// database is logged and tried again on the next round.
+    pub async fn run(self) -> Result<()> {
+        info!(
+            "Running jobs of kinds {:?}",
+            self.handlers.keys().collect::<Vec<_>>()
+        );
+        let worker = Arc::new(self);
+        let slots = Arc::new(Semaphore::new(CONCURRENCY));
+        let mut cleaned_up_at: Option<Instant> = None;
+
+        loop {
+            // wait for a free slot before claiming anything
+            drop(slots.acquire().await?);
+            let free = slots.available_permits();
+            let claimed = worker.claim(free).await.unwrap_or_else(|e| {
+                warn!("Could not claim jobs: {e}");
+                Vec::new()
+            });
+            let idle = claimed.len() < free;
+
+            for job in claimed {
+                let slot = slots.clone().acquire_owned().await?;
+                let worker = worker.clone();
+                tokio::spawn(async move {
+                    worker.execute(job).await;
+                    drop(slot);
+                });
+            }
+
+            if idle {
+                if cleaned_up_at.is_none_or(|at| at.elapsed() >= CLEANUP_INTERVAL) {
+                    if let Err(e) = worker.clean_up().await {
+                        warn!("Could not clean up jobs: {e}");
+                    }
+                    cleaned_up_at = Some(Instant::now());
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    /// Takes up to `limit` due jobs off the queue, along with jobs whose
+    /// worker did not finish them in time.
+    async fn claim(&self, limit: usize) -> Result<Vec<ClaimedJob>> {
+        let kinds: Vec<&str> = self.handlers.keys().copied().collect();
+        Ok(sqlx::query_as(
+            "with due as ( \
+                select id from jobs where kind = any($1) and ( \
+                    (status = 'queued' and run_at <= now()) \
+                    or (status = 'running' and locked_until < now()) \
+                ) \
+                order by run_at limit $2 for update skip locked \
+            ) \
+            update jobs set status = 'running', attempts = attempts + 1, \
+                locked_until = now() + make_interval(secs => $3) \
+            from due where jobs.id = due.id \
+            returning jobs.id, kind, payload, attempts",
+        )
+        .bind(kinds)
+        .bind(limit as i64)
+        .bind(JOB_LEASE.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Runs the job and records how it went. Failed jobs are queued again
+    /// after their retry delay, until they run out of attempts.
+    async fn execute(&self, job: ClaimedJob) {
+        let Some(handler) = self.handlers.get(job.kind.as_str()) else {
+            return;
+        };
+        let outcome = tokio::time::timeout(JOB_LEASE, handler.run(&self.pool, job.payload.clone()))
+            .await
+            .unwrap_or_else(|_| Err(eyre!("Job ran for longer than {:?}", JOB_LEASE)));
+
+        let recorded = match outcome {
+            Ok(()) => {
+                sqlx::query!(
+                    "update jobs set status = 'succeeded', locked_until = null, \
+                    last_error = null, finished_at = now() where id = $1",
+                    job.id
+                )
+                .execute(&self.pool)
+                .await
+            }
+            Err(e) if job.attempts >= handler.max_attempts() => {
+                let error = e.to_string();
+                warn!(
+                    "Giving up on {} job {} after {} attempts: {error}",
+                    job.kind, job.id, job.attempts
+                );
+                if let Err(e) = handler.give_up(&self.pool, job.payload, &error).await {
+                    warn!("Could not give up on {} job {}: {e}", job.kind, job.id);
+                }
+                sqlx::query!(
+                    "update jobs set status = 'failed', locked_until = null, \
+                    last_error = $2, finished_at = now() where id = $1",
+                    job.id,
+                    error
+                )
+                .execute(&self.pool)
+                .await
+            }
+            Err(e) => {
+                let delay = handler.retry_delay(job.attempts);
+                warn!("{} job {} failed, retrying in {:?}: {e}", job.kind, job.id, delay);
+                sqlx::query!(
+                    "update jobs set status = 'queued', locked_until = null, last_error = $2, \
+                    run_at = now() + make_interval(secs => $3) where id = $1",
+                    job.id,
+                    e.to_string(),
+                    delay.as_secs_f64()
+                )
+                .execute(&self.pool)
+                .await
+            }
+        };
+        if let Err(e) = recorded {
+            warn!("Could not record the outcome of {} job {}: {e}", job.kind, job.id);
+        }
+    }
+
+    /// Deletes succeeded jobs once they are old enough. Failed ones are kept
+    /// until they are retried.
+    async fn clean_up(&self) -> Result<()> {
+        sqlx::query!(
+            "delete from jobs where status = 'succeeded' \
+            and finished_at < now() - make_interval(secs => $1)",
+            JOB_RETENTION.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
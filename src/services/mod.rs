@@ -1,11 +1,239 @@
+mod address_service;
+mod blob_store;
+mod bulk;
+mod cart_service;
+mod category_service;
 mod customer_service;
+mod discount_service;
+mod image_service;
+mod job_queue;
+mod order_event_service;
 mod order_service;
+mod payment_provider;
+mod payment_service;
 mod product_service;
+mod refund_service;
+mod shipment_service;
+mod tax_service;
 pub mod user_service;
+mod variant_service;
+mod webhook_service;
 
+pub use address_service::AddressService;
+pub use blob_store::{Blob, BlobStore, LocalBlobStore, S3BlobStore, S3Config};
+pub use cart_service::CartService;
+pub use category_service::CategoryService;
 pub use customer_service::CustomerService;
+pub use discount_service::DiscountService;
+pub use image_service::ImageService;
+pub use job_queue::{Job, JobQueue, JobWorker};
+pub use order_event_service::{OrderEventService, ORDER_EVENTS_CHANNEL};
 pub use order_service::OrderService;
+pub use payment_provider::{FakePaymentProvider, PaymentProvider, PAYMENT_SIGNATURE_HEADER};
+pub use payment_service::PaymentService;
 pub use product_service::ProductService;
+pub use refund_service::RefundService;
+pub use shipment_service::ShipmentService;
+pub use tax_service::TaxService;
 pub use user_service::UserService;
+pub use variant_service::VariantService;
+pub use webhook_service::{
+    DeliverWebhook, DispatchWebhookEvent, WebhookService, MAX_DELIVERY_ATTEMPTS,
+    WEBHOOK_DELIVERY_HEADER, WEBHOOK_EVENT_HEADER, WEBHOOK_SIGNATURE_HEADER,
+};
 
 static PG_LIMIT: u16 = u16::MAX;
+
+/// Rows inserted with explicit ids do not advance the `serial` sequence of the
+/// table, so move it past the highest id to keep later inserts from colliding.
+async fn sync_id_sequence<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    table: &str,
+) -> color_eyre::Result<()> {
+    sqlx::query(&format!(
+        "select setval(pg_get_serial_sequence('{table}', 'id'), coalesce(max(id), 0) + 1, false) from {table}"
+    ))
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Multi-row inserts of `rows` into `columns` of `table`, split so that none
+/// of them binds more parameters than Postgres allows in one statement.
+/// `push_row` pushes the values of a row, one per column.
+fn insert_statements<'a, T>(
+    table: &str,
+    columns: &[&str],
+    rows: &'a [T],
+    mut push_row: impl FnMut(sqlx::query_builder::Separated<'_, 'a, sqlx::Postgres, &'static str>, &'a T),
+) -> Vec<sqlx::QueryBuilder<'a, sqlx::Postgres>> {
+    let rows_per_statement = (PG_LIMIT as usize / columns.len().max(1)).max(1);
+    rows.chunks(rows_per_statement)
+        .map(|chunk| {
+            let mut query_builder =
+                sqlx::QueryBuilder::new(format!("insert into {table} ({}) ", columns.join(", ")));
+            query_builder.push_values(chunk, &mut push_row);
+            query_builder
+        })
+        .collect()
+}
+
+/// Inserts all `rows`, in as many statements as the bind parameter limit
+/// requires. Run it in a transaction to insert either every row or none.
+async fn bulk_insert<'a, T>(
+    conn: &mut sqlx::PgConnection,
+    table: &str,
+    columns: &[&str],
+    rows: &'a [T],
+    push_row: impl FnMut(sqlx::query_builder::Separated<'_, 'a, sqlx::Postgres, &'static str>, &'a T),
+) -> color_eyre::Result<()> {
+    for mut query_builder in insert_statements(table, columns, rows, push_row) {
+        tracing::info!("Executing group insert query: {}", query_builder.sql());
+        query_builder.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Hides the row from reads and lists, keeping it and everything referring
+/// to it in place.
+async fn soft_delete(pool: &sqlx::PgPool, table: &str, id: i32) -> color_eyre::Result<()> {
+    let deleted = sqlx::query(&format!(
+        "update {table} set deleted_at = now() where id = $1 and deleted_at is null"
+    ))
+    .bind(id)
+    .execute(pool)
+    .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(color_eyre::eyre::eyre!(
+            "No row with id {} to delete in {}",
+            id,
+            table
+        ));
+    }
+    Ok(())
+}
+
+/// Brings a soft deleted row back.
+async fn restore(pool: &sqlx::PgPool, table: &str, id: i32) -> color_eyre::Result<()> {
+    let restored = sqlx::query(&format!(
+        "update {table} set deleted_at = null where id = $1 and deleted_at is not null"
+    ))
+    .bind(id)
+    .execute(pool)
+    .await?;
+    if restored.rows_affected() == 0 {
+        return Err(color_eyre::eyre::eyre!(
+            "No deleted row with id {} to restore in {}",
+            id,
+            table
+        ));
+    }
+    Ok(())
+}
+
+/// Locks the row for the rest of the transaction and checks that it is still
+/// at the version the update was made against, if the update names one.
+async fn lock_version(
+    conn: &mut sqlx::PgConnection,
+    table: &str,
+    resource: &'static str,
+    id: i32,
+    expected: Option<i32>,
+) -> color_eyre::Result<()> {
+    let current: i32 = sqlx::query_scalar(&format!(
+        "select version from {table} where id = $1 and deleted_at is null for update"
+    ))
+    .bind(id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| color_eyre::eyre::eyre!("{} with id {} does not exist", resource, id))?;
+    match expected {
+        Some(expected) if expected != current => Err(crate::models::VersionError::Mismatch {
+            resource,
+            id,
+            expected,
+            current,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Fetches one page of `table`, together with the number of rows matching
+/// the filters pushed by `push_filters`. Filters are appended after a
+/// `where` clause, each one starting with `and`.
+async fn fetch_page<T>(
+    pool: &sqlx::PgPool,
+    table: &str,
+    columns: &str,
+    sort_columns: &[crate::models::SortColumn<'_>],
+    list_params: &crate::models::ListParams,
+    push_filters: impl Fn(&mut sqlx::QueryBuilder<sqlx::Postgres>),
+) -> color_eyre::Result<crate::models::Page<T>>
+where
+    T: for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+{
+    let order_by = list_params.order_by(sort_columns)?;
+    let sort_key = list_params.sort_key(sort_columns)?;
+
+    let mut count_query = sqlx::QueryBuilder::new(format!("select count(*) from {table} where true"));
+    push_filters(&mut count_query);
+    let (total,): (i64,) = count_query.build_query_as().fetch_one(pool).await?;
+
+    let mut query = sqlx::QueryBuilder::new(format!(
+        "select {columns}, {sort_key} as sort_key from {table} where true"
+    ));
+    push_filters(&mut query);
+    list_params.push_after(&mut query, sort_columns)?;
+    query.push(order_by);
+    tracing::info!("Executing list query: {}", query.sql());
+    let rows = fetch_rows(query, pool, list_params).await?;
+
+    page_of(rows, total, list_params)
+}
+
+/// Fetches the rows of a page built by `query`, one more than the page holds
+/// to tell whether another page follows. Casting the values of a tampered
+/// cursor fails with a data exception, which is reported as a bad cursor.
+async fn fetch_rows<'e>(
+    mut query: sqlx::QueryBuilder<'_, sqlx::Postgres>,
+    executor: impl sqlx::PgExecutor<'e>,
+    list_params: &crate::models::ListParams,
+) -> color_eyre::Result<Vec<sqlx::postgres::PgRow>> {
+    query.push(" limit ").push_bind(list_params.limit + 1);
+    query.push(" offset ").push_bind(list_params.offset);
+    query.build().fetch_all(executor).await.map_err(|e| match e {
+        sqlx::Error::Database(ref db_error)
+            if list_params.has_cursor()
+                && db_error.code().is_some_and(|code| code.starts_with("22")) =>
+        {
+            crate::models::ListError::InvalidCursor.into()
+        }
+        e => e.into(),
+    })
+}
+
+/// Turns the rows fetched by `fetch_rows` into a page, the sort key of its
+/// last row leads to the next page when there is one.
+fn page_of<T>(
+    mut rows: Vec<sqlx::postgres::PgRow>,
+    total: i64,
+    list_params: &crate::models::ListParams,
+) -> color_eyre::Result<crate::models::Page<T>>
+where
+    T: for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow>,
+{
+    use sqlx::Row;
+
+    let has_next = rows.len() as i64 > list_params.limit;
+    rows.truncate(list_params.limit as usize);
+    let next = match rows.last() {
+        Some(row) if has_next => {
+            Some(row.try_get::<sqlx::types::Json<Vec<serde_json::Value>>, _>("sort_key")?.0)
+        }
+        _ => None,
+    };
+    let items = rows.iter().map(T::from_row).collect::<Result<_, _>>()?;
+
+    Ok(crate::models::Page { items, total, next })
+}
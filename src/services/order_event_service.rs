@@ -0,0 +1,63 @@
+use std::future::ready;
+
+use color_eyre::Result;
+use futures_util::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use crate::models::OrderEvent;
+
+/// Postgres channel the `orders_notify_event` trigger sends to.
+pub const ORDER_EVENTS_CHANNEL: &str = "order_events";
+
+/// Events a slow subscriber may fall behind by before it misses some.
+const ORDER_EVENTS_CAPACITY: usize = 1024;
+
+/// Order events received by this instance, for all of its subscribers.
+static ORDER_EVENTS: Lazy<broadcast::Sender<OrderEvent>> =
+    Lazy::new(|| broadcast::channel(ORDER_EVENTS_CAPACITY).0);
+
+pub struct OrderEventService;
+
+impl OrderEventService {
+    /// Passes order events sent by the database on to the subscribers of
+    /// this instance. Events come from the database rather than from the
+    /// handlers, so every instance sees the changes made through all others.
+    /// The listener reconnects by itself when the connection drops, events
+    /// sent in the meantime are lost.
+    pub async fn listen(pool: PgPool) -> Result<()> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(ORDER_EVENTS_CHANNEL).await?;
+        info!("Listening for order events");
+
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<OrderEvent>(notification.payload()) {
+                // nobody may be subscribed, which is fine
+                Ok(event) => _ = ORDER_EVENTS.send(event),
+                Err(e) => warn!("Invalid order event {:?}: {e}", notification.payload()),
+            }
+        }
+    }
+
+    /// Events of orders from now on, only of the customer's orders when a
+    /// customer id is given.
+    pub fn subscribe(customer_id: Option<i32>) -> impl Stream<Item = OrderEvent> {
+        futures_util::stream::unfold(ORDER_EVENTS.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Subscriber fell behind, {skipped} order events skipped")
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .filter(move |event| {
+            ready(customer_id.is_none_or(|customer_id| event.customer_id == customer_id))
+        })
+    }
+}
@@ -1,19 +1,36 @@
 use crate::db_actions::{get_pool, Clearable, MockFillable};
 use chrono::Local;
-use color_eyre::Result;
-use sqlx::{PgPool, QueryBuilder};
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{PgConnection, PgPool};
 use std::collections::HashMap;
-use tracing::info;
 
-use super::PG_LIMIT;
+use super::{bulk_insert, fetch_page, lock_version, restore, soft_delete, sync_id_sequence};
 
+use super::address_service::AddressService;
 use super::customer_service::CustomerService;
+use super::discount_service::DiscountService;
 use super::product_service::ProductService;
+use super::tax_service::TaxService;
 use crate::models::*;
 use async_trait::async_trait;
 
 pub struct OrderService;
 
+const ORDER_COLUMNS: &str = "id, customer_id, status, created_at, deleted_at, version";
+
+/// Line items of an order with the product and variant they are for, as one
+/// JSON array.
+const ORDER_PRODUCTS_COLUMN: &str = "(select coalesce(json_agg(json_build_object(\
+    'product_id', products_in_orders.product_id, 'variant_id', products_in_orders.variant_id, \
+    'name', products.name, 'sku', product_variants.sku, \
+    'quantity', products_in_orders.quantity, 'unit_price', products_in_orders.unit_price, \
+    'refunded_quantity', products_in_orders.refunded_quantity) \
+    order by products_in_orders.product_id, products_in_orders.variant_id), '[]') \
+    from products_in_orders \
+    join products on products.id = products_in_orders.product_id \
+    join product_variants on product_variants.id = products_in_orders.variant_id \
+    where products_in_orders.order_id = orders.id) as products";
+
 macro_rules! create_orders {
     ($a: expr, $b: expr) => {
         OrderService::create_orders($a, $b, false)
@@ -36,6 +53,8 @@ impl MockFillable for OrderService {
             customer_id: customers_in_db[0].id,
             status: "In progress".to_string(),
             created_at: Local::now().naive_local(),
+            deleted_at: None,
+            version: 1,
         };
         let mut products_in_order = HashMap::new();
         products_in_order.insert(&products_in_db[0], 1);
@@ -47,6 +66,8 @@ impl MockFillable for OrderService {
             customer_id: customers_in_db[0].id,
             status: "In progress".to_string(),
             created_at: Local::now().naive_local(),
+            deleted_at: None,
+            version: 1,
         };
         let mut products_in_order = HashMap::new();
         products_in_order.insert(&products_in_db[0], 6);
@@ -58,6 +79,8 @@ impl MockFillable for OrderService {
             customer_id: customers_in_db[1].id,
             status: "New".to_string(),
             created_at: Local::now().naive_local(),
+            deleted_at: None,
+            version: 1,
         };
         let mut products_in_order = HashMap::new();
         products_in_order.insert(&products_in_db[0], 3);
@@ -73,6 +96,9 @@ impl MockFillable for OrderService {
 impl Clearable for OrderService {
     async fn clear(&self) -> Result<()> {
         let pool = get_pool().await?;
+        sqlx::query!("delete from order_tax_lines")
+            .execute(&pool)
+            .await?;
         sqlx::query!("delete from products_in_orders")
             .execute(&pool)
             .await?;
@@ -87,6 +113,7 @@ impl OrderService {
         customer_orders: &HashMap<Order, HashMap<&Product, i32>>,
         with_id: bool,
     ) -> Result<()> {
+        let mut tx = pool.begin().await?;
         for (new_order, products_in_order) in customer_orders.iter() {
             let curr_order_row: (i32,) = match with_id {
                 true => {
@@ -97,7 +124,7 @@ impl OrderService {
                     .bind(new_order.customer_id)
                     .bind(&new_order.status)
                     .bind(new_order.created_at)
-                    .fetch_one(pool)
+                    .fetch_one(&mut tx)
                     .await?
                 }
                 false => {
@@ -107,46 +134,64 @@ impl OrderService {
                     .bind(new_order.customer_id)
                     .bind(&new_order.status)
                     .bind(new_order.created_at)
-                    .fetch_one(pool)
+                    .fetch_one(&mut tx)
                     .await?
                 }
             };
             let curr_order_id = curr_order_row.0;
 
-            let products_in_order: Vec<ProductInOrder> = products_in_order
+            let quantities = ProductService::resolve_variants(
+                &mut tx,
+                &products_in_order
+                    .iter()
+                    .map(|(product, amount)| (product.id, *amount))
+                    .collect(),
+                &HashMap::new(),
+            )
+            .await?;
+            let variant_ids: Vec<i32> = quantities.keys().copied().collect();
+            let prices = ProductService::get_variant_prices(&mut tx, &variant_ids).await?;
+            let products_in_order: Vec<ProductInOrder> = quantities
                 .iter()
-                .map(|(product, amount)| ProductInOrder {
+                .map(|(variant_id, amount)| ProductInOrder {
                     order_id: curr_order_id,
-                    product_id: product.id,
+                    product_id: prices[variant_id].0,
+                    variant_id: *variant_id,
                     quantity: *amount,
+                    unit_price: prices[variant_id].1,
+                    refunded_quantity: 0,
                 })
                 .collect();
 
-            let mut query_builder = QueryBuilder::new(
-                "insert into products_in_orders (order_id, product_id, quantity) ",
-            );
-            query_builder.push_values(
-                products_in_order.into_iter().take(PG_LIMIT as usize / 3),
-                |mut builder, product_in_order| {
-                    builder
-                        .push_bind(product_in_order.order_id)
-                        .push_bind(product_in_order.product_id)
-                        .push_bind(product_in_order.quantity);
-                },
-            );
-
-            info!("Executing group insert query: {}", query_builder.sql());
-            let query = query_builder.build();
-            query.execute(pool).await?;
+            let (lines, _) = Self::price_lines(&products_in_order)?;
+            Self::insert_products_in_order(&mut tx, &products_in_order).await?;
+            let taxes = TaxService::compute_taxes(
+                &mut tx,
+                curr_order_id,
+                new_order.customer_id,
+                &lines,
+                None,
+            )
+            .await?;
+            TaxService::store_order_taxes(&mut tx, curr_order_id, &taxes).await?;
         }
+        if with_id {
+            sync_id_sequence(&mut tx, "orders").await?;
+        }
+        tx.commit().await?;
 
         Ok(())
     }
 
     pub async fn get_order(pool: &PgPool, order_id: i32) -> Result<Order> {
-        let order = sqlx::query_as!(Order, "select * from orders where id = $1", order_id)
-            .fetch_one(pool)
-            .await?;
+        let order = sqlx::query_as!(
+            Order,
+            "select id, customer_id, status, created_at, deleted_at, version from orders \
+            where id = $1 and deleted_at is null",
+            order_id
+        )
+        .fetch_one(pool)
+        .await?;
 
         Ok(order)
     }
@@ -155,21 +200,60 @@ impl OrderService {
         pool: &PgPool,
         order_id: i32,
     ) -> Result<OrderWithProducts> {
-        let order = sqlx::query_as!(Order, "select * from orders where id = $1", order_id)
-            .fetch_one(pool)
-            .await?;
+        let order = sqlx::query!(
+            r#"select orders.id, customer_id, status, created_at, version,
+            discount as "discount: Money", discount_codes.code as "discount_code?"
+            from orders left join discount_codes on discount_codes.id = orders.discount_code_id
+            where orders.id = $1 and orders.deleted_at is null"#,
+            order_id
+        )
+        .fetch_one(pool)
+        .await?;
 
         let products_in_order = sqlx::query_as!(
             ProductInOrder,
-            "select * from products_in_orders where order_id = $1",
+            r#"select product_id, variant_id, order_id, quantity,
+            unit_price as "unit_price: Money", refunded_quantity
+            from products_in_orders where order_id = $1 order by product_id, variant_id"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await?;
+        let default_variant_ids: Vec<i32> = sqlx::query_scalar!(
+            "select variant_id from products_in_orders \
+            join product_variants on product_variants.id = variant_id \
+            where order_id = $1 and is_default",
             order_id
         )
         .fetch_all(pool)
         .await?;
 
         let mut products = HashMap::new();
-        for product in products_in_order {
-            products.insert(product.product_id, product.quantity);
+        let mut variants = HashMap::new();
+        for product in products_in_order.iter() {
+            if default_variant_ids.contains(&product.variant_id) {
+                products.insert(product.product_id, product.quantity);
+            } else {
+                variants.insert(product.variant_id, product.quantity);
+            }
+        }
+        let (lines, subtotal) = Self::price_lines(&products_in_order)?;
+        let taxes = TaxService::get_order_taxes(pool, order_id).await?;
+        let shipping_address = AddressService::get_shipping_address(pool, order_id).await?;
+        let mut tax: Option<Money> = None;
+        for tax_line in taxes.iter() {
+            tax = Some(match tax {
+                Some(tax) => tax.checked_add(tax_line.tax)?,
+                None => tax_line.tax,
+            });
+        }
+
+        let mut total = subtotal;
+        if let (Some(subtotal), Some(discount)) = (total, order.discount) {
+            total = Some(subtotal.checked_sub(discount)?);
+        }
+        if let (Some(subtotal), Some(tax)) = (total, tax) {
+            total = Some(subtotal.checked_add(tax)?);
         }
 
         Ok(OrderWithProducts {
@@ -178,114 +262,506 @@ impl OrderService {
             status: order.status,
             created_at: order.created_at,
             products,
+            variants,
+            discount_code: order.discount_code,
+            shipping_address_id: None,
+            shipping_address,
+            lines,
+            subtotal,
+            discount: order.discount,
+            taxes,
+            tax,
+            total,
+            version: order.version,
         })
     }
 
-    pub async fn get_all_orders(pool: &PgPool) -> Result<Vec<Order>> {
-        let mut all_orders = Vec::new();
-        let all_customers = sqlx::query_as!(Customer, "select * from customers")
-            .fetch_all(pool)
-            .await?;
+    /// Lists orders in a single query, whoever they belong to. Line items
+    /// are aggregated into each order when included.
+    pub async fn get_orders(
+        pool: &PgPool,
+        list_params: &ListParams,
+        filter: &OrderFilter,
+    ) -> Result<Page<ListedOrder>> {
+        let columns = match filter.include {
+            Some(OrderInclude::Products) => format!("{ORDER_COLUMNS}, {ORDER_PRODUCTS_COLUMN}"),
+            None => ORDER_COLUMNS.to_string(),
+        };
+        fetch_page(
+            pool,
+            "orders",
+            &columns,
+            &[
+                ("id", "id", "integer"),
+                ("customer_id", "customer_id", "integer"),
+                ("status", "status", "text"),
+                ("created_at", "created_at", "timestamp"),
+            ],
+            list_params,
+            |query_builder| {
+                if !filter.include_deleted {
+                    query_builder.push(" and deleted_at is null");
+                }
+                if let Some(status) = &filter.status {
+                    query_builder.push(" and status = ").push_bind(status.clone());
+                }
+                if let Some(customer_id) = filter.customer_id {
+                    query_builder.push(" and customer_id = ").push_bind(customer_id);
+                }
+                if let Some(created_from) = filter.created_from {
+                    query_builder.push(" and created_at >= ").push_bind(created_from);
+                }
+                if let Some(created_to) = filter.created_to {
+                    query_builder.push(" and created_at <= ").push_bind(created_to);
+                }
+            },
+        )
+        .await
+    }
+
+    /// Loads what `expand` asks to embed in the given orders, each given
+    /// with the id of its customer. Every resource is loaded once, however
+    /// many orders refer to it.
+    pub async fn get_expansions(
+        pool: &PgPool,
+        orders: &[(i32, i32)],
+        expand: &Expand,
+    ) -> Result<HashMap<i32, OrderExpansion>> {
+        let order_ids: Vec<i32> = orders.iter().map(|(order_id, _)| *order_id).collect();
+        let mut expansions: HashMap<i32, OrderExpansion> = order_ids
+            .iter()
+            .map(|order_id| (*order_id, OrderExpansion::default()))
+            .collect();
+
+        if expand.contains(Expansion::Customer) {
+            let customer_ids: Vec<i32> =
+                orders.iter().map(|(_, customer_id)| *customer_id).collect();
+            let customers: HashMap<i32, Customer> =
+                CustomerService::get_customers_by_ids(pool, &customer_ids)
+                    .await?
+                    .into_iter()
+                    .map(|customer| (customer.id, customer))
+                    .collect();
+            for (order_id, customer_id) in orders.iter() {
+                if let (Some(expansion), Some(customer)) =
+                    (expansions.get_mut(order_id), customers.get(customer_id))
+                {
+                    expansion.customer = Some(customer.clone());
+                }
+            }
+        }
 
-        for customer in all_customers {
-            let mut customer_orders = sqlx::query_as!(
-                Order,
-                "select * from orders where customer_id = $1",
-                customer.id
+        if expand.contains(Expansion::Products) {
+            let ordered = sqlx::query!(
+                "select distinct order_id, product_id from products_in_orders \
+                where order_id = any($1) order by order_id, product_id",
+                &order_ids
             )
             .fetch_all(pool)
             .await?;
+            let product_ids: Vec<i32> = ordered.iter().map(|row| row.product_id).collect();
+            let products: HashMap<i32, Product> =
+                ProductService::get_products_by_ids(pool, &product_ids)
+                    .await?
+                    .into_iter()
+                    .map(|product| (product.id, product))
+                    .collect();
+            for expansion in expansions.values_mut() {
+                expansion.products = Some(Vec::new());
+            }
+            for row in ordered {
+                if let (Some(expansion), Some(product)) =
+                    (expansions.get_mut(&row.order_id), products.get(&row.product_id))
+                {
+                    expansion.products.get_or_insert_with(Vec::new).push(product.clone());
+                }
+            }
+        }
+
+        Ok(expansions)
+    }
+
+    /// Line items of the given orders with the product and variant they are
+    /// for, per order id. Orders without any are left out.
+    pub async fn get_ordered_products(
+        pool: &PgPool,
+        order_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<OrderedProduct>>> {
+        let rows = sqlx::query!(
+            r#"select products_in_orders.order_id, products_in_orders.product_id,
+            products_in_orders.variant_id, products.name, product_variants.sku,
+            products_in_orders.quantity, products_in_orders.unit_price as "unit_price: Money",
+            products_in_orders.refunded_quantity
+            from products_in_orders
+            join products on products.id = products_in_orders.product_id
+            join product_variants on product_variants.id = products_in_orders.variant_id
+            where products_in_orders.order_id = any($1)
+            order by products_in_orders.product_id, products_in_orders.variant_id"#,
+            order_ids
+        )
+        .fetch_all(pool)
+        .await?;
 
-            all_orders.append(&mut customer_orders);
+        let mut ordered_products: HashMap<i32, Vec<OrderedProduct>> = HashMap::new();
+        for row in rows {
+            ordered_products
+                .entry(row.order_id)
+                .or_default()
+                .push(OrderedProduct {
+                    product_id: row.product_id,
+                    variant_id: row.variant_id,
+                    name: row.name,
+                    sku: row.sku,
+                    quantity: row.quantity,
+                    unit_price: row.unit_price,
+                    refunded_quantity: row.refunded_quantity,
+                });
         }
 
-        Ok(all_orders)
+        Ok(ordered_products)
     }
 
     pub async fn create_order(pool: &PgPool, new_order: OrderWithProducts) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        let curr_order_id = Self::create_order_in_transaction(&mut tx, new_order).await?;
+        tx.commit().await?;
+
+        Ok(curr_order_id)
+    }
+
+    /// Same as `create_order`, but leaves committing to the caller, so the
+    /// order can be created atomically with other changes.
+    pub async fn create_order_in_transaction(
+        tx: &mut PgConnection,
+        new_order: OrderWithProducts,
+    ) -> Result<i32> {
+        let quantities =
+            ProductService::resolve_variants(&mut *tx, &new_order.products, &new_order.variants)
+                .await?;
+        let variant_ids: Vec<i32> = quantities.keys().copied().collect();
+        let current_prices = ProductService::get_variant_prices(&mut *tx, &variant_ids).await?;
+        Self::ensure_single_currency(current_prices.values().map(|(_, price)| price))?;
+        ProductService::take_from_stock(&mut *tx, &quantities).await?;
+
+        let mut products_in_order: Vec<ProductInOrder> = quantities
+            .iter()
+            .map(|(variant_id, amount)| ProductInOrder {
+                order_id: new_order.id,
+                product_id: current_prices[variant_id].0,
+                variant_id: *variant_id,
+                quantity: *amount,
+                unit_price: current_prices[variant_id].1,
+                refunded_quantity: 0,
+            })
+            .collect();
+
+        let (lines, subtotal) = Self::price_lines(&products_in_order)?;
+        let (discount_code_id, discount) = match &new_order.discount_code {
+            Some(code) => {
+                let subtotal = subtotal.ok_or(DiscountError::NoEligibleProducts)?;
+                let (discount_code_id, discount) = DiscountService::redeem(
+                    &mut *tx,
+                    code,
+                    new_order.customer_id,
+                    &lines,
+                    subtotal,
+                )
+                .await?;
+                (Some(discount_code_id), Some(discount))
+            }
+            None => (None, None),
+        };
+
         let curr_order_row: (i32,) = sqlx::query_as(
-            "insert into orders (customer_id, status, created_at) values ($1, $2, $3) returning id",
+            "insert into orders (customer_id, status, created_at, discount_code_id, discount) \
+            values ($1, $2, $3, $4, $5) returning id",
         )
         .bind(new_order.customer_id)
         .bind(&new_order.status)
         .bind(Local::now().naive_local())
-        .fetch_one(pool)
+        .bind(discount_code_id)
+        .bind(discount)
+        .fetch_one(&mut *tx)
         .await?;
         let curr_order_id = curr_order_row.0;
 
-        let products_in_order: Vec<ProductInOrder> = new_order
-            .products
+        for product_in_order in products_in_order.iter_mut() {
+            product_in_order.order_id = curr_order_id;
+        }
+        Self::insert_products_in_order(tx, &products_in_order).await?;
+        AddressService::snapshot_shipping_address(
+            tx,
+            curr_order_id,
+            new_order.customer_id,
+            new_order.shipping_address_id,
+        )
+        .await?;
+        // taxed where the order ships, so only once its address is known
+        let taxes = TaxService::compute_taxes(
+            tx,
+            curr_order_id,
+            new_order.customer_id,
+            &lines,
+            discount,
+        )
+        .await?;
+        TaxService::store_order_taxes(tx, curr_order_id, &taxes).await?;
+
+        Ok(curr_order_id)
+    }
+
+    /// Updates the order if it is still at `version`, or at any version when
+    /// `None`, and returns its new version.
+    pub async fn update_order(
+        pool: &PgPool,
+        order: OrderWithProducts,
+        version: Option<i32>,
+    ) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        lock_version(&mut tx, "orders", "Order", order.id, version).await?;
+        let status = sqlx::query_scalar!("select status from orders where id = $1", order.id)
+            .fetch_one(&mut tx)
+            .await?;
+        if order_status::RETURNED.contains(&status.as_str()) {
+            return Err(RefundError::NotUpdatable(status).into());
+        }
+
+        let current_lines = sqlx::query!(
+            r#"select product_id, variant_id, quantity, refunded_quantity,
+            unit_price as "unit_price: Money"
+            from products_in_orders where order_id = $1"#,
+            order.id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        let quantities =
+            ProductService::resolve_variants(&mut tx, &order.products, &order.variants).await?;
+
+        // refunded units stay refunded, so no line can shrink below them
+        let refunded_quantities: HashMap<i32, i32> = current_lines
             .iter()
-            .map(|(product, amount)| ProductInOrder {
-                order_id: curr_order_id,
-                product_id: *product,
-                quantity: *amount,
-            })
+            .map(|line| (line.variant_id, line.refunded_quantity))
             .collect();
+        for (variant_id, refunded_quantity) in refunded_quantities.iter() {
+            if quantities.get(variant_id).copied().unwrap_or_default() < *refunded_quantity {
+                return Err(RefundError::BelowRefunded(*variant_id).into());
+            }
+        }
 
-        let mut query_builder =
-            QueryBuilder::new("insert into products_in_orders (order_id, product_id, quantity) ");
-        query_builder.push_values(
-            products_in_order.into_iter().take(PG_LIMIT as usize / 3),
-            |mut builder, product_in_order| {
-                builder
-                    .push_bind(product_in_order.order_id)
-                    .push_bind(product_in_order.product_id)
-                    .push_bind(product_in_order.quantity);
-            },
-        );
+        // only the difference to what the order already holds goes through stock
+        let mut stock_changes = quantities.clone();
+        for line in current_lines.iter() {
+            *stock_changes.entry(line.variant_id).or_default() -= line.quantity;
+        }
+        ProductService::take_from_stock(&mut tx, &stock_changes).await?;
 
-        info!("Executing group insert query: {}", query_builder.sql());
-        let query = query_builder.build();
-        query.execute(pool).await?;
+        // variants already in the order keep the price they were ordered at
+        let mut unit_prices: HashMap<i32, (i32, Money)> = current_lines
+            .into_iter()
+            .map(|line| (line.variant_id, (line.product_id, line.unit_price)))
+            .collect();
 
-        Ok(curr_order_id)
-    }
+        let new_variant_ids: Vec<i32> = quantities
+            .keys()
+            .filter(|variant_id| !unit_prices.contains_key(variant_id))
+            .copied()
+            .collect();
+        unit_prices.extend(ProductService::get_variant_prices(&mut tx, &new_variant_ids).await?);
+        Self::ensure_single_currency(
+            quantities
+                .keys()
+                .map(|variant_id| &unit_prices[variant_id].1),
+        )?;
+
+        let products_in_order: Vec<ProductInOrder> = quantities
+            .iter()
+            .map(|(variant_id, amount)| ProductInOrder {
+                order_id: order.id,
+                product_id: unit_prices[variant_id].0,
+                variant_id: *variant_id,
+                quantity: *amount,
+                unit_price: unit_prices[variant_id].1,
+                refunded_quantity: refunded_quantities.get(variant_id).copied().unwrap_or_default(),
+            })
+            .collect();
 
-    pub async fn update_order(pool: &PgPool, order: OrderWithProducts) -> Result<()> {
-        sqlx::query!(
-            "update orders set customer_id = $1, status = $2, created_at = $3 where id = $4",
+        // a redeemed discount follows the new products, but its limits and
+        // validity window were already checked when the order was created
+        let discount_code_id = sqlx::query_scalar!(
+            "select discount_code_id from orders where id = $1",
+            order.id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        let (lines, subtotal) = Self::price_lines(&products_in_order)?;
+        let discount = match discount_code_id {
+            Some(discount_code_id) => {
+                let discount_code =
+                    DiscountService::lock_discount_code(&mut tx, discount_code_id).await?;
+                let currency = subtotal.map(|subtotal| subtotal.currency).unwrap_or_default();
+                Some(DiscountService::discount_amount(&discount_code, &lines, currency)?)
+            }
+            None => None,
+        };
+        let taxes =
+            TaxService::compute_taxes(&mut tx, order.id, order.customer_id, &lines, discount)
+                .await?;
+
+        let new_version = sqlx::query_scalar!(
+            "update orders set customer_id = $1, status = $2, created_at = $3, discount = $4 \
+            where id = $5 returning version",
             order.customer_id,
             order.status,
             order.created_at,
+            discount as Option<Money>,
             order.id
         )
-        .execute(pool)
+        .fetch_one(&mut tx)
         .await?;
 
         sqlx::query!(
             "delete from products_in_orders where order_id = $1",
             order.id
         )
-        .execute(pool)
+        .execute(&mut tx)
         .await?;
 
-        let products_in_order: Vec<ProductInOrder> = order
-            .products
-            .iter()
-            .map(|(product, amount)| ProductInOrder {
-                order_id: order.id,
-                product_id: *product,
-                quantity: *amount,
-            })
+        Self::insert_products_in_order(&mut tx, &products_in_order).await?;
+        TaxService::store_order_taxes(&mut tx, order.id, &taxes).await?;
+        tx.commit().await?;
+
+        Ok(new_version)
+    }
+
+    /// Soft deleted orders keep their products out of stock, cancel them
+    /// first to put the products back. Hard deletes are refused for orders
+    /// that were ever paid for, so payments always keep their orders, and
+    /// return the products of orders that were not cancelled to stock.
+    pub async fn delete_order(pool: &PgPool, id: i32, hard: bool) -> Result<()> {
+        if !hard {
+            return soft_delete(pool, "orders", id).await;
+        }
+
+        let mut tx = pool.begin().await?;
+        let status = sqlx::query_scalar!("select status from orders where id = $1 for update", id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| eyre!("Order with id {} does not exist", id))?;
+        let paid = sqlx::query_scalar!(
+            r#"select exists(select 1 from payment_intents where order_id = $1) as "paid!""#,
+            id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        if paid {
+            return Err(DeleteError::InUse {
+                resource: "Order",
+                id,
+                referenced_by: "payments",
+            }
+            .into());
+        }
+
+        if status != order_status::CANCELLED {
+            let returned_quantities = sqlx::query!(
+                "select variant_id, quantity - refunded_quantity as \"returned!\" \
+                from products_in_orders where order_id = $1",
+                id
+            )
+            .fetch_all(&mut tx)
+            .await?
+            .into_iter()
+            .map(|line| (line.variant_id, -line.returned))
             .collect();
+            ProductService::take_from_stock(&mut tx, &returned_quantities).await?;
+        }
+        sqlx::query!("delete from order_tax_lines where order_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("delete from order_shipping_addresses where order_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("delete from products_in_orders where order_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("delete from orders where id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
 
-        let mut query_builder =
-            QueryBuilder::new("insert into products_in_orders (order_id, product_id, quantity) ");
-        query_builder.push_values(
-            products_in_order.into_iter().take(PG_LIMIT as usize / 3),
+        Ok(())
+    }
+
+    pub async fn restore_order(pool: &PgPool, id: i32) -> Result<()> {
+        restore(pool, "orders", id).await
+    }
+
+    fn ensure_single_currency<'a>(prices: impl IntoIterator<Item = &'a Money>) -> Result<()> {
+        let mut prices = prices.into_iter();
+        if let Some(first) = prices.next() {
+            if let Some(other) = prices.find(|price| price.currency != first.currency) {
+                return Err(eyre!(
+                    "Order cannot mix currencies: {} and {}",
+                    first.currency,
+                    other.currency
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_products_in_order(
+        conn: &mut PgConnection,
+        products_in_order: &[ProductInOrder],
+    ) -> Result<()> {
+        bulk_insert(
+            conn,
+            "products_in_orders",
+            &[
+                "order_id",
+                "product_id",
+                "variant_id",
+                "quantity",
+                "unit_price",
+                "refunded_quantity",
+            ],
+            products_in_order,
             |mut builder, product_in_order| {
                 builder
                     .push_bind(product_in_order.order_id)
                     .push_bind(product_in_order.product_id)
-                    .push_bind(product_in_order.quantity);
+                    .push_bind(product_in_order.variant_id)
+                    .push_bind(product_in_order.quantity)
+                    .push_bind(product_in_order.unit_price)
+                    .push_bind(product_in_order.refunded_quantity);
             },
-        );
+        )
+        .await
+    }
 
-        info!("Executing group insert query: {}", query_builder.sql());
-        let query = query_builder.build();
-        query.execute(pool).await?;
+    /// Prices every line with its snapshotted unit price and sums them up.
+    /// Returns `None` as the subtotal for an order without any products.
+    fn price_lines(products_in_order: &[ProductInOrder]) -> Result<(Vec<OrderLine>, Option<Money>)> {
+        let mut lines = Vec::with_capacity(products_in_order.len());
+        let mut subtotal: Option<Money> = None;
+
+        for product_in_order in products_in_order {
+            let line_total = product_in_order
+                .unit_price
+                .checked_mul(product_in_order.quantity)?;
+            subtotal = Some(match subtotal {
+                Some(subtotal) => subtotal.checked_add(line_total)?,
+                None => line_total,
+            });
+            lines.push(OrderLine {
+                product_id: product_in_order.product_id,
+                variant_id: product_in_order.variant_id,
+                quantity: product_in_order.quantity,
+                unit_price: product_in_order.unit_price,
+                line_total,
+            });
+        }
 
-        Ok(())
+        Ok((lines, subtotal))
     }
 }
@@ -0,0 +1,136 @@
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{order_status, Money, PaymentError, PaymentEvent, PaymentIntent, PaymentStatus};
+use crate::setup::PAYMENT_PROVIDER;
+use async_trait::async_trait;
+use chrono::Local;
+use color_eyre::Result;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use super::order_service::OrderService;
+
+pub struct PaymentService;
+
+#[async_trait]
+impl Clearable for PaymentService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from payment_intents")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl PaymentService {
+    /// Starts a payment of the order's current total with the configured
+    /// payment provider.
+    pub async fn create_payment_intent(pool: &PgPool, order_id: i32) -> Result<PaymentIntent> {
+        let order = OrderService::get_order_with_products(pool, order_id).await?;
+        let amount = order
+            .total
+            .filter(|total| total.cents > 0)
+            .ok_or(PaymentError::NothingToPay)?;
+
+        let provider_reference = PAYMENT_PROVIDER.create_payment(order_id, amount).await?;
+        info!(
+            "Created payment {} of {} for order {}",
+            provider_reference, amount, order_id
+        );
+
+        let payment_intent = sqlx::query_as!(
+            PaymentIntent,
+            r#"insert into payment_intents (order_id, provider, provider_reference, amount)
+            values ($1, $2, $3, $4)
+            returning id, order_id, provider, provider_reference, amount as "amount: Money",
+            status as "status: PaymentStatus", created_at, updated_at"#,
+            order_id,
+            PAYMENT_PROVIDER.name(),
+            provider_reference,
+            amount as Money
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(payment_intent)
+    }
+
+    pub async fn get_payment_intent(pool: &PgPool, id: i32) -> Result<PaymentIntent> {
+        Ok(sqlx::query_as!(
+            PaymentIntent,
+            r#"select id, order_id, provider, provider_reference, amount as "amount: Money",
+            status as "status: PaymentStatus", created_at, updated_at
+            from payment_intents where id = $1"#,
+            id
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    /// Verifies and applies a webhook call of the configured provider, moving
+    /// both the payment and its order to the reported status.
+    pub async fn handle_webhook(pool: &PgPool, payload: &[u8], signature: &str) -> Result<()> {
+        let event = PAYMENT_PROVIDER.parse_webhook(payload, signature)?;
+        Self::apply_event(pool, event).await
+    }
+
+    async fn apply_event(pool: &PgPool, event: PaymentEvent) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        let payment_intent = sqlx::query!(
+            r#"select id, order_id, status as "status: PaymentStatus" from payment_intents
+            where provider = $1 and provider_reference = $2 for update"#,
+            PAYMENT_PROVIDER.name(),
+            event.provider_reference
+        )
+        .fetch_one(&mut tx)
+        .await?;
+
+        // providers retry deliveries, so an already applied event is fine
+        if payment_intent.status == event.status {
+            warn!(
+                "Payment {} is already {:?}",
+                event.provider_reference, event.status
+            );
+            return Ok(());
+        }
+        if !payment_intent.status.can_become(event.status) {
+            return Err(PaymentError::InvalidTransition {
+                from: payment_intent.status,
+                to: event.status,
+            }
+            .into());
+        }
+
+        sqlx::query!(
+            "update payment_intents set status = $1, updated_at = $2 where id = $3",
+            event.status as PaymentStatus,
+            Local::now().naive_local(),
+            payment_intent.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let new_order_status = match event.status {
+            PaymentStatus::Succeeded => Some(order_status::PAID),
+            PaymentStatus::Failed => Some(order_status::PAYMENT_FAILED),
+            PaymentStatus::Refunded => Some(order_status::REFUNDED),
+            PaymentStatus::Pending => None,
+        };
+        if let Some(new_order_status) = new_order_status {
+            sqlx::query!(
+                "update orders set status = $1 where id = $2",
+                new_order_status,
+                payment_intent.order_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        info!(
+            "Payment {} of order {} is now {:?}",
+            event.provider_reference, payment_intent.order_id, event.status
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,50 @@
 use crate::db_actions::{get_pool, Clearable, MockFillable};
 
-use super::PG_LIMIT;
-use crate::models::Product;
+use super::bulk::{export_stream, parse_records, validate_records};
+use super::image_service::ImageService;
+use super::{
+    bulk_insert, fetch_page, fetch_rows, insert_statements, lock_version, page_of, restore,
+    soft_delete, sync_id_sequence,
+};
+use crate::models::{
+    BulkFormat, Currency, DeleteError, ImportReport, ListParams, Money, Page, Product,
+    ProductFilter, ProductRecord, SearchError, SortOrder, StockError, VariantError,
+};
 use async_trait::async_trait;
-use color_eyre::Result;
-use sqlx::{PgPool, QueryBuilder};
+use axum::body::Bytes;
+use color_eyre::{eyre::eyre, Result};
+use futures_util::Stream;
+use sqlx::{query_builder::Separated, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, HashSet};
 use tracing::info;
 
 pub struct ProductService;
 
+/// How similar a word of the search query has to be to a word of a product
+/// name to match it despite typos, between 0 and 1.
+const TYPO_SIMILARITY_THRESHOLD: &str = "0.4";
+
+/// Columns of `Product`, with the stock taken from the default variant.
+const PRODUCT_COLUMNS: &str = "id, name, price, available, tax_category, \
+    (select stock from product_variants where product_id = products.id and is_default) as stock, \
+    description, tags, category_id, deleted_at, version";
+
+/// Stock of a product, the alias in `PRODUCT_COLUMNS` cannot be filtered by.
+const STOCK_COLUMN: &str =
+    "(select stock from product_variants where product_id = products.id and is_default)";
+
+/// Columns bound when inserting products, in the order they are pushed.
+const PRODUCT_INSERT_COLUMNS: [&str; 8] = [
+    "id",
+    "name",
+    "price",
+    "available",
+    "tax_category",
+    "description",
+    "tags",
+    "category_id",
+];
+
 macro_rules! create_products {
     ($a: expr, $b: expr) => {
         ProductService::create_products($a, $b, false)
@@ -25,14 +61,30 @@ impl MockFillable for ProductService {
             Product {
                 id: 1,
                 name: "Product 1".to_string(),
-                price: 1,
+                price: Money::new(1999, Currency::Pln),
                 available: true,
+                tax_category: "standard".to_string(),
+                stock: Some(1000),
+                description: "Everyday cotton t-shirt in a regular fit".to_string(),
+                tags: vec!["clothing".to_string(), "cotton".to_string()],
+                category_id: Some(2),
+                deleted_at: None,
+                version: 1,
+                images: Vec::new(),
             },
             Product {
                 id: 2,
                 name: "Product 2".to_string(),
-                price: 2,
+                price: Money::new(4999, Currency::Pln),
                 available: true,
+                tax_category: "reduced".to_string(),
+                stock: None,
+                description: "Hardcover cookbook with seasonal vegetarian recipes".to_string(),
+                tags: vec!["books".to_string(), "cooking".to_string()],
+                category_id: Some(4),
+                deleted_at: None,
+                version: 1,
+                images: Vec::new(),
             },
         ];
 
@@ -53,30 +105,62 @@ impl Clearable for ProductService {
 
 impl ProductService {
     pub async fn create_product(pool: &PgPool, new_product: Product) -> Result<i32> {
+        let mut tx = pool.begin().await?;
         let new_product_row: (i32,) = sqlx::query_as(
-            "insert into products (name, price, available) values ($1, $2, $3) returning id",
+            "insert into products (name, price, available, tax_category, description, tags, \
+            category_id) values ($1, $2, $3, $4, $5, $6, $7) returning id",
         )
         .bind(new_product.name)
         .bind(new_product.price)
         .bind(new_product.available)
-        .fetch_one(pool)
+        .bind(new_product.tax_category)
+        .bind(new_product.description)
+        .bind(new_product.tags)
+        .bind(new_product.category_id)
+        .fetch_one(&mut tx)
         .await?;
+        let product_id = new_product_row.0;
 
-        Ok(new_product_row.0)
+        Self::create_default_variants(&mut tx, &[(product_id, new_product.stock)]).await?;
+        tx.commit().await?;
+
+        Ok(product_id)
     }
 
-    pub async fn update_product(pool: &PgPool, updated_product: Product) -> Result<()> {
-        sqlx::query!(
-            "update products set name = $1, price = $2, available = $3 where id = $4",
+    /// Updates the product if it is still at `version`, or at any version
+    /// when `None`, and returns its new version.
+    pub async fn update_product(
+        pool: &PgPool,
+        updated_product: Product,
+        version: Option<i32>,
+    ) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        lock_version(&mut tx, "products", "Product", updated_product.id, version).await?;
+        let new_version = sqlx::query_scalar!(
+            "update products set name = $1, price = $2, available = $3, tax_category = $4, \
+            description = $5, tags = $6, category_id = $7 where id = $8 returning version",
             updated_product.name,
-            updated_product.price,
+            updated_product.price as Money,
             updated_product.available,
+            updated_product.tax_category,
+            updated_product.description,
+            &updated_product.tags,
+            updated_product.category_id,
             updated_product.id
         )
-        .execute(pool)
+        .fetch_one(&mut tx)
         .await?;
 
-        Ok(())
+        sqlx::query!(
+            "update product_variants set stock = $1 where product_id = $2 and is_default",
+            updated_product.stock,
+            updated_product.id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(new_version)
     }
 
     pub async fn create_products(
@@ -84,56 +168,552 @@ impl ProductService {
         new_products: &[Product],
         with_id: bool,
     ) -> Result<()> {
-        let mut query_builder = match with_id {
-            true => {
-                let mut query_builder =
-                    QueryBuilder::new("insert into products (id, name, price, available) ");
-                query_builder.push_values(
-                    new_products.iter().take(PG_LIMIT as usize / 4),
-                    |mut builder, product| {
-                        builder
-                            .push_bind(product.id)
-                            .push_bind(&product.name)
-                            .push_bind(product.price)
-                            .push_bind(product.available);
-                    },
-                );
-                query_builder
-            }
+        let mut tx = pool.begin().await?;
+
+        // ids are taken up front, so the default variants can point to them
+        let product_ids: Vec<i32> = match with_id {
+            true => new_products.iter().map(|product| product.id).collect(),
             false => {
-                let mut query_builder =
-                    QueryBuilder::new("insert into products (name, price, available) ");
-                query_builder.push_values(
-                    new_products.iter().take(PG_LIMIT as usize / 3),
-                    |mut builder, product| {
-                        builder
-                            .push_bind(&product.name)
-                            .push_bind(product.price)
-                            .push_bind(product.available);
-                    },
-                );
-                query_builder
+                sqlx::query_scalar(
+                    "select nextval(pg_get_serial_sequence('products', 'id'))::int \
+                    from generate_series(1, $1)",
+                )
+                .bind(new_products.len() as i32)
+                .fetch_all(&mut tx)
+                .await?
             }
         };
 
-        info!("Executing group insert query: {}", query_builder.sql());
-        let query = query_builder.build();
-        query.execute(pool).await?;
+        let rows: Vec<(&i32, &Product)> = product_ids.iter().zip(new_products).collect();
+        bulk_insert(&mut tx, "products", &PRODUCT_INSERT_COLUMNS, &rows, Self::push_product_row)
+            .await?;
+
+        let default_variants: Vec<(i32, Option<i32>)> = product_ids
+            .iter()
+            .zip(new_products.iter())
+            .map(|(product_id, product)| (*product_id, product.stock))
+            .collect();
+        Self::create_default_variants(&mut tx, &default_variants).await?;
+        tx.commit().await?;
+        if with_id {
+            sync_id_sequence(pool, "products").await?;
+        }
 
         Ok(())
     }
 
     pub async fn get_product(pool: &PgPool, id: i32) -> Result<Product> {
-        Ok(
-            sqlx::query_as!(Product, "select * from products where id = $1", id)
+        let mut product: Product =
+            sqlx::query_as(&format!(
+                "select {PRODUCT_COLUMNS} from products where id = $1 and deleted_at is null"
+            ))
+                .bind(id)
                 .fetch_one(pool)
-                .await?,
-        )
+                .await?;
+        ImageService::attach_images(pool, std::slice::from_mut(&mut product)).await?;
+
+        Ok(product)
+    }
+
+    /// Soft deleted products are included, as orders still refer to them.
+    pub async fn get_products_by_ids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Product>> {
+        let mut products: Vec<Product> = sqlx::query_as(&format!(
+            "select {PRODUCT_COLUMNS} from products where id = any($1) order by id"
+        ))
+        .bind(ids)
+        .fetch_all(pool)
+        .await?;
+        ImageService::attach_images(pool, &mut products).await?;
+
+        Ok(products)
     }
 
     pub async fn get_all_products(pool: &PgPool) -> Result<Vec<Product>> {
-        Ok(sqlx::query_as!(Product, "select * from products")
+        Ok(sqlx::query_as(&format!(
+            "select {PRODUCT_COLUMNS} from products where deleted_at is null"
+        ))
             .fetch_all(pool)
             .await?)
     }
+
+    pub async fn get_products(
+        pool: &PgPool,
+        list_params: &ListParams,
+        filter: &ProductFilter,
+    ) -> Result<Page<Product>> {
+        let mut page = fetch_page(
+            pool,
+            "products",
+            PRODUCT_COLUMNS,
+            &[
+                ("id", "id", "integer"),
+                ("name", "name", "text"),
+                ("price", "(price).cents", "bigint"),
+                ("available", "available", "boolean"),
+                ("stock", STOCK_COLUMN, "integer"),
+            ],
+            list_params,
+            |query_builder| Self::push_filters(query_builder, filter),
+        )
+        .await?;
+        ImageService::attach_images(pool, &mut page.items).await?;
+
+        Ok(page)
+    }
+
+    /// Finds products whose name, tags or description contain words starting
+    /// with the words of `query`, best matches first. Names also match with
+    /// small typos.
+    pub async fn search_products(
+        pool: &PgPool,
+        query: &str,
+        list_params: &ListParams,
+        filter: &ProductFilter,
+    ) -> Result<Page<Product>> {
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() {
+            return Err(SearchError::EmptyQuery.into());
+        }
+        // only alphanumeric words get here, so the query cannot break the syntax
+        let ts_query = words
+            .iter()
+            .map(|word| format!("{}:*", word))
+            .collect::<Vec<_>>()
+            .join(" & ");
+        let plain_query = words.join(" ");
+
+        let push_matches = |query_builder: &mut QueryBuilder<Postgres>| {
+            query_builder
+                .push(" and (search_vector @@ to_tsquery('simple', ")
+                .push_bind(ts_query.clone())
+                .push(") or ")
+                .push_bind(plain_query.clone())
+                .push(" <% name)");
+            Self::push_filters(query_builder, filter);
+        };
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("select set_config('pg_trgm.word_similarity_threshold', $1, true)")
+            .bind(TYPO_SIMILARITY_THRESHOLD)
+            .execute(&mut tx)
+            .await?;
+
+        let mut count_query = QueryBuilder::new("select count(*) from products where true");
+        push_matches(&mut count_query);
+        let (total,): (i64,) = count_query.build_query_as().fetch_one(&mut tx).await?;
+
+        // ranks are numeric, reals would lose digits on their way through cursors
+        let list_params = list_params.sorted_by("rank", SortOrder::Desc);
+        let sort_columns = [("rank", "rank", "numeric")];
+        let mut search_query = QueryBuilder::new(format!(
+            "select *, {} as sort_key from (select {PRODUCT_COLUMNS}, \
+            (ts_rank(search_vector, to_tsquery('simple', ",
+            list_params.sort_key(&sort_columns)?
+        ));
+        search_query
+            .push_bind(ts_query.clone())
+            .push(")) + word_similarity(")
+            .push_bind(plain_query.clone())
+            .push(", name))::numeric as rank from products where true");
+        push_matches(&mut search_query);
+        search_query.push(") products where true");
+        list_params.push_after(&mut search_query, &sort_columns)?;
+        search_query.push(list_params.order_by(&sort_columns)?);
+        info!("Executing search query: {}", search_query.sql());
+        let rows = fetch_rows(search_query, &mut tx, &list_params).await?;
+        tx.commit().await?;
+        let mut page = page_of(rows, total, &list_params)?;
+        ImageService::attach_images(pool, &mut page.items).await?;
+
+        Ok(page)
+    }
+
+    /// Creates products for rows without an id or with an id that is not
+    /// taken yet and updates the others. Invalid rows are reported and left
+    /// out, and nothing is kept on a dry run.
+    pub async fn import_products(
+        pool: &PgPool,
+        format: BulkFormat,
+        body: &[u8],
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let records = parse_records::<ProductRecord>(format, body);
+        let total = records.len();
+        let tax_categories: HashSet<String> =
+            sqlx::query_scalar!("select code from tax_categories")
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .collect();
+        let category_ids: HashSet<i32> = sqlx::query_scalar!("select id from categories")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut seen_ids = HashSet::new();
+        let (records, errors) = validate_records(records, |record| match record {
+            record if record.name.trim().is_empty() => Some("Name must not be empty".to_string()),
+            record if record.price_cents < 0 => Some("Price must not be negative".to_string()),
+            record if record.stock.is_some_and(|stock| stock < 0) => {
+                Some("Stock must not be negative".to_string())
+            }
+            record if !tax_categories.contains(&record.tax_category) => {
+                Some(format!("Unknown tax category {}", record.tax_category))
+            }
+            ProductRecord {
+                category_id: Some(category_id),
+                ..
+            } if !category_ids.contains(category_id) => {
+                Some(format!("Unknown category {}", category_id))
+            }
+            ProductRecord { id: Some(id), .. } if *id <= 0 => {
+                Some(format!("Invalid id {}", id))
+            }
+            ProductRecord { id: Some(id), .. } if !seen_ids.insert(*id) => {
+                Some(format!("Product {} appears more than once", id))
+            }
+            _ => None,
+        });
+        let products: Vec<Product> = records.into_iter().map(Product::from).collect();
+
+        let mut tx = pool.begin().await?;
+        let (created, updated) = Self::upsert_products(&mut tx, &products).await?;
+        match dry_run {
+            true => tx.rollback().await?,
+            false => tx.commit().await?,
+        }
+        info!(
+            "Imported {} new and {} updated products, {} rows failed",
+            created,
+            updated,
+            errors.len()
+        );
+
+        Ok(ImportReport {
+            dry_run,
+            total,
+            created,
+            updated,
+            errors,
+        })
+    }
+
+    /// Streams every product that is not soft deleted, ordered by id.
+    pub fn export_products(pool: PgPool, format: BulkFormat) -> impl Stream<Item = Result<Bytes>> {
+        export_stream(format, move |last_id, limit| {
+            let pool = pool.clone();
+            async move {
+                let products: Vec<Product> = sqlx::query_as(&format!(
+                    "select {PRODUCT_COLUMNS} from products \
+                    where deleted_at is null and id > $1 order by id limit $2"
+                ))
+                .bind(last_id)
+                .bind(limit)
+                .fetch_all(&pool)
+                .await?;
+                Ok(products
+                    .into_iter()
+                    .map(|product| (product.id, ProductRecord::from(product)))
+                    .collect())
+            }
+        })
+    }
+
+    /// Inserts the products with id 0 under new ids and upserts the others
+    /// by id, returning how many were created and how many updated.
+    async fn upsert_products(
+        conn: &mut PgConnection,
+        products: &[Product],
+    ) -> Result<(usize, usize)> {
+        let new_count = products.iter().filter(|product| product.id == 0).count();
+        let mut new_ids = sqlx::query_scalar::<_, i32>(
+            "select nextval(pg_get_serial_sequence('products', 'id'))::int \
+            from generate_series(1, $1)",
+        )
+        .bind(new_count as i32)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter();
+        let product_ids = products
+            .iter()
+            .map(|product| match product.id {
+                0 => new_ids.next(),
+                id => Some(id),
+            })
+            .collect::<Option<Vec<i32>>>()
+            .ok_or_else(|| eyre!("Ran out of product ids"))?;
+
+        let mut created = Vec::new();
+        let mut updated = Vec::new();
+        let rows: Vec<(&i32, &Product)> = product_ids.iter().zip(products).collect();
+        let stock: HashMap<i32, Option<i32>> = rows
+            .iter()
+            .map(|(product_id, product)| (**product_id, product.stock))
+            .collect();
+        let statements =
+            insert_statements("products", &PRODUCT_INSERT_COLUMNS, &rows, Self::push_product_row);
+        for mut query_builder in statements {
+            // rows that were inserted rather than updated have no xmax yet
+            query_builder.push(
+                " on conflict (id) do update set name = excluded.name, price = excluded.price, \
+                available = excluded.available, tax_category = excluded.tax_category, \
+                description = excluded.description, tags = excluded.tags, \
+                category_id = excluded.category_id returning id, xmax = 0",
+            );
+            let upserted: Vec<(i32, bool)> = query_builder
+                .build_query_as()
+                .fetch_all(&mut *conn)
+                .await?;
+            for (product_id, inserted) in upserted {
+                match inserted {
+                    true => created.push((product_id, stock[&product_id])),
+                    false => updated.push((product_id, stock[&product_id])),
+                }
+            }
+        }
+
+        Self::create_default_variants(&mut *conn, &created).await?;
+        let (updated_ids, updated_stock): (Vec<i32>, Vec<Option<i32>>) =
+            updated.iter().copied().unzip();
+        sqlx::query(
+            "update product_variants set stock = updated.stock \
+            from unnest($1::int[], $2::int[]) as updated(product_id, stock) \
+            where product_variants.product_id = updated.product_id and is_default",
+        )
+        .bind(updated_ids)
+        .bind(updated_stock)
+        .execute(&mut *conn)
+        .await?;
+        sync_id_sequence(&mut *conn, "products").await?;
+
+        Ok((created.len(), updated.len()))
+    }
+
+    /// Soft deleted products can no longer be ordered, but stay in the orders
+    /// they are in. Hard deletes take the variants and images along and are
+    /// refused for products that were ever ordered or that discount codes
+    /// are limited to.
+    pub async fn delete_product(pool: &PgPool, id: i32, hard: bool) -> Result<()> {
+        if !hard {
+            return soft_delete(pool, "products", id).await;
+        }
+
+        let mut tx = pool.begin().await?;
+        let references = sqlx::query!(
+            r#"select exists(select 1 from products_in_orders where product_id = $1) as "ordered!",
+            exists(select 1 from discount_codes_products where product_id = $1) as "discounted!""#,
+            id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        let referenced_by = match (references.ordered, references.discounted) {
+            (true, _) => Some("orders"),
+            (_, true) => Some("discount codes"),
+            _ => None,
+        };
+        if let Some(referenced_by) = referenced_by {
+            return Err(DeleteError::InUse {
+                resource: "Product",
+                id,
+                referenced_by,
+            }
+            .into());
+        }
+
+        sqlx::query!("delete from cart_items where product_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!(
+            "delete from variant_attributes where variant_id in \
+            (select id from product_variants where product_id = $1)",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!("delete from product_variants where product_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        let image_keys: Vec<String> = sqlx::query!(
+            "delete from product_images where product_id = $1 returning blob_key, thumbnail_key",
+            id
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .flat_map(|image| [image.blob_key, image.thumbnail_key])
+        .collect();
+        let deleted = sqlx::query!("delete from products where id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!("Product with id {} does not exist", id));
+        }
+        tx.commit().await?;
+
+        ImageService::delete_blobs(image_keys).await;
+        Ok(())
+    }
+
+    pub async fn restore_product(pool: &PgPool, id: i32) -> Result<()> {
+        restore(pool, "products", id).await
+    }
+
+    fn push_filters(query_builder: &mut QueryBuilder<Postgres>, filter: &ProductFilter) {
+        if !filter.include_deleted {
+            query_builder.push(" and deleted_at is null");
+        }
+        if let Some(min_price) = filter.min_price {
+            query_builder.push(" and (price).cents >= ").push_bind(min_price);
+        }
+        if let Some(max_price) = filter.max_price {
+            query_builder.push(" and (price).cents <= ").push_bind(max_price);
+        }
+        if let Some(currency) = filter.currency {
+            query_builder.push(" and (price).currency = ").push_bind(currency);
+        }
+        if let Some(available) = filter.available {
+            query_builder.push(" and available = ").push_bind(available);
+        }
+        if let Some(category_id) = filter.category_id {
+            query_builder
+                .push(
+                    " and category_id in (with recursive subcategories as (\
+                    select id from categories where id = ",
+                )
+                .push_bind(category_id)
+                .push(
+                    " union all select categories.id from categories \
+                    join subcategories on categories.parent_id = subcategories.id) \
+                    select id from subcategories)",
+                );
+        }
+    }
+
+    /// Takes `quantity` pieces of every variant out of stock, or puts them
+    /// back for negative quantities. Variants without tracked stock are
+    /// left alone.
+    pub async fn take_from_stock(
+        conn: &mut PgConnection,
+        quantities: &HashMap<i32, i32>,
+    ) -> Result<()> {
+        for (variant_id, quantity) in quantities.iter().filter(|(_, quantity)| **quantity != 0) {
+            let updated = sqlx::query!(
+                "update product_variants set stock = stock - $2 \
+                where id = $1 and (stock is null or stock >= $2)",
+                variant_id,
+                quantity
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            if updated.rows_affected() == 0 {
+                return Err(StockError::OutOfStock(*variant_id).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Turns quantities per product, meaning their default variants, and
+    /// quantities per variant into quantities per variant.
+    pub async fn resolve_variants(
+        conn: &mut PgConnection,
+        products: &HashMap<i32, i32>,
+        variants: &HashMap<i32, i32>,
+    ) -> Result<HashMap<i32, i32>> {
+        let product_ids: Vec<i32> = products.keys().copied().collect();
+        let default_variants: HashMap<i32, i32> = sqlx::query!(
+            "select product_id, product_variants.id from product_variants \
+            join products on products.id = product_id \
+            where product_id = any($1) and is_default and deleted_at is null",
+            &product_ids
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.product_id, row.id))
+        .collect();
+
+        let mut quantities = variants.clone();
+        for (product_id, quantity) in products.iter() {
+            let variant_id = default_variants
+                .get(product_id)
+                .ok_or(VariantError::UnknownProduct(*product_id))?;
+            *quantities.entry(*variant_id).or_default() += quantity;
+        }
+
+        Ok(quantities)
+    }
+
+    /// Returns the product and the current price of every variant.
+    pub async fn get_variant_prices(
+        conn: &mut PgConnection,
+        variant_ids: &[i32],
+    ) -> Result<HashMap<i32, (i32, Money)>> {
+        let prices: HashMap<i32, (i32, Money)> = sqlx::query!(
+            r#"select product_variants.id, product_id,
+            coalesce(product_variants.price, products.price) as "price!: Money"
+            from product_variants join products on products.id = product_variants.product_id
+            where product_variants.id = any($1) and products.deleted_at is null"#,
+            variant_ids
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, (row.product_id, row.price)))
+        .collect();
+
+        if let Some(missing) = variant_ids.iter().find(|id| !prices.contains_key(id)) {
+            return Err(VariantError::UnknownVariant(*missing).into());
+        }
+
+        Ok(prices)
+    }
+
+    /// Pushes the values of `PRODUCT_INSERT_COLUMNS` for a product and its id.
+    fn push_product_row<'a>(
+        mut builder: Separated<'_, 'a, Postgres, &'static str>,
+        (product_id, product): &'a (&'a i32, &'a Product),
+    ) {
+        builder
+            .push_bind(**product_id)
+            .push_bind(&product.name)
+            .push_bind(product.price)
+            .push_bind(product.available)
+            .push_bind(&product.tax_category)
+            .push_bind(&product.description)
+            .push_bind(&product.tags)
+            .push_bind(product.category_id);
+    }
+
+    async fn create_default_variants(
+        conn: &mut PgConnection,
+        products: &[(i32, Option<i32>)],
+    ) -> Result<()> {
+        if products.is_empty() {
+            return Ok(());
+        }
+
+        bulk_insert(
+            conn,
+            "product_variants",
+            &["product_id", "sku", "stock", "is_default"],
+            products,
+            |mut builder, (product_id, stock)| {
+                builder
+                    .push_bind(product_id)
+                    .push_bind(format!("P-{}", product_id))
+                    .push_bind(stock)
+                    .push_bind(true);
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{
+    order_status, Money, PaymentStatus, Refund, RefundError, RefundLine, RefundRequest,
+};
+use crate::setup::PAYMENT_PROVIDER;
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{PgConnection, PgPool};
+use tracing::info;
+
+use super::bulk_insert;
+use super::product_service::ProductService;
+
+pub struct RefundService;
+
+#[async_trait]
+impl Clearable for RefundService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from refund_lines").execute(&pool).await?;
+        sqlx::query!("delete from refunds").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl RefundService {
+    /// Cancels an order that was not fulfilled yet. Paid orders are refunded
+    /// in full, all ordered products go back to stock either way.
+    pub async fn cancel_order(pool: &PgPool, order_id: i32) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        let status = Self::lock_order(&mut tx, order_id).await?;
+        if !order_status::CANCELLABLE.contains(&status.as_str()) {
+            return Err(RefundError::NotCancellable(status).into());
+        }
+
+        if status == order_status::PAID {
+            let full_refund = RefundRequest {
+                products: HashMap::new(),
+                variants: HashMap::new(),
+                restock: true,
+                reason: Some("Order cancelled".to_string()),
+            };
+            Self::refund_in_transaction(&mut tx, order_id, full_refund).await?;
+        } else {
+            let returned_quantities = sqlx::query!(
+                "select variant_id, quantity - refunded_quantity as \"returned!\" \
+                from products_in_orders where order_id = $1",
+                order_id
+            )
+            .fetch_all(&mut tx)
+            .await?
+            .into_iter()
+            .map(|line| (line.variant_id, -line.returned))
+            .collect();
+            ProductService::take_from_stock(&mut tx, &returned_quantities).await?;
+        }
+
+        sqlx::query!(
+            "update orders set status = $1 where id = $2",
+            order_status::CANCELLED,
+            order_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        info!("Order {} cancelled", order_id);
+        Ok(())
+    }
+
+    pub async fn refund_order(
+        pool: &PgPool,
+        order_id: i32,
+        request: RefundRequest,
+    ) -> Result<Refund> {
+        let mut tx = pool.begin().await?;
+        Self::lock_order(&mut tx, order_id).await?;
+        let refund = Self::refund_in_transaction(&mut tx, order_id, request).await?;
+        tx.commit().await?;
+
+        Ok(refund)
+    }
+
+    pub async fn get_order_refunds(pool: &PgPool, order_id: i32) -> Result<Vec<Refund>> {
+        let refunds = sqlx::query!(
+            r#"select id, order_id, payment_intent_id, amount as "amount: Money", reason,
+            restocked, created_at
+            from refunds where order_id = $1 order by id"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let refund_ids: Vec<i32> = refunds.iter().map(|refund| refund.id).collect();
+        let mut lines_by_refund: HashMap<i32, Vec<RefundLine>> = HashMap::new();
+        let lines = sqlx::query!(
+            r#"select refund_id, product_id, variant_id, quantity, amount as "amount: Money"
+            from refund_lines where refund_id = any($1) order by product_id, variant_id"#,
+            &refund_ids
+        )
+        .fetch_all(pool)
+        .await?;
+        for line in lines {
+            lines_by_refund
+                .entry(line.refund_id)
+                .or_default()
+                .push(RefundLine {
+                    product_id: line.product_id,
+                    variant_id: line.variant_id,
+                    quantity: line.quantity,
+                    amount: line.amount,
+                });
+        }
+
+        Ok(refunds
+            .into_iter()
+            .map(|refund| Refund {
+                lines: lines_by_refund.remove(&refund.id).unwrap_or_default(),
+                id: refund.id,
+                order_id: refund.order_id,
+                payment_intent_id: refund.payment_intent_id,
+                amount: refund.amount,
+                reason: refund.reason,
+                restocked: refund.restocked,
+                created_at: refund.created_at,
+            })
+            .collect())
+    }
+
+    /// Locks the order against concurrent refunds and returns its status.
+    async fn lock_order(conn: &mut PgConnection, order_id: i32) -> Result<String> {
+        sqlx::query_scalar!("select status from orders where id = $1 for update", order_id)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or_else(|| eyre!("Order with id {} does not exist", order_id))
+    }
+
+    /// Refunds the requested quantities out of the settled payment of the
+    /// order. Every line gets the share of the paid amount matching its
+    /// share of the order value, so discounts and taxes are refunded
+    /// proportionally. The refund that empties the order returns whatever
+    /// was not refunded yet, so rounding never keeps any cents.
+    async fn refund_in_transaction(
+        conn: &mut PgConnection,
+        order_id: i32,
+        request: RefundRequest,
+    ) -> Result<Refund> {
+        let order_lines = sqlx::query!(
+            r#"select product_id, variant_id, quantity, refunded_quantity,
+            unit_price as "unit_price: Money"
+            from products_in_orders where order_id = $1 order by product_id, variant_id"#,
+            order_id
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut quantities: Vec<(i32, i32)> = Vec::new();
+        if request.products.is_empty() && request.variants.is_empty() {
+            for line in order_lines.iter() {
+                if line.quantity > line.refunded_quantity {
+                    quantities.push((line.variant_id, line.quantity - line.refunded_quantity));
+                }
+            }
+        } else {
+            let requested =
+                ProductService::resolve_variants(&mut *conn, &request.products, &request.variants)
+                    .await?;
+            for (variant_id, quantity) in requested.iter() {
+                let line = order_lines
+                    .iter()
+                    .find(|line| line.variant_id == *variant_id)
+                    .ok_or(RefundError::InvalidQuantity(*variant_id))?;
+                if *quantity <= 0 || *quantity > line.quantity - line.refunded_quantity {
+                    return Err(RefundError::InvalidQuantity(*variant_id).into());
+                }
+                quantities.push((*variant_id, *quantity));
+            }
+            quantities.sort();
+        }
+        if quantities.is_empty() {
+            return Err(RefundError::NothingToRefund.into());
+        }
+
+        let payment_intent = sqlx::query!(
+            r#"select id, provider_reference, amount as "amount: Money" from payment_intents
+            where order_id = $1 and status = $2 order by id desc limit 1 for update"#,
+            order_id,
+            PaymentStatus::Succeeded as PaymentStatus
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(RefundError::NotPaid)?;
+        let paid = payment_intent.amount;
+
+        let already_refunded = sqlx::query_scalar!(
+            r#"select amount as "amount: Money" from refunds where payment_intent_id = $1"#,
+            payment_intent.id
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .try_fold(Money::zero(paid.currency), |sum, amount| sum.checked_add(amount))?;
+
+        let mut subtotal = Money::zero(paid.currency);
+        for line in order_lines.iter() {
+            subtotal = subtotal.checked_add(line.unit_price.checked_mul(line.quantity)?)?;
+        }
+
+        let mut lines = Vec::with_capacity(quantities.len());
+        let mut amount = Money::zero(paid.currency);
+        for (variant_id, quantity) in quantities.iter() {
+            let order_line = order_lines
+                .iter()
+                .find(|line| line.variant_id == *variant_id)
+                .ok_or(RefundError::InvalidQuantity(*variant_id))?;
+            let value = order_line.unit_price.checked_mul(*quantity)?;
+            let line_amount = if subtotal.cents == 0 {
+                Money::zero(paid.currency)
+            } else {
+                let cents = value.cents as i128 * paid.cents as i128 / subtotal.cents as i128;
+                Money::new(cents as i64, paid.currency)
+            };
+            amount = amount.checked_add(line_amount)?;
+            lines.push(RefundLine {
+                product_id: order_line.product_id,
+                variant_id: *variant_id,
+                quantity: *quantity,
+                amount: line_amount,
+            });
+        }
+
+        let refunds_everything = order_lines.iter().all(|line| {
+            let refunded_now = quantities
+                .iter()
+                .find(|(variant_id, _)| *variant_id == line.variant_id)
+                .map_or(0, |(_, quantity)| *quantity);
+            line.refunded_quantity + refunded_now == line.quantity
+        });
+        if refunds_everything {
+            let remainder = paid.checked_sub(already_refunded)?.checked_sub(amount)?;
+            if let Some(last_line) = lines.last_mut() {
+                last_line.amount = last_line.amount.checked_add(remainder)?;
+            }
+            amount = amount.checked_add(remainder)?;
+        }
+
+        let reason = request.reason.unwrap_or_default();
+        let (refund_id, created_at): (i32, NaiveDateTime) = sqlx::query_as(
+            "insert into refunds (order_id, payment_intent_id, amount, reason, restocked, created_at) \
+            values ($1, $2, $3, $4, $5, $6) returning id, created_at",
+        )
+        .bind(order_id)
+        .bind(payment_intent.id)
+        .bind(amount)
+        .bind(&reason)
+        .bind(request.restock)
+        .bind(Local::now().naive_local())
+        .fetch_one(&mut *conn)
+        .await?;
+
+        bulk_insert(
+            &mut *conn,
+            "refund_lines",
+            &["refund_id", "product_id", "variant_id", "quantity", "amount"],
+            &lines,
+            |mut builder, line| {
+                builder
+                    .push_bind(refund_id)
+                    .push_bind(line.product_id)
+                    .push_bind(line.variant_id)
+                    .push_bind(line.quantity)
+                    .push_bind(line.amount);
+            },
+        )
+        .await?;
+
+        for line in lines.iter() {
+            sqlx::query!(
+                "update products_in_orders set refunded_quantity = refunded_quantity + $1 \
+                where order_id = $2 and variant_id = $3",
+                line.quantity,
+                order_id,
+                line.variant_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        if request.restock {
+            let returned_quantities = lines
+                .iter()
+                .map(|line| (line.variant_id, -line.quantity))
+                .collect();
+            ProductService::take_from_stock(&mut *conn, &returned_quantities).await?;
+        }
+
+        let new_order_status = if refunds_everything {
+            sqlx::query!(
+                "update payment_intents set status = $1, updated_at = $2 where id = $3",
+                PaymentStatus::Refunded as PaymentStatus,
+                Local::now().naive_local(),
+                payment_intent.id
+            )
+            .execute(&mut *conn)
+            .await?;
+            order_status::REFUNDED
+        } else {
+            order_status::PARTIALLY_REFUNDED
+        };
+        sqlx::query!(
+            "update orders set status = $1 where id = $2",
+            new_order_status,
+            order_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // the provider goes last, so a failed refund there rolls all of the above back
+        PAYMENT_PROVIDER
+            .refund(&payment_intent.provider_reference, amount)
+            .await?;
+        info!("Refunded {} of order {}", amount, order_id);
+
+        Ok(Refund {
+            id: refund_id,
+            order_id,
+            payment_intent_id: Some(payment_intent.id),
+            amount,
+            reason,
+            restocked: request.restock,
+            created_at,
+            lines,
+        })
+    }
+}
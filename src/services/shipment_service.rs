@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{order_status, NewShipment, Shipment, ShipmentError, ShipmentLine};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use color_eyre::{eyre::eyre, Result};
+use sqlx::PgPool;
+use tracing::info;
+
+use super::bulk_insert;
+use super::product_service::ProductService;
+
+pub struct ShipmentService;
+
+#[async_trait]
+impl Clearable for ShipmentService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from shipment_lines")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from shipments").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl ShipmentService {
+    /// Records a shipment of products of a paid order. Only quantities that
+    /// were neither shipped nor refunded before can be shipped.
+    pub async fn create_shipment(
+        pool: &PgPool,
+        order_id: i32,
+        new_shipment: NewShipment,
+    ) -> Result<Shipment> {
+        let mut tx = pool.begin().await?;
+        let status =
+            sqlx::query_scalar!("select status from orders where id = $1 for update", order_id)
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or_else(|| eyre!("Order with id {} does not exist", order_id))?;
+        if !order_status::SHIPPABLE.contains(&status.as_str()) {
+            return Err(ShipmentError::NotShippable(status).into());
+        }
+
+        // unshipped quantity and product per variant
+        let unshipped: HashMap<i32, (i32, i32)> = sqlx::query!(
+            r#"select product_id, variant_id, quantity - refunded_quantity - coalesce((
+                select sum(shipment_lines.quantity) from shipment_lines
+                join shipments on shipments.id = shipment_lines.shipment_id
+                where shipments.order_id = products_in_orders.order_id
+                and shipment_lines.variant_id = products_in_orders.variant_id
+            ), 0)::int as "unshipped!"
+            from products_in_orders where order_id = $1"#,
+            order_id
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|line| (line.variant_id, (line.unshipped, line.product_id)))
+        .collect();
+
+        let mut lines: Vec<ShipmentLine> =
+            if new_shipment.products.is_empty() && new_shipment.variants.is_empty() {
+                unshipped
+                    .iter()
+                    .filter(|(_, (quantity, _))| *quantity > 0)
+                    .map(|(variant_id, (quantity, product_id))| ShipmentLine {
+                        product_id: *product_id,
+                        variant_id: *variant_id,
+                        quantity: *quantity,
+                    })
+                    .collect()
+            } else {
+                let requested = ProductService::resolve_variants(
+                    &mut tx,
+                    &new_shipment.products,
+                    &new_shipment.variants,
+                )
+                .await?;
+                let mut lines = Vec::with_capacity(requested.len());
+                for (variant_id, quantity) in requested.iter() {
+                    let (left, product_id) = unshipped
+                        .get(variant_id)
+                        .copied()
+                        .ok_or(ShipmentError::InvalidQuantity(*variant_id))?;
+                    if *quantity <= 0 || *quantity > left {
+                        return Err(ShipmentError::InvalidQuantity(*variant_id).into());
+                    }
+                    lines.push(ShipmentLine {
+                        product_id,
+                        variant_id: *variant_id,
+                        quantity: *quantity,
+                    });
+                }
+                lines
+            };
+        if lines.is_empty() {
+            return Err(ShipmentError::NothingToShip.into());
+        }
+        lines.sort_by_key(|line| (line.product_id, line.variant_id));
+
+        let (shipment_id, shipped_at): (i32, NaiveDateTime) = sqlx::query_as(
+            "insert into shipments (order_id, carrier, tracking_number, shipped_at) \
+            values ($1, $2, $3, $4) returning id, shipped_at",
+        )
+        .bind(order_id)
+        .bind(&new_shipment.carrier)
+        .bind(&new_shipment.tracking_number)
+        .bind(Local::now().naive_local())
+        .fetch_one(&mut tx)
+        .await?;
+
+        bulk_insert(
+            &mut tx,
+            "shipment_lines",
+            &["shipment_id", "product_id", "variant_id", "quantity"],
+            &lines,
+            |mut builder, line| {
+                builder
+                    .push_bind(shipment_id)
+                    .push_bind(line.product_id)
+                    .push_bind(line.variant_id)
+                    .push_bind(line.quantity);
+            },
+        )
+        .await?;
+
+        let ships_everything = unshipped.iter().all(|(variant_id, (left, _))| {
+            let shipped_now = lines
+                .iter()
+                .find(|line| line.variant_id == *variant_id)
+                .map_or(0, |line| line.quantity);
+            shipped_now == *left
+        });
+        let new_order_status = if ships_everything {
+            order_status::SHIPPED
+        } else {
+            order_status::PARTIALLY_SHIPPED
+        };
+        sqlx::query!(
+            "update orders set status = $1 where id = $2",
+            new_order_status,
+            order_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        info!(
+            "Order {} shipped with {} as {}",
+            order_id, new_shipment.carrier, new_shipment.tracking_number
+        );
+        Ok(Shipment {
+            id: shipment_id,
+            order_id,
+            carrier: new_shipment.carrier,
+            tracking_number: new_shipment.tracking_number,
+            shipped_at,
+            delivered_at: None,
+            lines,
+        })
+    }
+
+    pub async fn get_shipment(pool: &PgPool, id: i32) -> Result<Shipment> {
+        let shipment = sqlx::query!(
+            "select id, order_id, carrier, tracking_number, shipped_at, delivered_at \
+            from shipments where id = $1",
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        let lines = sqlx::query_as!(
+            ShipmentLine,
+            "select product_id, variant_id, quantity from shipment_lines \
+            where shipment_id = $1 order by product_id, variant_id",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Shipment {
+            id: shipment.id,
+            order_id: shipment.order_id,
+            carrier: shipment.carrier,
+            tracking_number: shipment.tracking_number,
+            shipped_at: shipment.shipped_at,
+            delivered_at: shipment.delivered_at,
+            lines,
+        })
+    }
+
+    pub async fn get_order_shipments(pool: &PgPool, order_id: i32) -> Result<Vec<Shipment>> {
+        let shipments = sqlx::query!(
+            "select id, order_id, carrier, tracking_number, shipped_at, delivered_at \
+            from shipments where order_id = $1 order by id",
+            order_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let shipment_ids: Vec<i32> = shipments.iter().map(|shipment| shipment.id).collect();
+        let mut lines_by_shipment: HashMap<i32, Vec<ShipmentLine>> = HashMap::new();
+        let lines = sqlx::query!(
+            "select shipment_id, product_id, variant_id, quantity from shipment_lines \
+            where shipment_id = any($1) order by product_id, variant_id",
+            &shipment_ids
+        )
+        .fetch_all(pool)
+        .await?;
+        for line in lines {
+            lines_by_shipment
+                .entry(line.shipment_id)
+                .or_default()
+                .push(ShipmentLine {
+                    product_id: line.product_id,
+                    variant_id: line.variant_id,
+                    quantity: line.quantity,
+                });
+        }
+
+        Ok(shipments
+            .into_iter()
+            .map(|shipment| Shipment {
+                lines: lines_by_shipment.remove(&shipment.id).unwrap_or_default(),
+                id: shipment.id,
+                order_id: shipment.order_id,
+                carrier: shipment.carrier,
+                tracking_number: shipment.tracking_number,
+                shipped_at: shipment.shipped_at,
+                delivered_at: shipment.delivered_at,
+            })
+            .collect())
+    }
+
+    pub async fn mark_delivered(pool: &PgPool, id: i32) -> Result<()> {
+        let updated = sqlx::query!(
+            "update shipments set delivered_at = $1 where id = $2 and delivered_at is null",
+            Local::now().naive_local(),
+            id
+        )
+        .execute(pool)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(eyre!(
+                "Shipment with id {} does not exist or was already delivered",
+                id
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::bulk_insert;
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+use crate::models::{Money, OrderLine, OrderTaxLine, TaxCategory, TaxError, TaxRate};
+use async_trait::async_trait;
+use color_eyre::{eyre::eyre, Result};
+use sqlx::{PgConnection, PgPool};
+use tracing::warn;
+
+pub struct TaxService;
+
+#[async_trait]
+impl MockFillable for TaxService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let new_tax_rates = [
+            ("PL", "standard", 2300),
+            ("PL", "reduced", 800),
+            ("PL", "exempt", 0),
+            ("DE", "standard", 1900),
+            ("DE", "reduced", 700),
+            ("DE", "exempt", 0),
+        ];
+
+        let pool = get_pool().await?;
+        for (region, tax_category, rate_basis_points) in new_tax_rates {
+            Self::create_tax_rate(
+                &pool,
+                TaxRate {
+                    id: 0,
+                    region: region.to_string(),
+                    tax_category: tax_category.to_string(),
+                    rate_basis_points,
+                },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for TaxService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from tax_rates").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl TaxService {
+    pub async fn create_tax_category(pool: &PgPool, new_tax_category: TaxCategory) -> Result<()> {
+        sqlx::query!(
+            "insert into tax_categories (code, name) values ($1, $2)",
+            new_tax_category.code,
+            new_tax_category.name
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_all_tax_categories(pool: &PgPool) -> Result<Vec<TaxCategory>> {
+        Ok(
+            sqlx::query_as!(TaxCategory, "select code, name from tax_categories")
+                .fetch_all(pool)
+                .await?,
+        )
+    }
+
+    pub async fn create_tax_rate(pool: &PgPool, new_tax_rate: TaxRate) -> Result<i32> {
+        let new_tax_rate_row: (i32,) = sqlx::query_as(
+            "insert into tax_rates (region, tax_category, rate_basis_points) \
+            values ($1, $2, $3) returning id",
+        )
+        .bind(new_tax_rate.region)
+        .bind(new_tax_rate.tax_category)
+        .bind(new_tax_rate.rate_basis_points)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(new_tax_rate_row.0)
+    }
+
+    pub async fn get_tax_rate(pool: &PgPool, id: i32) -> Result<TaxRate> {
+        Ok(
+            sqlx::query_as!(TaxRate, "select * from tax_rates where id = $1", id)
+                .fetch_one(pool)
+                .await?,
+        )
+    }
+
+    pub async fn get_all_tax_rates(pool: &PgPool) -> Result<Vec<TaxRate>> {
+        Ok(sqlx::query_as!(TaxRate, "select * from tax_rates")
+            .fetch_all(pool)
+            .await?)
+    }
+
+    pub async fn update_tax_rate(pool: &PgPool, updated_tax_rate: TaxRate) -> Result<()> {
+        let updated = sqlx::query!(
+            "update tax_rates set region = $1, tax_category = $2, rate_basis_points = $3 \
+            where id = $4",
+            updated_tax_rate.region,
+            updated_tax_rate.tax_category,
+            updated_tax_rate.rate_basis_points,
+            updated_tax_rate.id
+        )
+        .execute(pool)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(TaxError::UnknownRate(updated_tax_rate.id).into());
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_tax_rate(pool: &PgPool, id: i32) -> Result<()> {
+        let deleted = sqlx::query!("delete from tax_rates where id = $1", id)
+            .execute(pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(TaxError::UnknownRate(id).into());
+        }
+
+        Ok(())
+    }
+
+    /// Computes one tax line per tax category present in `lines`, using the
+    /// rates of the region the order ships to. That is the country of its
+    /// shipping address, else of the customer's default shipping address,
+    /// and only the region of the customer when there is neither. The order
+    /// discount is spread over the categories proportionally to their value
+    /// before tax is applied.
+    ///
+    /// Categories without a rate in the region are not taxed rather than
+    /// failing the order, their tax lines show a rate of 0 so admins can tell
+    /// and add the rate.
+    pub async fn compute_taxes(
+        conn: &mut PgConnection,
+        order_id: i32,
+        customer_id: i32,
+        lines: &[OrderLine],
+        discount: Option<Money>,
+    ) -> Result<Vec<OrderTaxLine>> {
+        if lines.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let region = sqlx::query_scalar!(
+            r#"select coalesce(
+                (select country from order_shipping_addresses where order_id = $1),
+                (select country from customer_addresses
+                where customer_id = $2 and kind = 'shipping' and is_default),
+                (select region from customers where id = $2)
+            ) as "region!""#,
+            order_id,
+            customer_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let product_ids: Vec<i32> = lines.iter().map(|line| line.product_id).collect();
+        let tax_categories: HashMap<i32, String> = sqlx::query!(
+            "select id, tax_category from products where id = any($1)",
+            &product_ids
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, row.tax_category))
+        .collect();
+
+        let rates: HashMap<String, i32> = sqlx::query!(
+            "select tax_category, rate_basis_points from tax_rates where region = $1",
+            region
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.tax_category, row.rate_basis_points))
+        .collect();
+
+        let currency = lines[0].line_total.currency;
+        let mut value_by_category: BTreeMap<&str, Money> = BTreeMap::new();
+        for line in lines {
+            let tax_category = tax_categories
+                .get(&line.product_id)
+                .ok_or_else(|| eyre!("Product with id {} does not exist", line.product_id))?;
+            let value = value_by_category
+                .entry(tax_category)
+                .or_insert(Money::zero(currency));
+            *value = value.checked_add(line.line_total)?;
+        }
+
+        let subtotal = lines
+            .iter()
+            .try_fold(Money::zero(currency), |sum, line| sum.checked_add(line.line_total))?;
+        let discount = discount.unwrap_or(Money::zero(currency));
+        let mut undistributed_discount = discount;
+
+        let mut taxes = Vec::with_capacity(value_by_category.len());
+        let category_count = value_by_category.len();
+        for (i, (tax_category, value)) in value_by_category.into_iter().enumerate() {
+            let rate_basis_points = match rates.get(tax_category) {
+                Some(rate_basis_points) => *rate_basis_points,
+                None => {
+                    warn!("No tax rate for category {} in region {}", tax_category, region);
+                    0
+                }
+            };
+
+            // the last category takes what is left, so rounding never loses cents
+            let discount_share = if i + 1 == category_count || subtotal.cents == 0 {
+                undistributed_discount
+            } else {
+                let cents = value.cents as i128 * discount.cents as i128 / subtotal.cents as i128;
+                Money::new(cents as i64, currency)
+            };
+            undistributed_discount = undistributed_discount.checked_sub(discount_share)?;
+
+            let taxable_amount = value.checked_sub(discount_share)?;
+            taxes.push(OrderTaxLine {
+                tax_category: tax_category.to_string(),
+                region: region.clone(),
+                rate_basis_points,
+                taxable_amount,
+                tax: taxable_amount.apply_rate(rate_basis_points)?,
+            });
+        }
+
+        Ok(taxes)
+    }
+
+    pub async fn get_order_taxes(pool: &PgPool, order_id: i32) -> Result<Vec<OrderTaxLine>> {
+        Ok(sqlx::query_as!(
+            OrderTaxLine,
+            r#"select tax_category, region, rate_basis_points,
+            taxable_amount as "taxable_amount: Money", tax as "tax: Money"
+            from order_tax_lines where order_id = $1 order by tax_category"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Replaces the stored tax lines of the order with `taxes`.
+    pub async fn store_order_taxes(
+        conn: &mut PgConnection,
+        order_id: i32,
+        taxes: &[OrderTaxLine],
+    ) -> Result<()> {
+        sqlx::query!("delete from order_tax_lines where order_id = $1", order_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if taxes.is_empty() {
+            return Ok(());
+        }
+
+        bulk_insert(
+            conn,
+            "order_tax_lines",
+            &[
+                "order_id",
+                "tax_category",
+                "region",
+                "rate_basis_points",
+                "taxable_amount",
+                "tax",
+            ],
+            taxes,
+            |mut builder, tax_line| {
+                builder
+                    .push_bind(order_id)
+                    .push_bind(&tax_line.tax_category)
+                    .push_bind(&tax_line.region)
+                    .push_bind(tax_line.rate_basis_points)
+                    .push_bind(tax_line.taxable_amount)
+                    .push_bind(tax_line.tax);
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+}
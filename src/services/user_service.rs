@@ -58,7 +58,7 @@ impl UserService {
     pub async fn get_user(pool: &PgPool, name: &str) -> Result<User> {
         let user = sqlx::query_as!(
             User,
-            r#"SELECT id, name, passwd_hash, role as "role: Roles" FROM users WHERE name = $1"#,
+            r#"SELECT id, name, passwd_hash, role as "role: Roles", customer_id FROM users WHERE name = $1"#,
             name
         )
         .fetch_one(pool)
@@ -75,16 +75,39 @@ impl UserService {
             .map_err(|e| eyre!(e))?;
 
         info!("Creating user: {}", user.name);
+        let mut tx = pool.begin().await?;
+        // every customer account gets its own customer record to order as
+        let customer_id = match role {
+            Roles::Customer => Some(
+                sqlx::query_scalar!(
+                    "INSERT INTO customers (name, address) VALUES ($1, '') RETURNING id",
+                    user.name
+                )
+                .fetch_one(&mut tx)
+                .await?,
+            ),
+            Roles::Admin => None,
+        };
+
         let user = sqlx::query_as!(
             User,
-            r#"INSERT INTO users (name, passwd_hash, role) VALUES ($1, $2, $3) RETURNING id, name, passwd_hash, role as "role: Roles""#,
+            r#"INSERT INTO users (name, passwd_hash, role, customer_id) VALUES ($1, $2, $3, $4) RETURNING id, name, passwd_hash, role as "role: Roles", customer_id"#,
             user.name,
             hash.to_string(),
-            role as Roles
+            role as Roles,
+            customer_id
         )
-        .fetch_one(pool)
+        .fetch_one(&mut tx)
         .await?;
+        tx.commit().await?;
 
         Ok(user)
     }
+
+    pub async fn get_customer_id(pool: &PgPool, name: &str) -> Result<i32> {
+        sqlx::query_scalar!("SELECT customer_id FROM users WHERE name = $1", name)
+            .fetch_one(pool)
+            .await?
+            .ok_or_else(|| eyre!("User {} is not linked to a customer", name))
+    }
 }
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use super::bulk_insert;
+use crate::db_actions::{get_pool, Clearable, MockFillable};
+use crate::models::{Attribute, AttributeKind, Currency, Money, Variant, VariantError};
+use async_trait::async_trait;
+use color_eyre::{eyre::eyre, Result};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+
+pub struct VariantService;
+
+#[async_trait]
+impl MockFillable for VariantService {
+    async fn fill_with_mocked_data(&self) -> Result<()> {
+        let new_attributes = [
+            ("size", "Size", AttributeKind::Text),
+            ("colour", "Colour", AttributeKind::Text),
+            ("organic", "Organic", AttributeKind::Boolean),
+        ];
+
+        let pool = get_pool().await?;
+        for (code, name, kind) in new_attributes {
+            Self::create_attribute(
+                &pool,
+                Attribute {
+                    id: 0,
+                    code: code.to_string(),
+                    name: name.to_string(),
+                    kind,
+                },
+            )
+            .await?;
+        }
+
+        let new_variants = [
+            ("TSHIRT-M-BLACK", None, "M", "black"),
+            ("TSHIRT-XL-WHITE", Some(Money::new(2299, Currency::Pln)), "XL", "white"),
+        ];
+        for (sku, price, size, colour) in new_variants {
+            Self::create_variant(
+                &pool,
+                Variant {
+                    id: 0,
+                    product_id: 1,
+                    sku: sku.to_string(),
+                    price,
+                    stock: Some(100),
+                    available: true,
+                    is_default: false,
+                    attributes: HashMap::from([
+                        ("size".to_string(), Value::from(size)),
+                        ("colour".to_string(), Value::from(colour)),
+                        ("organic".to_string(), Value::from(true)),
+                    ]),
+                },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Clearable for VariantService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from variant_attributes")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from product_variants")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from attributes").execute(&pool).await?;
+        Ok(())
+    }
+}
+
+impl VariantService {
+    pub async fn create_attribute(pool: &PgPool, new_attribute: Attribute) -> Result<i32> {
+        let new_attribute_row: (i32,) = sqlx::query_as(
+            "insert into attributes (code, name, kind) values ($1, $2, $3) returning id",
+        )
+        .bind(new_attribute.code)
+        .bind(new_attribute.name)
+        .bind(new_attribute.kind)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(new_attribute_row.0)
+    }
+
+    pub async fn get_all_attributes(pool: &PgPool) -> Result<Vec<Attribute>> {
+        Ok(sqlx::query_as!(
+            Attribute,
+            r#"select id, code, name, kind as "kind: AttributeKind" from attributes order by id"#
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Adds a variant next to the default one every product has.
+    pub async fn create_variant(pool: &PgPool, new_variant: Variant) -> Result<i32> {
+        let mut tx = pool.begin().await?;
+        let new_variant_row: (i32,) = sqlx::query_as(
+            "insert into product_variants (product_id, sku, price, stock, available) \
+            values ($1, $2, $3, $4, $5) returning id",
+        )
+        .bind(new_variant.product_id)
+        .bind(new_variant.sku)
+        .bind(new_variant.price)
+        .bind(new_variant.stock)
+        .bind(new_variant.available)
+        .fetch_one(&mut tx)
+        .await?;
+        let variant_id = new_variant_row.0;
+
+        Self::set_attributes(&mut tx, variant_id, &new_variant.attributes).await?;
+        tx.commit().await?;
+
+        Ok(variant_id)
+    }
+
+    pub async fn get_variant(pool: &PgPool, id: i32) -> Result<Variant> {
+        let variant = sqlx::query!(
+            r#"select id, product_id, sku, price as "price: Money", stock, available, is_default
+            from product_variants where id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(VariantError::UnknownVariant(id))?;
+        let mut attributes = Self::get_attributes(pool, &[id]).await?;
+
+        Ok(Variant {
+            id: variant.id,
+            product_id: variant.product_id,
+            sku: variant.sku,
+            price: variant.price,
+            stock: variant.stock,
+            available: variant.available,
+            is_default: variant.is_default,
+            attributes: attributes.remove(&id).unwrap_or_default(),
+        })
+    }
+
+    pub async fn get_product_variants(pool: &PgPool, product_id: i32) -> Result<Vec<Variant>> {
+        let variants = sqlx::query!(
+            r#"select id, product_id, sku, price as "price: Money", stock, available, is_default
+            from product_variants where product_id = $1 order by is_default desc, id"#,
+            product_id
+        )
+        .fetch_all(pool)
+        .await?;
+        let variant_ids: Vec<i32> = variants.iter().map(|variant| variant.id).collect();
+        let mut attributes = Self::get_attributes(pool, &variant_ids).await?;
+
+        Ok(variants
+            .into_iter()
+            .map(|variant| Variant {
+                attributes: attributes.remove(&variant.id).unwrap_or_default(),
+                id: variant.id,
+                product_id: variant.product_id,
+                sku: variant.sku,
+                price: variant.price,
+                stock: variant.stock,
+                available: variant.available,
+                is_default: variant.is_default,
+            })
+            .collect())
+    }
+
+    /// Variants cannot move to another product, their attributes are
+    /// replaced as a whole.
+    pub async fn update_variant(pool: &PgPool, updated_variant: Variant) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        let updated = sqlx::query!(
+            "update product_variants set sku = $1, price = $2, stock = $3, available = $4 \
+            where id = $5 and product_id = $6",
+            updated_variant.sku,
+            updated_variant.price as Option<Money>,
+            updated_variant.stock,
+            updated_variant.available,
+            updated_variant.id,
+            updated_variant.product_id
+        )
+        .execute(&mut tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(VariantError::UnknownVariant(updated_variant.id).into());
+        }
+
+        Self::set_attributes(&mut tx, updated_variant.id, &updated_variant.attributes).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Default variants and variants that were already ordered are kept.
+    pub async fn delete_variant(pool: &PgPool, id: i32) -> Result<()> {
+        let variant = Self::get_variant(pool, id).await?;
+        if variant.is_default {
+            return Err(VariantError::DefaultVariant(id).into());
+        }
+
+        let mut tx = pool.begin().await?;
+        sqlx::query!("delete from variant_attributes where variant_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        let deleted = sqlx::query!(
+            "delete from product_variants where id = $1 \
+            and not exists (select 1 from products_in_orders where variant_id = $1)",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!("Variant with id {} was already ordered", id));
+        }
+        sqlx::query!("delete from cart_items where variant_id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_attributes(
+        pool: &PgPool,
+        variant_ids: &[i32],
+    ) -> Result<HashMap<i32, HashMap<String, Value>>> {
+        let rows = sqlx::query!(
+            r#"select variant_id, code, kind as "kind: AttributeKind", value
+            from variant_attributes join attributes on attributes.id = attribute_id
+            where variant_id = any($1)"#,
+            variant_ids
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut attributes: HashMap<i32, HashMap<String, Value>> = HashMap::new();
+        for row in rows {
+            attributes
+                .entry(row.variant_id)
+                .or_default()
+                .insert(row.code, row.kind.from_text(&row.value));
+        }
+        Ok(attributes)
+    }
+
+    async fn set_attributes(
+        conn: &mut PgConnection,
+        variant_id: i32,
+        attributes: &HashMap<String, Value>,
+    ) -> Result<()> {
+        sqlx::query!("delete from variant_attributes where variant_id = $1", variant_id)
+            .execute(&mut *conn)
+            .await?;
+        if attributes.is_empty() {
+            return Ok(());
+        }
+
+        let codes: Vec<String> = attributes.keys().cloned().collect();
+        let known_attributes: HashMap<String, (i32, AttributeKind)> = sqlx::query!(
+            r#"select id, code, kind as "kind: AttributeKind" from attributes where code = any($1)"#,
+            &codes
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.code, (row.id, row.kind)))
+        .collect();
+
+        let mut values = Vec::with_capacity(attributes.len());
+        for (code, value) in attributes.iter() {
+            let (attribute_id, kind) = known_attributes
+                .get(code)
+                .ok_or_else(|| VariantError::UnknownAttribute(code.clone()))?;
+            let value = kind
+                .to_text(value)
+                .ok_or_else(|| VariantError::InvalidAttributeValue(code.clone()))?;
+            values.push((*attribute_id, value));
+        }
+
+        bulk_insert(
+            conn,
+            "variant_attributes",
+            &["variant_id", "attribute_id", "value"],
+            &values,
+            |mut builder, (attribute_id, value)| {
+                builder
+                    .push_bind(variant_id)
+                    .push_bind(*attribute_id)
+                    .push_bind(value);
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use color_eyre::{eyre::eyre, Result};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::db_actions::{get_pool, Clearable};
+use crate::models::{
+    ListParams, NewWebhookSubscription, Page, WebhookDelivery, WebhookDeliveryFilter,
+    WebhookError, WebhookEventType, WebhookPayload, WebhookSubscription,
+};
+
+use super::fetch_page;
+use super::job_queue::{Job, JobQueue};
+
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const WEBHOOK_EVENT_HEADER: &str = "x-webhook-event";
+pub const WEBHOOK_DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+/// Deliveries are given up on and marked dead after this many attempts.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 10;
+
+const DELIVERY_COLUMNS: &str = "id, subscription_id, event_id, \
+    (select event_type from webhook_events where webhook_events.id = event_id) as event_type, \
+    status, attempts, next_attempt_at, last_attempt_at, response_status, last_error, created_at";
+
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("HTTP client has a valid configuration")
+});
+
+/// Delivery waiting to be sent, with everything needed to send it.
+#[derive(sqlx::FromRow)]
+struct PendingDelivery {
+    id: i32,
+    attempts: i32,
+    url: String,
+    secret: String,
+    event_id: i32,
+    event_type: WebhookEventType,
+    payload: serde_json::Value,
+    event_created_at: NaiveDateTime,
+}
+
+pub struct WebhookService;
+
+#[async_trait]
+impl Clearable for WebhookService {
+    async fn clear(&self) -> Result<()> {
+        let pool = get_pool().await?;
+        sqlx::query!("delete from webhook_deliveries")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from webhook_events")
+            .execute(&pool)
+            .await?;
+        sqlx::query!("delete from webhook_subscriptions")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl WebhookService {
+    pub async fn get_subscriptions(pool: &PgPool) -> Result<Vec<WebhookSubscription>> {
+        Ok(
+            sqlx::query_as("select * from webhook_subscriptions order by id")
+                .fetch_all(pool)
+                .await?,
+        )
+    }
+
+    pub async fn get_subscription(pool: &PgPool, id: i32) -> Result<WebhookSubscription> {
+        sqlx::query_as("select * from webhook_subscriptions where id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| eyre!("Webhook subscription with id {} does not exist", id))
+    }
+
+    /// Only events recorded from now on are sent to the new subscription.
+    pub async fn create_subscription(
+        pool: &PgPool,
+        subscription: NewWebhookSubscription,
+    ) -> Result<WebhookSubscription> {
+        Self::validate(&subscription)?;
+        let secret = subscription.secret.unwrap_or_else(Self::generate_secret);
+        Ok(sqlx::query_as(
+            "insert into webhook_subscriptions (url, event_types, secret, active) \
+            values ($1, $2, $3, $4) returning *",
+        )
+        .bind(&subscription.url)
+        .bind(&subscription.event_types)
+        .bind(secret)
+        .bind(subscription.active)
+        .fetch_one(pool)
+        .await?)
+    }
+
+    pub async fn update_subscription(
+        pool: &PgPool,
+        id: i32,
+        subscription: NewWebhookSubscription,
+    ) -> Result<WebhookSubscription> {
+        Self::validate(&subscription)?;
+        let mut tx = pool.begin().await?;
+        let updated: WebhookSubscription = sqlx::query_as(
+            "update webhook_subscriptions \
+            set url = $2, event_types = $3, secret = coalesce($4, secret), active = $5 \
+            where id = $1 returning *",
+        )
+        .bind(id)
+        .bind(&subscription.url)
+        .bind(&subscription.event_types)
+        .bind(&subscription.secret)
+        .bind(subscription.active)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| eyre!("Webhook subscription with id {} does not exist", id))?;
+
+        // deliveries left pending while the subscription was paused are sent
+        // once it is active again
+        if updated.active {
+            let paused = sqlx::query_scalar!(
+                "select id from webhook_deliveries where subscription_id = $1 \
+                and status = 'pending' and not exists ( \
+                    select 1 from jobs where kind = $2 and status in ('queued', 'running') \
+                    and (payload->>'delivery_id')::int = webhook_deliveries.id \
+                )",
+                id,
+                DeliverWebhook::KIND
+            )
+            .fetch_all(&mut tx)
+            .await?;
+            for delivery_id in paused {
+                JobQueue::enqueue(&mut tx, &DeliverWebhook { delivery_id }).await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    /// Deletes the subscription together with its delivery log.
+    pub async fn delete_subscription(pool: &PgPool, id: i32) -> Result<()> {
+        let deleted = sqlx::query!("delete from webhook_subscriptions where id = $1", id)
+            .execute(pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(eyre!("Webhook subscription with id {} does not exist", id));
+        }
+        Ok(())
+    }
+
+    /// Delivery log of the subscription. `sort=id:desc` lists the newest
+    /// deliveries first.
+    pub async fn get_deliveries(
+        pool: &PgPool,
+        subscription_id: i32,
+        list_params: &ListParams,
+        filter: &WebhookDeliveryFilter,
+    ) -> Result<Page<WebhookDelivery>> {
+        Self::get_subscription(pool, subscription_id).await?;
+        fetch_page(
+            pool,
+            "webhook_deliveries",
+            DELIVERY_COLUMNS,
+            &[
+                ("id", "id", "integer"),
+                ("status", "status", "webhook_delivery_status"),
+                ("attempts", "attempts", "integer"),
+                ("next_attempt_at", "next_attempt_at", "timestamp"),
+                ("created_at", "created_at", "timestamp"),
+            ],
+            list_params,
+            |query_builder| {
+                query_builder
+                    .push(" and subscription_id = ")
+                    .push_bind(subscription_id);
+                if let Some(status) = filter.status {
+                    query_builder.push(" and status = ").push_bind(status);
+                }
+            },
+        )
+        .await
+    }
+
+    /// Sends a dead delivery again, with as many attempts left as a new one.
+    pub async fn retry_delivery(pool: &PgPool, id: i32) -> Result<WebhookDelivery> {
+        let mut tx = pool.begin().await?;
+        let retried: Option<WebhookDelivery> = sqlx::query_as(&format!(
+            "update webhook_deliveries \
+            set status = 'pending', attempts = 0, next_attempt_at = now() \
+            where id = $1 and status = 'dead' returning {DELIVERY_COLUMNS}"
+        ))
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+        if let Some(delivery) = retried {
+            JobQueue::enqueue(&mut tx, &DeliverWebhook { delivery_id: id }).await?;
+            tx.commit().await?;
+            return Ok(delivery);
+        }
+
+        let exists: Option<i32> =
+            sqlx::query_scalar!("select id from webhook_deliveries where id = $1", id)
+                .fetch_optional(&mut tx)
+                .await?;
+        match exists {
+            Some(_) => Err(WebhookError::NotDead(id).into()),
+            None => Err(eyre!("Webhook delivery with id {} does not exist", id)),
+        }
+    }
+
+    /// Hex encoded HMAC-SHA256 of `payload`, sent in `X-Webhook-Signature`
+    /// for receivers to check that payloads come from this server.
+    pub fn sign(secret: &str, payload: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn validate(subscription: &NewWebhookSubscription) -> Result<()> {
+        let valid_url = reqwest::Url::parse(&subscription.url)
+            .map(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+            .unwrap_or(false);
+        if !valid_url {
+            return Err(WebhookError::InvalidUrl(subscription.url.clone()).into());
+        }
+        if subscription.event_types.is_empty() {
+            return Err(WebhookError::NoEventTypes.into());
+        }
+        Ok(())
+    }
+
+    fn generate_secret() -> String {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        hex::encode(secret)
+    }
+}
+
+/// Queues a delivery of a recorded event to every active subscription for its
+/// type. Queued by the database together with the event.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchWebhookEvent {
+    pub event_id: i32,
+}
+
+#[async_trait]
+impl Job for DispatchWebhookEvent {
+    const KIND: &'static str = "dispatch_webhook_event";
+
+    async fn run(&self, pool: &PgPool) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        // events dispatched before are left alone, so running again is harmless
+        let deliveries = sqlx::query_scalar!(
+            r#"with event as (
+                update webhook_events set dispatched_at = now()
+                where id = $1 and dispatched_at is null returning id, event_type
+            )
+            insert into webhook_deliveries (subscription_id, event_id)
+            select webhook_subscriptions.id, event.id from event
+            join webhook_subscriptions on webhook_subscriptions.active
+                and event.event_type = any(webhook_subscriptions.event_types)
+            on conflict do nothing
+            returning id"#,
+            self.event_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        for delivery_id in deliveries {
+            JobQueue::enqueue(&mut tx, &DeliverWebhook { delivery_id }).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Sends an event to a subscription, retried until the receiver accepts it
+/// or the delivery is marked dead.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeliverWebhook {
+    pub delivery_id: i32,
+}
+
+#[async_trait]
+impl Job for DeliverWebhook {
+    const KIND: &'static str = "deliver_webhook";
+    const MAX_ATTEMPTS: i32 = MAX_DELIVERY_ATTEMPTS;
+
+    /// Sends the delivery and logs the attempt. Deliveries count as delivered
+    /// once the receiver responds with a 2xx status.
+    async fn run(&self, pool: &PgPool) -> Result<()> {
+        let delivery: Option<PendingDelivery> = sqlx::query_as(
+            "select webhook_deliveries.id, attempts, url, secret, event_id, event_type, payload, \
+                webhook_events.created_at as event_created_at \
+            from webhook_deliveries \
+            join webhook_subscriptions on webhook_subscriptions.id = subscription_id \
+            join webhook_events on webhook_events.id = event_id \
+            where webhook_deliveries.id = $1 and status = 'pending' \
+            and webhook_subscriptions.active",
+        )
+        .bind(self.delivery_id)
+        .fetch_optional(pool)
+        .await?;
+        // delivered, dead, deleted or paused in the meantime
+        let Some(delivery) = delivery else {
+            return Ok(());
+        };
+
+        let payload = serde_json::to_vec(&WebhookPayload {
+            id: delivery.event_id,
+            event_type: delivery.event_type,
+            created_at: delivery.event_created_at,
+            data: delivery.payload,
+        })?;
+        let event_type = serde_json::to_value(delivery.event_type)?;
+        let response = HTTP_CLIENT
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                WEBHOOK_SIGNATURE_HEADER,
+                WebhookService::sign(&delivery.secret, &payload),
+            )
+            .header(WEBHOOK_EVENT_HEADER, event_type.as_str().unwrap_or_default())
+            .header(WEBHOOK_DELIVERY_HEADER, delivery.id)
+            .body(payload)
+            .send()
+            .await;
+
+        let (response_status, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16() as i32), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16() as i32),
+                Some(format!("Receiver responded with {}", response.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let Some(error) = error else {
+            info!("Delivered webhook {} to {}", delivery.id, delivery.url);
+            sqlx::query!(
+                "update webhook_deliveries set status = 'delivered', attempts = $2, \
+                last_attempt_at = now(), response_status = $3, last_error = null \
+                where id = $1",
+                delivery.id,
+                attempts,
+                response_status
+            )
+            .execute(pool)
+            .await?;
+            return Ok(());
+        };
+
+        sqlx::query!(
+            "update webhook_deliveries set attempts = $2, \
+            next_attempt_at = now() + make_interval(secs => $3), \
+            last_attempt_at = now(), response_status = $4, last_error = $5 \
+            where id = $1",
+            delivery.id,
+            attempts,
+            Self::retry_delay(attempts).as_secs_f64(),
+            response_status,
+            error
+        )
+        .execute(pool)
+        .await?;
+        Err(eyre!("Webhook {} was not delivered: {}", delivery.id, error))
+    }
+
+    async fn give_up(&self, pool: &PgPool, _error: &str) -> Result<()> {
+        sqlx::query!(
+            "update webhook_deliveries set status = 'dead' where id = $1 and status = 'pending'",
+            self.delivery_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
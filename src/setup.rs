@@ -4,7 +4,11 @@ use dotenvy::dotenv;
 use once_cell::sync::Lazy;
 use std::env;
 use tracing_subscriber::EnvFilter;
+use crate::graphql::{build_schema, ShopSchema};
 use crate::models::Keys;
+use crate::services::{
+    BlobStore, FakePaymentProvider, LocalBlobStore, PaymentProvider, S3BlobStore, S3Config,
+};
 
 pub static KEYS: Lazy<Keys> = Lazy::new(|| {
     let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
@@ -13,6 +17,44 @@ pub static KEYS: Lazy<Keys> = Lazy::new(|| {
 
 pub static PEPPER: Lazy<String> = Lazy::new(|| std::env::var("PEPPER").expect("PEPPER must be set"));
 
+pub static PAYMENT_PROVIDER: Lazy<Box<dyn PaymentProvider>> = Lazy::new(|| {
+    let secret =
+        std::env::var("PAYMENT_WEBHOOK_SECRET").expect("PAYMENT_WEBHOOK_SECRET must be set");
+    let provider = std::env::var("PAYMENT_PROVIDER").unwrap_or_else(|_| "fake".to_string());
+    match provider.as_str() {
+        "fake" => Box::new(FakePaymentProvider::new(secret.as_bytes())),
+        other => panic!("Unknown PAYMENT_PROVIDER: {other}"),
+    }
+});
+
+/// Uploads are kept on the local disk unless `BLOB_STORE=s3` is set. Blobs
+/// are served by the server itself below `MEDIA_URL`, unless an S3 bucket
+/// is public under `S3_PUBLIC_URL`.
+pub static BLOB_STORE: Lazy<Box<dyn BlobStore>> = Lazy::new(|| {
+    let media_url = std::env::var("MEDIA_URL").unwrap_or_else(|_| "/api/media".to_string());
+    let store = std::env::var("BLOB_STORE").unwrap_or_else(|_| "local".to_string());
+    match store.as_str() {
+        "local" => {
+            let root = std::env::var("BLOB_STORE_PATH").unwrap_or_else(|_| "uploads".to_string());
+            Box::new(LocalBlobStore::new(root, &media_url))
+        }
+        "s3" => {
+            let s3_var = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{name} must be set"));
+            Box::new(S3BlobStore::new(S3Config {
+                endpoint: s3_var("S3_ENDPOINT"),
+                bucket: s3_var("S3_BUCKET"),
+                region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key_id: s3_var("S3_ACCESS_KEY_ID"),
+                secret_access_key: s3_var("S3_SECRET_ACCESS_KEY"),
+                public_url: std::env::var("S3_PUBLIC_URL").unwrap_or(media_url),
+            }))
+        }
+        other => panic!("Unknown BLOB_STORE: {other}"),
+    }
+});
+
+pub static GRAPHQL_SCHEMA: Lazy<ShopSchema> = Lazy::new(build_schema);
+
 pub async fn setup() -> Result<()> {
     dotenv()?;
 
@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::put,
+    Router,
+};
+use color_eyre::Result;
+use data::services::{Blob, BlobStore, LocalBlobStore, S3BlobStore, S3Config};
+use sha2::{Digest, Sha256};
+
+type Objects = Arc<Mutex<HashMap<String, Blob>>>;
+
+/// Rejects requests that are not signed by `test-key` or whose body does
+/// not match the signed payload hash.
+fn is_signed(headers: &HeaderMap, body: &[u8]) -> bool {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let payload_hash = headers
+        .get("x-amz-content-sha256")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    authorization.starts_with("AWS4-HMAC-SHA256 Credential=test-key/")
+        && authorization.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date")
+        && payload_hash == hex::encode(Sha256::digest(body))
+}
+
+async fn put_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if !is_signed(&headers, &body) {
+        return StatusCode::FORBIDDEN;
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    objects.lock().unwrap().insert(
+        format!("{bucket}/{key}"),
+        Blob {
+            data: body.to_vec(),
+            content_type,
+        },
+    );
+    StatusCode::OK
+}
+
+async fn get_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !is_signed(&headers, &[]) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let blob = objects
+        .lock()
+        .unwrap()
+        .get(&format!("{bucket}/{key}"))
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(([(header::CONTENT_TYPE, blob.content_type)], blob.data))
+}
+
+async fn delete_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !is_signed(&headers, &[]) {
+        return StatusCode::FORBIDDEN;
+    }
+    objects.lock().unwrap().remove(&format!("{bucket}/{key}"));
+    StatusCode::NO_CONTENT
+}
+
+/// Starts a minimal stand-in for an S3 compatible service on a free port.
+async fn start_s3_stand_in() -> Result<SocketAddr> {
+    let router = Router::new()
+        .route(
+            "/:bucket/*key",
+            put(put_object).get(get_object).delete(delete_object),
+        )
+        .with_state(Objects::default());
+    let server = axum::Server::bind(&"127.0.0.1:0".parse()?).serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    Ok(addr)
+}
+
+async fn check_round_trip(store: &dyn BlobStore) -> Result<()> {
+    let key = "products/1/test.png";
+    store.put(key, b"png data".to_vec(), "image/png").await?;
+    assert_eq!(
+        store.get(key).await?,
+        Some(Blob {
+            data: b"png data".to_vec(),
+            content_type: "image/png".to_string(),
+        })
+    );
+
+    store.delete(key).await?;
+    assert_eq!(store.get(key).await?, None);
+    // deleting twice is fine
+    store.delete(key).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_blob_store() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("blob_store_{}", std::process::id()));
+    let store = LocalBlobStore::new(&root, "/api/media/");
+    check_round_trip(&store).await?;
+
+    assert_eq!(store.url("a/b.png"), "/api/media/a/b.png");
+    assert!(store.put("../escape.png", Vec::new(), "image/png").await.is_err());
+    assert_eq!(store.get("../Cargo.toml").await?, None);
+
+    tokio::fs::remove_dir_all(root).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_s3_blob_store() -> Result<()> {
+    let addr = start_s3_stand_in().await?;
+    let config = || S3Config {
+        endpoint: format!("http://{addr}"),
+        bucket: "images".to_string(),
+        region: "us-east-1".to_string(),
+        access_key_id: "test-key".to_string(),
+        secret_access_key: "test-secret".to_string(),
+        public_url: "https://cdn.example.com/images/".to_string(),
+    };
+
+    let store = S3BlobStore::new(config());
+    check_round_trip(&store).await?;
+    assert_eq!(
+        store.url("products/1/a b.png"),
+        "https://cdn.example.com/images/products/1/a%20b.png"
+    );
+
+    let store = S3BlobStore::new(S3Config {
+        access_key_id: "other-key".to_string(),
+        ..config()
+    });
+    assert!(store.put("a.png", Vec::new(), "image/png").await.is_err());
+
+    Ok(())
+}
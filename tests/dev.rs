@@ -2,10 +2,15 @@ use std::{collections::HashMap, fmt::Display};
 
 use color_eyre::{eyre::eyre, Result};
 use data::models::Customer;
+use data::services::{FakePaymentProvider, PAYMENT_SIGNATURE_HEADER};
 use once_cell::sync::Lazy;
-use reqwest::{header::AUTHORIZATION, Client};
+use reqwest::{
+    header::{ACCEPT, ALLOW, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH},
+    Client,
+};
 use serde::Deserialize;
 use serde_json::json;
+use utoipa::OpenApi;
 
 #[derive(Deserialize, Debug)]
 struct AuthResponse {
@@ -57,40 +62,1972 @@ macro_rules! test_admin_endpoint {
     };
 }
 
+async fn authorize(rc: &Client, name: &str) -> Result<String> {
+    let mut credentials = HashMap::new();
+    credentials.insert("name", name);
+    credentials.insert("password", "example_password");
+    Ok("Bearer ".to_string()
+        + &rc
+            .post(URL.to_string() + "/api/user/authorize")
+            .json(&json!(credentials))
+            .send()
+            .await?
+            .json::<AuthResponse>()
+            .await?
+            .token)
+}
+
+/// Pays the order in full through the fake provider's webhook.
+async fn pay_order(
+    rc: &Client,
+    token: &str,
+    order_id: &str,
+) -> Result<data::models::PaymentIntent> {
+    let payment_intent = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/payment"),
+        token,
+        json!({ "order_id": order_id.parse::<i32>()? })
+    )
+    .json::<data::models::PaymentIntent>()
+    .await?;
+    let provider = FakePaymentProvider::new(std::env::var("PAYMENT_WEBHOOK_SECRET")?.as_bytes());
+    let event = json!({
+        "provider_reference": payment_intent.provider_reference,
+        "status": "succeeded"
+    })
+    .to_string();
+    let response = rc
+        .post(URL.to_string() + "/api/payment/webhook")
+        .header(PAYMENT_SIGNATURE_HEADER, provider.sign(event.as_bytes()))
+        .body(event)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(payment_intent)
+}
+
 #[tokio::test]
 async fn test_no_auth_routes() -> Result<()> {
     let endpoints = [
-        "/api/product/1",
+        "/api/v1/products/1",
         "/api/product/all",
-        "/api/order/1",
+        "/api/v1/orders/1",
         "/api/order/all",
     ];
     test_get_request_no_auth_endpoints!(endpoints);
 
     let rc = Client::new();
-    let order = json!(
-        {
+    let order = json!(
+        {
+            "id": 0,
+            "customer_id": 1,
+            "status": "Done",
+            "created_at": "2023-04-25T08:41:23.104715",
+            "products": {
+                "1": 5,
+                "2": 15
+            }
+        }
+    );
+    let response = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&order)
+        .send()
+        .await?;
+    dbg!(response.text().await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_routes() -> Result<()> {
+    let rc = Client::new();
+
+    let response = rc
+        .get(URL.to_string() + "/api/product/all?limit=1&sort=price:desc&available=true")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let total: usize = response.headers()[data::models::TOTAL_COUNT_HEADER]
+        .to_str()?
+        .parse()?;
+    let link = response.headers()[reqwest::header::LINK].to_str()?.to_string();
+    let first_page = response.json::<Vec<data::models::Product>>().await?;
+    assert_eq!(first_page.len(), 1);
+    assert!(total >= 2);
+
+    let next = link
+        .split(", ")
+        .find(|link| link.ends_with("rel=\"next\""))
+        .and_then(|link| link.split(['<', '>']).nth(1))
+        .ok_or(eyre!("No link to the next page in {}", link))?;
+    let second_page = rc
+        .get(URL.to_string() + next)
+        .send()
+        .await?
+        .json::<Vec<data::models::Product>>()
+        .await?;
+    assert_eq!(second_page.len(), 1);
+    assert!(second_page[0].price.cents <= first_page[0].price.cents);
+    assert_ne!(second_page[0].id, first_page[0].id);
+
+    // the next page starts behind the last product shown, products added in between do not
+    // push it back onto the next page
+    let response = rc
+        .get(URL.to_string() + "/api/product/all?limit=1&sort=id:desc")
+        .send()
+        .await?;
+    let link = response.headers()[reqwest::header::LINK].to_str()?.to_string();
+    let first_page = response.json::<Vec<data::models::Product>>().await?;
+    let token = authorize(&rc, "example_admin").await?;
+    test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/product"),
+        &token,
+        json!({
+            "id": 0,
+            "name": "Listed in between",
+            "price": { "cents": 100, "currency": "PLN" },
+            "available": true
+        })
+    );
+    let next = link
+        .split(", ")
+        .find(|link| link.ends_with("rel=\"next\""))
+        .and_then(|link| link.split(['<', '>']).nth(1))
+        .ok_or(eyre!("No link to the next page in {}", link))?;
+    let second_page = rc
+        .get(URL.to_string() + next)
+        .send()
+        .await?
+        .json::<Vec<data::models::Product>>()
+        .await?;
+    assert!(second_page[0].id < first_page[0].id);
+
+    for cursor in [hex::encode("offset:1"), hex::encode("[\"x\"]"), "zz".to_string()] {
+        let response = rc
+            .get(URL.to_string() + "/api/product/all?sort=id:desc&cursor=" + &cursor)
+            .send()
+            .await?;
+        assert_eq!(response.status(), 400);
+    }
+
+    let response = rc
+        .get(URL.to_string() + "/api/product/all?sort=password:asc")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 400);
+    let response = rc
+        .get(URL.to_string() + "/api/order/all?limit=0")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    let orders = rc
+        .get(URL.to_string() + "/api/order/all?customer_id=1&created_from=2023-01-01T00:00:00")
+        .send()
+        .await?
+        .json::<Vec<data::models::Order>>()
+        .await?;
+    assert!(orders.iter().all(|order| order.customer_id == 1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_list_includes() -> Result<()> {
+    let rc = Client::new();
+
+    let orders = rc
+        .get(URL.to_string() + "/api/order/all?sort=id:asc")
+        .send()
+        .await?
+        .json::<Vec<serde_json::Value>>()
+        .await?;
+    assert!(orders.iter().all(|order| order.get("products").is_none()));
+
+    let orders = rc
+        .get(URL.to_string() + "/api/order/all?sort=id:asc&include=products")
+        .send()
+        .await?
+        .json::<Vec<data::models::ListedOrder>>()
+        .await?;
+    let listed = orders
+        .iter()
+        .find(|listed| listed.order.id == 1)
+        .ok_or(eyre!("Order 1 is not listed"))?;
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=1")
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    let lines: Vec<data::models::OrderLine> = serde_json::from_value(order["lines"].clone())?;
+    let products = listed
+        .products
+        .as_ref()
+        .ok_or(eyre!("Order 1 is listed without its products"))?;
+    assert_eq!(
+        products
+            .iter()
+            .map(|product| (product.variant_id, product.quantity, product.unit_price))
+            .collect::<Vec<_>>(),
+        lines
+            .iter()
+            .map(|line| (line.variant_id, line.quantity, line.unit_price))
+            .collect::<Vec<_>>()
+    );
+    assert!(products.iter().all(|product| !product.name.is_empty()));
+
+    let response = rc
+        .get(URL.to_string() + "/api/order/all?include=customers")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fields_and_expand() -> Result<()> {
+    let rc = Client::new();
+    let admin_token = authorize(&rc, "example_admin").await?;
+    let customer_token = authorize(&rc, "example_customer").await?;
+    let get = |route: &str| rc.get(URL.to_string() + route).send();
+    let get_auth = |route: &str, token: &str| {
+        rc.get(URL.to_string() + route)
+            .header("Authorization", token)
+            .send()
+    };
+    let keys = |value: &serde_json::Value| {
+        let mut keys: Vec<String> = value
+            .as_object()
+            .map(|object| object.keys().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    };
+
+    let product = get("/api/product?id=1&fields=name,id,missing")
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(keys(&product), vec!["id", "name"]);
+
+    // the trimmed product is tagged apart from the whole one
+    let etag = get("/api/product?id=1").await?.headers()[ETAG].clone();
+    let response = rc
+        .get(URL.to_string() + "/api/product?id=1&fields=id,name")
+        .header(IF_NONE_MATCH, etag.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let fields_etag = response.headers()[ETAG].clone();
+    assert_ne!(fields_etag, etag);
+    let response = rc
+        .get(URL.to_string() + "/api/product?id=1&fields=name,id")
+        .header(IF_NONE_MATCH, fields_etag)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 304);
+
+    let products = get("/api/product/all?fields=id,price")
+        .await?
+        .json::<Vec<serde_json::Value>>()
+        .await?;
+    assert!(!products.is_empty());
+    assert!(products
+        .iter()
+        .all(|product| keys(product) == vec!["id", "price"]));
+
+    let response = get("/api/order?id=1&expand=customer").await?;
+    assert_eq!(response.status(), 401);
+    let response = get_auth("/api/order?id=1&expand=customer", &customer_token).await?;
+    assert_eq!(response.status(), 403);
+
+    let order = get_auth(
+        "/api/order?id=1&fields=id,customer_id,lines&expand=customer,products",
+        &admin_token,
+    )
+    .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(keys(&order), vec!["customer_id", "expanded", "id", "lines"]);
+    let expanded: data::models::OrderExpansion =
+        serde_json::from_value(order["expanded"].clone())?;
+    assert_eq!(
+        expanded.customer.map(|customer| customer.id),
+        order["customer_id"].as_i64().map(|id| id as i32)
+    );
+    let mut ordered_ids: Vec<i64> = order["lines"]
+        .as_array()
+        .ok_or(eyre!("Order has no lines"))?
+        .iter()
+        .filter_map(|line| line["product_id"].as_i64())
+        .collect();
+    ordered_ids.dedup();
+    assert_eq!(
+        expanded
+            .products
+            .unwrap_or_default()
+            .iter()
+            .map(|product| product.id as i64)
+            .collect::<Vec<_>>(),
+        ordered_ids
+    );
+
+    let orders = get_auth("/api/order/all?customer_id=1&expand=customer", &admin_token)
+        .await?
+        .json::<Vec<serde_json::Value>>()
+        .await?;
+    assert!(!orders.is_empty());
+    assert!(orders.iter().all(|order| {
+        order["expanded"]["customer"]["id"] == 1 && order["expanded"].get("products").is_none()
+    }));
+    let response = get("/api/v1/orders?customer_id=1&expand=customer").await?;
+    assert_eq!(response.status(), 401);
+    let orders = get_auth("/api/order/all?customer_id=1&expand=customer", &customer_token)
+        .await?
+        .json::<Vec<serde_json::Value>>()
+        .await?;
+    assert!(!orders.is_empty());
+    assert!(orders
+        .iter()
+        .all(|order| order["expanded"].get("customer").is_none()));
+
+    let response = get("/api/order?id=1&expand=payments").await?;
+    assert_eq!(response.status(), 400);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_api_versions() -> Result<()> {
+    let rc = Client::new();
+
+    let response = rc.get(URL.to_string() + "/api/v1/products/1").send().await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("deprecation").is_none());
+    let product = response.json::<data::models::Product>().await?;
+    assert_eq!(product.id, 1);
+
+    let response = rc.get(URL.to_string() + "/api/product?id=1").send().await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert_eq!(response.json::<data::models::Product>().await?.id, 1);
+
+    let response = rc
+        .get(URL.to_string() + "/api/products/1?fields=id")
+        .header(ACCEPT, "application/vnd.shop.v1+json")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("deprecation").is_none());
+    assert_eq!(response.json::<serde_json::Value>().await?, json!({ "id": 1 }));
+
+    let response = rc
+        .get(URL.to_string() + "/api/products/1")
+        .header(ACCEPT, "application/vnd.shop.v2+json")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 406);
+
+    let response = rc.get(URL.to_string() + "/api/v1/products/one").send().await?;
+    assert_eq!(response.status(), 400);
+    let response = rc.delete(URL.to_string() + "/api/v1/products/1").send().await?;
+    assert_eq!(response.status(), 405);
+
+    Ok(())
+}
+
+/// Every documented path has to be routed with exactly the documented
+/// methods. Methods the router does not know get a 405 listing the ones it
+/// does in `Allow`, even behind the auth middleware.
+#[tokio::test]
+async fn test_openapi_matches_routes() -> Result<()> {
+    let rc = Client::new();
+    let spec = rc
+        .get(URL.to_string() + "/api/openapi.json")
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(spec, serde_json::to_value(data::openapi::ApiDoc::openapi())?);
+
+    let paths = spec["paths"].as_object().ok_or(eyre!("Spec has no paths"))?;
+    assert!(!paths.is_empty());
+
+    // every operation the router serves is documented, and nothing else
+    let mut documented: Vec<String> = paths
+        .iter()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .into_iter()
+                .flat_map(|operations| operations.keys())
+                .map(move |method| format!("{} {}", method.to_uppercase(), path))
+        })
+        .collect();
+    documented.sort();
+    let mut routed: Vec<String> = data::app::V1Routes::operations()
+        .iter()
+        .map(|(method, path)| format!("{method} {path}"))
+        .collect();
+    routed.sort();
+    assert_eq!(documented, routed);
+
+    for (path, operations) in paths {
+        let mut documented: Vec<String> = operations
+            .as_object()
+            .ok_or(eyre!("{} has no operations", path))?
+            .keys()
+            .map(|method| method.to_uppercase())
+            .collect();
+        documented.sort();
+
+        let url = path.replace("{id}", "1").replace("{key}", "missing");
+        let response = rc
+            .request(reqwest::Method::TRACE, URL.to_string() + &url)
+            .send()
+            .await?;
+        let allow = response
+            .headers()
+            .get(ALLOW)
+            .ok_or(eyre!("{} is not routed", path))?
+            .to_str()?;
+        let mut routed: Vec<String> = allow
+            .split(',')
+            .map(|method| method.trim().to_string())
+            .filter(|method| method != "HEAD")
+            .collect();
+        routed.sort();
+        assert_eq!(routed, documented, "methods of {} differ", path);
+    }
+
+    let response = rc.get(URL.to_string() + "/api/docs/").send().await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+/// Sends a GraphQL request and returns the whole response body.
+async fn graphql(rc: &Client, token: Option<&str>, query: &str) -> Result<serde_json::Value> {
+    let mut request = rc
+        .post(URL.to_string() + "/graphql")
+        .json(&json!({ "query": query }));
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, token);
+    }
+    Ok(request.send().await?.json().await?)
+}
+
+#[tokio::test]
+async fn test_graphql() -> Result<()> {
+    let rc = Client::new();
+    let admin_token = authorize(&rc, "example_admin").await?;
+    let customer_token = authorize(&rc, "example_customer").await?;
+
+    let list_orders = "{ orders(limit: 2, sort: \"id\") { total items { id customer { id } \
+        products { productId quantity product { id name } } } } }";
+    let response = graphql(&rc, Some(&admin_token), list_orders).await?;
+    assert!(response.get("errors").is_none(), "{}", response);
+    let orders = &response["data"]["orders"];
+    assert!(orders["total"].as_i64().unwrap_or_default() >= 2);
+    let items = orders["items"].as_array().ok_or(eyre!("No orders listed"))?;
+    assert_eq!(items.len(), 2);
+    for order in items {
+        assert!(order["customer"]["id"].is_i64());
+        let products = order["products"].as_array().ok_or(eyre!("No line items"))?;
+        for line in products {
+            assert_eq!(line["product"]["id"], line["productId"]);
+        }
+    }
+
+    // customers of orders are only shown to admins and to whoever placed them
+    let response = graphql(&rc, None, list_orders).await?;
+    assert_eq!(response["errors"][0]["extensions"]["status"], 401);
+    let response = graphql(&rc, None, "{ orders(limit: 1) { items { id } } }").await?;
+    assert!(response.get("errors").is_none(), "{}", response);
+    let response = graphql(
+        &rc,
+        Some(&customer_token),
+        "{ orders(filter: { customerId: 1 }) { items { customer { id } } } }",
+    )
+    .await?;
+    assert_eq!(response["errors"][0]["extensions"]["status"], 403);
+
+    let create_product = "mutation { createProduct(input: { name: \"Graphql lantern\", \
+        price: { cents: 1250, currency: EUR }, available: true, categoryId: 3 }) }";
+    let response = graphql(&rc, None, create_product).await?;
+    assert_eq!(response["data"], serde_json::Value::Null);
+    assert_eq!(response["errors"][0]["extensions"]["status"], 401);
+
+    let response = graphql(&rc, Some(&customer_token), create_product).await?;
+    assert_eq!(response["errors"][0]["extensions"]["status"], 403);
+
+    let response = graphql(&rc, Some(&admin_token), create_product).await?;
+    assert!(response.get("errors").is_none(), "{}", response);
+    let id = response["data"]["createProduct"]
+        .as_i64()
+        .ok_or(eyre!("No product id"))?;
+
+    let response = graphql(
+        &rc,
+        None,
+        &format!("{{ product(id: {id}) {{ name price {{ cents currency }} taxCategory }} }}"),
+    )
+    .await?;
+    assert_eq!(
+        response["data"]["product"],
+        json!({
+            "name": "Graphql lantern",
+            "price": { "cents": 1250, "currency": "EUR" },
+            "taxCategory": "standard"
+        })
+    );
+
+    let response = graphql(
+        &rc,
+        Some(&admin_token),
+        &format!("mutation {{ deleteProduct(id: {id}, hard: true) }}"),
+    )
+    .await?;
+    assert_eq!(response["data"]["deleteProduct"], true);
+    let response = graphql(&rc, None, &format!("{{ product(id: {id}) {{ id }} }}")).await?;
+    assert_eq!(response["errors"][0]["extensions"]["status"], 404);
+
+    let response = graphql(&rc, None, "{ products(limit: 0) { total } }").await?;
+    assert_eq!(response["errors"][0]["extensions"]["status"], 400);
+
+    Ok(())
+}
+
+/// gRPC is served on the same port as REST, over HTTP/2.
+#[tokio::test]
+async fn test_grpc() -> Result<()> {
+    use data::grpc::proto::{
+        auth_client::AuthClient, orders_client::OrdersClient, products_client::ProductsClient,
+        AuthorizeRequest, CreateProductRequest, Currency, DeleteProductRequest,
+        GetProductRequest, ListOrdersRequest, Money, Product,
+    };
+    use tonic::{metadata::MetadataValue, Code, Request};
+
+    let channel = tonic::transport::Endpoint::from_shared(URL.to_string())?
+        .connect()
+        .await?;
+    let mut products = ProductsClient::new(channel.clone());
+
+    let product = products
+        .get_product(GetProductRequest { id: 1 })
+        .await?
+        .into_inner();
+    assert_eq!(product.id, 1);
+    let status = products
+        .get_product(GetProductRequest { id: 0 })
+        .await
+        .expect_err("Product 0 does not exist");
+    assert_eq!(status.code(), Code::NotFound);
+
+    let orders = OrdersClient::new(channel.clone())
+        .list_orders(ListOrdersRequest {
+            limit: Some(2),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    assert_eq!(orders.orders.len(), 2);
+    assert!(orders.total >= 2);
+    assert!(orders.orders.iter().all(|order| !order.lines.is_empty()));
+
+    let new_product = CreateProductRequest {
+        product: Some(Product {
+            name: "Grpc kettle".to_string(),
+            price: Some(Money {
+                cents: 3400,
+                currency: Currency::Gbp.into(),
+            }),
+            available: true,
+            category_id: Some(3),
+            ..Default::default()
+        }),
+    };
+    let status = products
+        .create_product(new_product.clone())
+        .await
+        .expect_err("Products are created by admins only");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut auth = AuthClient::new(channel);
+    let customer_token = auth
+        .authorize(AuthorizeRequest {
+            name: "example_customer".to_string(),
+            password: "example_password".to_string(),
+        })
+        .await?
+        .into_inner();
+    let mut request = Request::new(new_product.clone());
+    request.metadata_mut().insert(
+        "authorization",
+        MetadataValue::try_from(format!("Bearer {}", customer_token.token))?,
+    );
+    let status = products
+        .create_product(request)
+        .await
+        .expect_err("Products are created by admins only");
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let admin_token = auth
+        .authorize(AuthorizeRequest {
+            name: "example_admin".to_string(),
+            password: "example_password".to_string(),
+        })
+        .await?
+        .into_inner();
+    let authorization = MetadataValue::try_from(format!("Bearer {}", admin_token.token))?;
+    let mut request = Request::new(new_product);
+    request
+        .metadata_mut()
+        .insert("authorization", authorization.clone());
+    let id = products.create_product(request).await?.into_inner().id;
+
+    let product = products
+        .get_product(GetProductRequest { id })
+        .await?
+        .into_inner();
+    assert_eq!(product.name, "Grpc kettle");
+    assert_eq!(product.tax_category, "standard");
+    assert_eq!(
+        product.price,
+        Some(Money {
+            cents: 3400,
+            currency: Currency::Gbp.into(),
+        })
+    );
+
+    let mut request = Request::new(DeleteProductRequest { id, hard: true });
+    request.metadata_mut().insert("authorization", authorization);
+    products.delete_product(request).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_product_search() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    // a word of their own keeps products of other tests and earlier runs out of the way
+    let run = format!("run{}", chrono::Utc::now().timestamp_micros());
+    let mut product_ids = Vec::new();
+    for (name, description) in [("Kettle", "Boils water"), ("Cookbook", "Vegetarian recipes")] {
+        let product_id = test_admin_endpoint!(
+            rc.post(URL.to_string() + "/api/admin/product"),
+            &token,
+            json!({
+                "id": 0,
+                "name": format!("{name} {run}"),
+                "price": { "cents": 2500, "currency": "PLN" },
+                "available": true,
+                "description": description
+            })
+        )
+        .text()
+        .await?;
+        product_ids.push(product_id.parse::<i32>()?);
+    }
+
+    let queries = [
+        ("cook", product_ids[1]),
+        ("vegetarian recipes", product_ids[1]),
+        ("ketle", product_ids[0]),
+    ];
+    for (query, expected_id) in queries {
+        let products = rc
+            .get(format!("{}/api/product/search?q={} {}", *URL, query, run))
+            .send()
+            .await?
+            .json::<Vec<data::models::Product>>()
+            .await?;
+        assert_eq!(
+            products.first().map(|product| product.id),
+            Some(expected_id),
+            "searching for {}",
+            query
+        );
+    }
+
+    let products = rc
+        .get(URL.to_string() + "/api/product/search?q=xyzzy")
+        .send()
+        .await?
+        .json::<Vec<data::models::Product>>()
+        .await?;
+    assert!(products.is_empty());
+
+    let response = rc
+        .get(URL.to_string() + "/api/product/search?q=%20")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    for product_id in product_ids {
+        let response = rc
+            .delete(format!("{}/api/v1/admin/products/{}?hard=true", *URL, product_id))
+            .header(AUTHORIZATION, &token)
+            .send()
+            .await?;
+        assert_eq!(response.status(), 200);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_variant_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let products = rc
+        .get(URL.to_string() + "/api/product/all?category_id=1")
+        .send()
+        .await?
+        .json::<Vec<data::models::Product>>()
+        .await?;
+    assert_eq!(
+        products.iter().map(|product| product.id).collect::<Vec<_>>(),
+        vec![1]
+    );
+
+    let variants = rc
+        .get(URL.to_string() + "/api/product/variants?id=1")
+        .send()
+        .await?
+        .json::<Vec<data::models::Variant>>()
+        .await?;
+    // default variants come first, their skus follow the product id
+    assert_eq!(variants[0].sku, "P-1");
+    let variant = variants
+        .iter()
+        .find(|variant| variant.sku == "TSHIRT-XL-WHITE")
+        .ok_or(eyre!("Mocked variant is missing"))?;
+    assert_eq!(variant.attributes["size"], json!("XL"));
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/product/variant"),
+        &token,
+        json!({
+            "id": 0,
+            "product_id": 1,
+            "sku": "TSHIRT-S-BLACK",
+            "price": null,
+            "stock": 10,
+            "attributes": { "organic": "maybe" }
+        })
+    );
+    assert_eq!(response.status(), 422);
+
+    let order_id = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": 1,
+            "status": "New",
+            "created_at": "2023-05-30T12:00:00",
+            "variants": { (variant.id.to_string()): 2 }
+        }))
+        .send()
+        .await?
+        .text()
+        .await?;
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(order["variants"][variant.id.to_string()], json!(2));
+    assert_eq!(order["lines"][0]["unit_price"]["cents"], json!(2299));
+
+    let stock = rc
+        .get(URL.to_string() + "/api/product/variants?id=1")
+        .send()
+        .await?
+        .json::<Vec<data::models::Variant>>()
+        .await?
+        .into_iter()
+        .find(|other| other.id == variant.id)
+        .and_then(|other| other.stock);
+    assert_eq!(stock, variant.stock.map(|stock| stock - 2));
+
+    let response = test_admin_endpoint!(
+        rc.delete(URL.to_string() + "/api/admin/product/variant?id=" + &variants[0].id.to_string()),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 409);
+
+    Ok(())
+}
+
+/// Encodes a plain image of the given size as PNG.
+fn png_image(width: u32, height: u32) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    image::DynamicImage::new_rgb8(width, height).write_to(
+        &mut std::io::Cursor::new(&mut data),
+        image::ImageFormat::Png,
+    )?;
+    Ok(data)
+}
+
+#[tokio::test]
+async fn test_product_image_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let form = reqwest::multipart::Form::new().part(
+        "image",
+        reqwest::multipart::Part::bytes(png_image(640, 480)?)
+            .file_name("photo.png")
+            .mime_str("image/png")?,
+    );
+    let images = rc
+        .post(URL.to_string() + "/api/admin/product/image?id=2")
+        .header(AUTHORIZATION, &token)
+        .multipart(form)
+        .send()
+        .await?
+        .json::<Vec<data::models::ProductImage>>()
+        .await?;
+    let image = &images[0];
+    assert_eq!((image.width, image.height), (640, 480));
+
+    let product = rc
+        .get(URL.to_string() + "/api/product?id=2")
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert!(product["images"]
+        .as_array()
+        .ok_or(eyre!("Product has no images"))?
+        .iter()
+        .any(|other| other["url"] == json!(image.url)));
+
+    let response = rc.get(URL.to_string() + &image.url).send().await?;
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let thumbnail = rc
+        .get(URL.to_string() + &image.thumbnail_url)
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    let thumbnail = image::load_from_memory(&thumbnail)?;
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 192));
+
+    let form = reqwest::multipart::Form::new().part(
+        "image",
+        reqwest::multipart::Part::bytes(b"not an image".to_vec()).file_name("notes.txt"),
+    );
+    let response = rc
+        .post(URL.to_string() + "/api/admin/product/image?id=2")
+        .header(AUTHORIZATION, &token)
+        .multipart(form)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 415);
+
+    let response = rc
+        .delete(URL.to_string() + "/api/admin/product/image?id=" + &image.id.to_string())
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc.get(URL.to_string() + &image.url).send().await?;
+    assert_eq!(response.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_soft_delete_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let product_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/product"),
+        &token,
+        json!({
+            "id": 0,
+            "name": "Discontinued product",
+            "price": { "cents": 98765, "currency": "PLN" },
+            "available": true
+        })
+    )
+    .text()
+    .await?;
+    let list_ids = |include_deleted: bool| {
+        let rc = rc.clone();
+        async move {
+            let products = rc
+                .get(format!(
+                    "{}/api/product/all?min_price=98765&max_price=98765&include_deleted={}",
+                    *URL, include_deleted
+                ))
+                .send()
+                .await?
+                .json::<Vec<data::models::Product>>()
+                .await?;
+            Ok::<_, color_eyre::Report>(
+                products.iter().map(|product| product.id.to_string()).collect::<Vec<_>>(),
+            )
+        }
+    };
+
+    let response = rc
+        .delete(URL.to_string() + "/api/admin/product/?id=" + &product_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc
+        .get(URL.to_string() + "/api/product?id=" + &product_id)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 404);
+    assert!(!list_ids(false).await?.contains(&product_id));
+    assert!(list_ids(true).await?.contains(&product_id));
+    // deleted products cannot be ordered
+    let response = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": 1,
+            "status": "New",
+            "created_at": "2023-06-03T12:00:00",
+            "products": { (product_id.clone()): 1 }
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), 422);
+
+    let response = rc
+        .post(URL.to_string() + "/api/admin/product/restore?id=" + &product_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(list_ids(false).await?.contains(&product_id));
+
+    let response = rc
+        .delete(URL.to_string() + "/api/admin/product/?hard=true&id=" + &product_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(!list_ids(true).await?.contains(&product_id));
+
+    // referenced rows are only ever soft deleted
+    for route in ["product", "customer"] {
+        let response = rc
+            .delete(format!("{}/api/admin/{}/?hard=true&id=1", *URL, route))
+            .header(AUTHORIZATION, &token)
+            .send()
+            .await?;
+        assert_eq!(response.status(), 409);
+    }
+
+    let order_id = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": 2,
+            "status": "New",
+            "created_at": "2023-06-03T12:00:00",
+            "products": { "2": 1 }
+        }))
+        .send()
+        .await?
+        .text()
+        .await?;
+    let response = rc
+        .delete(URL.to_string() + "/api/admin/order/?id=" + &order_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 404);
+    let response = rc
+        .post(URL.to_string() + "/api/admin/order/restore?id=" + &order_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc
+        .delete(URL.to_string() + "/api/admin/order/?hard=true&id=" + &order_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc
+        .post(URL.to_string() + "/api/admin/order/restore?id=" + &order_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_optimistic_concurrency() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let product_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/product"),
+        &token,
+        json!({
+            "id": 0,
+            "name": "Locked item",
+            "price": { "cents": 1500, "currency": "PLN" },
+            "available": true,
+            "stock": 10
+        })
+    )
+    .text()
+    .await?;
+    let product_url = URL.to_string() + "/api/product?id=" + &product_id;
+    let admin_product_url = URL.to_string() + "/api/admin/product/?id=" + &product_id;
+
+    let response = rc.get(&product_url).send().await?;
+    let etag = response.headers()[ETAG].clone();
+    assert_eq!(etag, "\"1\"");
+    let response = rc
+        .get(&product_url)
+        .header(IF_NONE_MATCH, etag.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status(), 304);
+
+    let response = test_admin_endpoint!(
+        rc.patch(&admin_product_url),
+        &token,
+        json!({ "name": "Edited item" })
+    );
+    assert_eq!(response.status(), 428);
+
+    let response = test_admin_endpoint!(
+        rc.patch(&admin_product_url).header(IF_MATCH, etag.clone()),
+        &token,
+        json!({ "name": "Edited item" })
+    );
+    assert_eq!(response.status(), 200);
+    let new_etag = response.headers()[ETAG].clone();
+    assert_eq!(new_etag, "\"2\"");
+
+    // the second admin still holds the first version
+    let response = test_admin_endpoint!(
+        rc.put(&admin_product_url).header(IF_MATCH, etag.clone()),
+        &token,
+        json!({
+            "id": product_id.parse::<i32>()?,
+            "name": "Overwritten item",
+            "price": { "cents": 1500, "currency": "PLN" },
+            "available": true
+        })
+    );
+    assert_eq!(response.status(), 412);
+    let response = rc
+        .get(&product_url)
+        .header(IF_NONE_MATCH, etag)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()[ETAG], new_etag);
+    let product = response.json::<data::models::Product>().await?;
+    assert_eq!(product.name, "Edited item");
+
+    // ordering takes the product from stock, which changes what is sent for it
+    let response = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": 2,
+            "status": "New",
+            "created_at": "2023-06-04T12:00:00",
+            "products": HashMap::from([(&product_id, 1)])
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc
+        .get(&product_url)
+        .header(IF_NONE_MATCH, new_etag.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()[ETAG], new_etag);
+    let product = response.json::<data::models::Product>().await?;
+    assert_eq!(product.stock, Some(9));
+
+    let order_id = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": 2,
+            "status": "New",
+            "created_at": "2023-06-04T12:00:00",
+            "products": { "2": 1 }
+        }))
+        .send()
+        .await?
+        .text()
+        .await?;
+    let order_url = URL.to_string() + "/api/admin/order/?id=" + &order_id;
+    let etag = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .headers()[ETAG]
+        .clone();
+    let response = test_admin_endpoint!(
+        rc.patch(&order_url).header(IF_MATCH, etag.clone()),
+        &token,
+        json!({ "products": { "2": 2 } })
+    );
+    assert_eq!(response.status(), 200);
+    // the new lines and taxes are part of the version sent back
+    let patched_etag = response.headers()[ETAG].clone();
+    let response = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .header(IF_NONE_MATCH, patched_etag)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 304);
+    let response = test_admin_endpoint!(
+        rc.patch(&order_url).header(IF_MATCH, etag),
+        &token,
+        json!({ "products": { "2": 3 } })
+    );
+    assert_eq!(response.status(), 412);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_patch_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let product_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/product"),
+        &token,
+        json!({
+            "id": 0,
+            "name": "Patched item",
+            "price": { "cents": 700, "currency": "PLN" },
+            "available": true,
+            "tags": ["first"]
+        })
+    )
+    .text()
+    .await?;
+    let product_url = URL.to_string() + "/api/product?id=" + &product_id;
+    let admin_product_url = URL.to_string() + "/api/admin/product/?id=" + &product_id;
+    let patch = |content_type: &'static str, body: String| {
+        rc.patch(&admin_product_url)
+            .header(AUTHORIZATION, &token)
+            .header(IF_MATCH, "*")
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+    };
+
+    let response = patch(
+        "application/merge-patch+json",
+        json!({ "description": "Patched twice", "stock": 5, "category_id": null }).to_string(),
+    )
+    .await?;
+    assert_eq!(response.status(), 200);
+    let response = patch(
+        "application/json-patch+json",
+        json!([
+            { "op": "test", "path": "/tags/0", "value": "first" },
+            { "op": "add", "path": "/tags/-", "value": "second" },
+            { "op": "replace", "path": "/price/cents", "value": 750 }
+        ])
+        .to_string(),
+    )
+    .await?;
+    assert_eq!(response.status(), 200);
+
+    let product = rc
+        .get(&product_url)
+        .send()
+        .await?
+        .json::<data::models::Product>()
+        .await?;
+    assert_eq!(product.description, "Patched twice");
+    assert_eq!(product.stock, Some(5));
+    assert_eq!(product.tags, vec!["first", "second"]);
+    assert_eq!(product.price.cents, 750);
+
+    for (content_type, body, status) in [
+        ("text/plain", "{}".to_string(), 415),
+        ("application/merge-patch+json", "{".to_string(), 400),
+        ("application/json-patch+json", json!([{ "op": "nope" }]).to_string(), 400),
+        (
+            "application/json-patch+json",
+            json!([{ "op": "test", "path": "/name", "value": "Other item" }]).to_string(),
+            409,
+        ),
+        (
+            "application/json-patch+json",
+            json!([{ "op": "remove", "path": "/tags/5" }]).to_string(),
+            422,
+        ),
+        ("application/merge-patch+json", json!({ "price": "free" }).to_string(), 422),
+        ("application/merge-patch+json", json!({ "id": 1 }).to_string(), 422),
+        ("application/json", json!({ "name": null }).to_string(), 422),
+    ] {
+        let response = patch(content_type, body.clone()).await?;
+        assert_eq!(response.status(), status, "patching with {}", body);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+    let import = |resource: &str, content_type: &'static str, query: &str, body: String| {
+        rc.post(format!("{}/api/admin/{}/import{}", *URL, resource, query))
+            .header(AUTHORIZATION, &token)
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+    };
+    let export = |resource: &str, format: &str| {
+        rc.get(format!("{}/api/admin/{}/export?format={}", *URL, resource, format))
+            .header(AUTHORIZATION, &token)
+            .send()
+    };
+
+    // names of their own tell the rows of this run apart from those of earlier runs
+    let run = format!("run{}", chrono::Utc::now().timestamp_micros());
+    let csv = format!(
+        "id,name,price_cents,currency,available,tax_category,stock,description,tags,category_id\n\
+        ,Imported item A {run},1200,PLN,true,standard,7,,,\n\
+        ,Imported item B {run},1300,PLN,true,reduced,,Bulk loaded,bulk|import,3\n\
+        ,Imported item C {run},1400,XYZ,true,standard,,,,\n\
+        ,Imported item D {run},1500,PLN,true,standard,,,,999\n"
+    );
+    for dry_run in [true, false] {
+        let report = import("product", "text/csv", &format!("?dry_run={dry_run}"), csv.to_string())
+            .await?
+            .json::<data::models::ImportReport>()
+            .await?;
+        assert_eq!(report.dry_run, dry_run);
+        assert_eq!((report.total, report.created, report.updated), (4, 2, 0));
+        assert_eq!(
+            report.errors.iter().map(|error| error.row).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+
+        let response = export("product", "csv").await?;
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+        let exported = response.text().await?;
+        assert!(exported.starts_with("id,name,price_cents,currency"));
+        assert_eq!(exported.contains(&format!("Imported item A {run}")), !dry_run);
+    }
+
+    let records = export("product", "ndjson")
+        .await?
+        .text()
+        .await?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<data::models::ProductRecord>, _>>()?;
+    let mut record = records
+        .into_iter()
+        .find(|record| record.name == format!("Imported item B {run}"))
+        .ok_or(eyre!("Imported product is missing from the export"))?;
+    assert_eq!(record.tags, "bulk|import");
+    record.name = format!("Imported item B2 {run}");
+    let report = import(
+        "product",
+        "application/x-ndjson",
+        "",
+        serde_json::to_string(&record)? + "\n\n{\"name\": \"No price\"}\n",
+    )
+    .await?
+    .json::<data::models::ImportReport>()
+    .await?;
+    assert_eq!((report.total, report.created, report.updated), (2, 0, 1));
+    // the blank line counts, rows are numbered as editors number lines
+    assert_eq!(report.errors[0].row, 3);
+    let product = rc
+        .get(format!("{}/api/product?id={}", *URL, record.id.unwrap_or_default()))
+        .send()
+        .await?
+        .json::<data::models::Product>()
+        .await?;
+    assert_eq!(product.name, format!("Imported item B2 {run}"));
+    assert_eq!(product.tags, vec!["bulk", "import"]);
+
+    let report = import(
+        "customer",
+        "application/x-ndjson",
+        "",
+        json!({ "name": format!("Imported customer {run}"), "address": "Bulk street 1" })
+            .to_string()
+            + "\n"
+            + &json!({ "name": " ", "address": "Nowhere" }).to_string(),
+    )
+    .await?
+    .json::<data::models::ImportReport>()
+    .await?;
+    assert_eq!((report.total, report.created, report.updated), (2, 1, 0));
+    let exported = export("customer", "csv").await?.text().await?;
+    assert!(exported.contains(&format!("Imported customer {run},Bulk street 1,PL")));
+
+    let response = import("customer", "application/xml", "", "<customers/>".to_string()).await?;
+    assert_eq!(response.status(), 415);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_large_import() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    // more rows than fit in the bind parameters of a single insert. The ids are fixed, so later
+    // runs update the rows of the first one instead of adding more and more of them
+    let count = u16::MAX as usize / 4 + 100;
+    let first_id = 1_000_000;
+    let run = format!("run{}", chrono::Utc::now().timestamp_micros());
+    let csv = (0..count).fold("id,name,address\n".to_string(), |csv, i| {
+        csv + &format!("{},Chunked customer {i} {run},Chunk street {i}\n", first_id + i)
+    });
+    let report = rc
+        .post(URL.to_string() + "/api/admin/customer/import")
+        .header(AUTHORIZATION, &token)
+        .header(CONTENT_TYPE, "text/csv")
+        .body(csv)
+        .send()
+        .await?
+        .json::<data::models::ImportReport>()
+        .await?;
+    assert_eq!((report.total, report.created + report.updated), (count, count));
+    assert!(report.errors.is_empty());
+
+    let exported = rc
+        .get(URL.to_string() + "/api/admin/customer/export")
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?
+        .text()
+        .await?;
+    let last = format!("Chunked customer {} {run},Chunk street {}", count - 1, count - 1);
+    assert!(exported.contains(&last));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_order_totals() -> Result<()> {
+    let rc = Client::new();
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=1")
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    dbg!(&order);
+
+    let line_totals: i64 = order["lines"]
+        .as_array()
+        .ok_or(eyre!("Order has no lines"))?
+        .iter()
+        .map(|line| {
+            line["unit_price"]["cents"].as_i64().unwrap_or_default()
+                * line["quantity"].as_i64().unwrap_or_default()
+        })
+        .sum();
+    let taxes: i64 = order["taxes"]
+        .as_array()
+        .ok_or(eyre!("Order has no taxes"))?
+        .iter()
+        .map(|tax_line| tax_line["tax"]["cents"].as_i64().unwrap_or_default())
+        .sum();
+    assert_eq!(order["subtotal"]["cents"].as_i64(), Some(line_totals));
+    assert_eq!(order["tax"]["cents"].as_i64(), Some(taxes));
+    assert_eq!(order["total"]["cents"].as_i64(), Some(line_totals + taxes));
+
+    let token = authorize(&rc, "example_admin").await?;
+    let mut customer_ids = HashMap::new();
+    // neither customer has a shipping address, so they are taxed by their region
+    for region in ["PL", "XX"] {
+        let customer_id = test_admin_endpoint!(
+            rc.post(URL.to_string() + "/api/admin/customer"),
+            &token,
+            json!({
+                "id": 0,
+                "name": format!("Taxed customer {region}"),
+                "address": "Some address",
+                "region": region
+            })
+        )
+        .text()
+        .await?;
+        customer_ids.insert(region, customer_id.parse::<i32>()?);
+    }
+    let mut product_ids = HashMap::new();
+    for (tax_category, cents) in [("standard", 1000), ("reduced", 1234)] {
+        let product_id = test_admin_endpoint!(
+            rc.post(URL.to_string() + "/api/admin/product"),
+            &token,
+            json!({
+                "id": 0,
+                "name": format!("Taxed {tax_category} item"),
+                "price": { "cents": cents, "currency": "PLN" },
+                "available": true,
+                "tax_category": tax_category,
+                "category_id": 3
+            })
+        )
+        .text()
+        .await?;
+        product_ids.insert(tax_category, product_id);
+    }
+    let products =
+        json!(HashMap::from([(&product_ids["standard"], 1), (&product_ids["reduced"], 1)]));
+
+    // 23% of 1000 and 8% of 1234 rounded half away from zero
+    let order = create_order_with_taxes(&rc, customer_ids["PL"], &products, None, None).await?;
+    assert_eq!(
+        order_taxes(&order),
+        vec![("reduced".to_string(), 99), ("standard".to_string(), 230)]
+    );
+    assert_eq!(order["subtotal"]["cents"].as_i64(), Some(2234));
+    assert_eq!(order["tax"]["cents"].as_i64(), Some(329));
+    assert_eq!(order["total"]["cents"].as_i64(), Some(2563));
+
+    // 19% of 1000 and 7% of 1234, the address the order ships to wins over the customer's region
+    let customer_token = authorize(&rc, "example_customer").await?;
+    let customer_id = test_get_request_auth_endpoint!(rc, "/api/cart", &customer_token)
+        .json::<data::models::Cart>()
+        .await?
+        .customer_id;
+    let address_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/customer/address"),
+        &customer_token,
+        json!({
+            "kind": "shipping",
+            "recipient": "Hans Schmidt",
+            "line1": "Hauptstraße 1",
+            "city": "Berlin",
+            "postal_code": "10115",
+            "country": "DE",
+            "is_default": false
+        })
+    )
+    .text()
+    .await?;
+    let order = create_order_with_taxes(
+        &rc,
+        customer_id,
+        &products,
+        None,
+        Some(address_id.parse::<i32>()?),
+    )
+    .await?;
+    assert_eq!(
+        order_taxes(&order),
+        vec![("reduced".to_string(), 86), ("standard".to_string(), 190)]
+    );
+    assert_eq!(order["tax"]["cents"].as_i64(), Some(276));
+    assert_eq!(order["total"]["cents"].as_i64(), Some(2510));
+
+    // a region without rates does not fail the order, it is charged 0% and shows as such
+    let order = create_order_with_taxes(&rc, customer_ids["XX"], &products, None, None).await?;
+    assert_eq!(
+        order_taxes(&order),
+        vec![("reduced".to_string(), 0), ("standard".to_string(), 0)]
+    );
+    assert!(order["taxes"]
+        .as_array()
+        .ok_or(eyre!("Order has no taxes"))?
+        .iter()
+        .all(|tax_line| tax_line["region"] == "XX" && tax_line["rate_basis_points"] == 0));
+    assert_eq!(order["total"]["cents"].as_i64(), Some(2234));
+
+    let discount_code_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/discount"),
+        &token,
+        json!({
+            "id": 0,
+            "code": "TAX-".to_string() + &chrono::Utc::now().timestamp_micros().to_string(),
+            "kind": "fixed",
+            "percent": null,
+            "amount": { "cents": 500, "currency": "PLN" },
+            "valid_from": null,
+            "valid_until": null,
+            "max_uses": 1,
+            "max_uses_per_customer": null,
+            "min_order_value": null,
+            "product_ids": []
+        })
+    )
+    .text()
+    .await?;
+    let discount_code = rc
+        .get(URL.to_string() + "/api/admin/discount?id=" + &discount_code_id)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?
+        .json::<data::models::DiscountCode>()
+        .await?;
+
+    // the reduced line takes floor(500 * 1234 / 2234) = 276 of the discount, the standard line
+    // the remaining 224, leaving 8% of 958 and 23% of 776 to tax
+    let order =
+        create_order_with_taxes(&rc, customer_ids["PL"], &products, Some(&discount_code.code), None)
+            .await?;
+    assert_eq!(order["discount"]["cents"].as_i64(), Some(500));
+    assert_eq!(
+        order_taxes(&order),
+        vec![("reduced".to_string(), 77), ("standard".to_string(), 178)]
+    );
+    let taxable_amounts: Vec<i64> = order["taxes"]
+        .as_array()
+        .ok_or(eyre!("Order has no taxes"))?
+        .iter()
+        .map(|tax_line| tax_line["taxable_amount"]["cents"].as_i64().unwrap_or_default())
+        .collect();
+    assert_eq!(taxable_amounts, vec![958, 776]);
+    assert_eq!(order["tax"]["cents"].as_i64(), Some(255));
+    assert_eq!(order["total"]["cents"].as_i64(), Some(1989));
+
+    Ok(())
+}
+
+async fn create_order_with_taxes(
+    rc: &Client,
+    customer_id: i32,
+    products: &serde_json::Value,
+    discount_code: Option<&str>,
+    shipping_address_id: Option<i32>,
+) -> Result<serde_json::Value> {
+    let response = rc
+        .post(URL.to_string() + "/api/order")
+        .json(&json!({
+            "id": 0,
+            "customer_id": customer_id,
+            "status": "New",
+            "created_at": "2023-04-25T08:41:23.104715",
+            "products": products,
+            "discount_code": discount_code,
+            "shipping_address_id": shipping_address_id
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let order_id = response.text().await?;
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    dbg!(&order);
+    Ok(order)
+}
+
+/// Tax per category of an order, in the order the categories are listed.
+fn order_taxes(order: &serde_json::Value) -> Vec<(String, i64)> {
+    order["taxes"]
+        .as_array()
+        .map(|taxes| {
+            taxes
+                .iter()
+                .map(|tax_line| {
+                    (
+                        tax_line["tax_category"].as_str().unwrap_or_default().to_string(),
+                        tax_line["tax"]["cents"].as_i64().unwrap_or_default(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_tax_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_admin").await?;
+
+    let categories = test_get_request_auth_endpoint!(rc, "/api/v1/admin/tax/categories", &token)
+        .json::<Vec<data::models::TaxCategory>>()
+        .await?;
+    assert!(categories.iter().any(|category| category.code == "reduced"));
+
+    // regions are unique per category, so every run taxes a region of its own
+    let region = format!("T{}", chrono::Utc::now().timestamp_micros());
+    let endpoint = "/api/v1/admin/tax/rates";
+    println!("\n========\nTesting: POST {endpoint}");
+    let tax_rate_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &token,
+        json!({
+            "id": 0,
+            "region": region,
+            "tax_category": "reduced",
+            "rate_basis_points": 500
+        })
+    )
+    .text()
+    .await?;
+    let endpoint = format!("/api/v1/admin/tax/rates/{tax_rate_id}");
+    let tax_rate = test_get_request_auth_endpoint!(rc, &endpoint, &token)
+        .json::<data::models::TaxRate>()
+        .await?;
+    assert_eq!(tax_rate.region, region);
+    assert_eq!(tax_rate.rate_basis_points, 500);
+
+    let tax_rates = test_get_request_auth_endpoint!(rc, "/api/v1/admin/tax/rates", &token)
+        .json::<Vec<data::models::TaxRate>>()
+        .await?;
+    assert!(tax_rates.iter().any(|tax_rate| tax_rate.region == region));
+
+    println!("\n========\nTesting: PUT {endpoint}");
+    let updated_tax_rate = json!({
+        "id": tax_rate.id,
+        "region": region,
+        "tax_category": "reduced",
+        "rate_basis_points": 550
+    });
+    let response =
+        test_admin_endpoint!(rc.put(URL.to_string() + &endpoint), &token, updated_tax_rate);
+    assert_eq!(response.status(), 200);
+    let tax_rate = test_get_request_auth_endpoint!(rc, &endpoint, &token)
+        .json::<data::models::TaxRate>()
+        .await?;
+    assert_eq!(tax_rate.rate_basis_points, 550);
+
+    println!("\n========\nTesting: DELETE {endpoint}");
+    let response = rc
+        .delete(URL.to_string() + &endpoint)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc.get(URL.to_string() + &endpoint).header(AUTHORIZATION, &token).send().await?;
+    assert_eq!(response.status(), 404);
+
+    // the rate is gone, so neither replacing nor deleting it again finds anything
+    let response = rc
+        .put(URL.to_string() + &endpoint)
+        .header(AUTHORIZATION, &token)
+        .json(&updated_tax_rate)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 404);
+    let response = rc
+        .delete(URL.to_string() + &endpoint)
+        .header(AUTHORIZATION, &token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_customer_routes() -> Result<()> {
+    let rc = Client::new();
+
+    let mut credentials = HashMap::new();
+    credentials.insert("name", "example_customer");
+    credentials.insert("password", "example_password");
+    let token = "Bearer ".to_string()
+        + &rc
+            .post(URL.to_string() + "/api/user/authorize")
+            .json(&json!(credentials))
+            .send()
+            .await?
+            .json::<AuthResponse>()
+            .await?
+            .token;
+
+    let response = test_get_request_auth_endpoint!(rc, "/api/v1/customers/1", &token);
+    dbg!(response.json::<Customer>().await?);
+
+    let response = test_get_request_auth_endpoint!(rc, "/api/customer/all", &token);
+    dbg!(response.json::<Vec<Customer>>().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cart_routes() -> Result<()> {
+    let rc = Client::new();
+
+    let mut credentials = HashMap::new();
+    credentials.insert("name", "cart_customer");
+    credentials.insert("password", "example_password");
+    let response = rc
+        .post(URL.to_string() + "/api/user/register")
+        .json(&json!(credentials))
+        .send()
+        .await?;
+    let token = match response.status().is_success() {
+        true => response.json::<AuthResponse>().await?.token,
+        false => {
+            rc.post(URL.to_string() + "/api/user/authorize")
+                .json(&json!(credentials))
+                .send()
+                .await?
+                .json::<AuthResponse>()
+                .await?
+                .token
+        }
+    };
+    let token = "Bearer ".to_string() + &token;
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart"),
+        &token,
+        json!({ "product_id": 1, "quantity": 2 })
+    );
+    assert_eq!(response.status(), 200);
+    let response = test_admin_endpoint!(
+        rc.put(URL.to_string() + "/api/v1/cart/items/1"),
+        &token,
+        json!({ "quantity": 3 })
+    );
+    assert_eq!(response.status(), 200);
+
+    let cart = test_get_request_auth_endpoint!(rc, "/api/cart", &token)
+        .json::<data::models::Cart>()
+        .await?;
+    dbg!(&cart);
+    assert!(cart.lines.iter().any(|line| line.product_id == 1 && line.quantity == 3));
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart/checkout"),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
+    let order_id = response.text().await?;
+    dbg!(&order_id);
+
+    let cart = test_get_request_auth_endpoint!(rc, "/api/cart", &token)
+        .json::<data::models::Cart>()
+        .await?;
+    assert!(cart.lines.is_empty());
+
+    let response = test_admin_endpoint!(
+        rc.put(URL.to_string() + "/api/cart?id=1"),
+        &token,
+        json!({ "quantity": 3 })
+    );
+    assert_eq!(response.status(), 404);
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart/checkout"),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 422);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_payment_routes() -> Result<()> {
+    let rc = Client::new();
+
+    let mut credentials = HashMap::new();
+    credentials.insert("name", "example_customer");
+    credentials.insert("password", "example_password");
+    let token = "Bearer ".to_string()
+        + &rc
+            .post(URL.to_string() + "/api/user/authorize")
+            .json(&json!(credentials))
+            .send()
+            .await?
+            .json::<AuthResponse>()
+            .await?
+            .token;
+
+    test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart"),
+        &token,
+        json!({ "product_id": 2, "quantity": 1 })
+    );
+    let order_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart/checkout"),
+        &token,
+        json!({})
+    )
+    .text()
+    .await?;
+
+    let payment_intent = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/payment"),
+        &token,
+        json!({ "order_id": order_id.parse::<i32>()? })
+    )
+    .json::<data::models::PaymentIntent>()
+    .await?;
+    dbg!(&payment_intent);
+
+    let provider = FakePaymentProvider::new(std::env::var("PAYMENT_WEBHOOK_SECRET")?.as_bytes());
+    let event = json!({
+        "provider_reference": payment_intent.provider_reference,
+        "status": "succeeded"
+    })
+    .to_string();
+
+    let response = rc
+        .post(URL.to_string() + "/api/payment/webhook")
+        .header(PAYMENT_SIGNATURE_HEADER, "not a signature")
+        .body(event.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status(), 401);
+
+    let response = rc
+        .post(URL.to_string() + "/api/payment/webhook")
+        .header(PAYMENT_SIGNATURE_HEADER, provider.sign(event.as_bytes()))
+        .body(event)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<data::models::Order>()
+        .await?;
+    assert_eq!(order.status, data::models::order_status::PAID);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_customer").await?;
+    let admin_token = authorize(&rc, "example_admin").await?;
+
+    let customer_id = test_get_request_auth_endpoint!(rc, "/api/cart", &token)
+        .json::<data::models::Cart>()
+        .await?
+        .customer_id;
+    let order_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/order"),
+        &admin_token,
+        json!({
             "id": 0,
-            "customer_id": 1,
-            "status": "Done",
-            "created_at": "2023-04-25T08:41:23.104715",
-            "products": {
-                "1": 5,
-                "2": 15
-            }
-        }
+            "customer_id": customer_id,
+            "status": "New",
+            "created_at": "2023-05-24T12:00:00",
+            "products": { "1": 3 }
+        })
+    )
+    .text()
+    .await?;
+
+    // nothing was paid yet
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/refund?id=" + &order_id),
+        &admin_token,
+        json!({})
     );
-    let response = rc
-        .post(URL.to_string() + "/api/order")
-        .json(&order)
+    assert_eq!(response.status(), 422);
+
+    let payment_intent = pay_order(&rc, &token, &order_id).await?;
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/refund?id=" + &order_id),
+        &admin_token,
+        json!({ "products": { "1": 4 } })
+    );
+    assert_eq!(response.status(), 422);
+
+    let refund = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/refund?id=" + &order_id),
+        &admin_token,
+        json!({ "products": { "1": 1 }, "reason": "Damaged" })
+    )
+    .json::<data::models::Refund>()
+    .await?;
+    dbg!(&refund);
+    assert_eq!(refund.amount.cents, payment_intent.amount.cents / 3);
+
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
         .send()
+        .await?
+        .json::<data::models::Order>()
         .await?;
-    dbg!(response.text().await?);
+    assert_eq!(order.status, data::models::order_status::PARTIALLY_REFUNDED);
+
+    // changing the lines would lose track of what was refunded
+    let response = test_admin_endpoint!(
+        rc.put(URL.to_string() + "/api/v1/admin/orders/" + &order_id).header(IF_MATCH, "*"),
+        &admin_token,
+        json!({
+            "id": order.id,
+            "customer_id": customer_id,
+            "status": "New",
+            "created_at": "2023-05-24T12:00:00",
+            "products": { "1": 3 }
+        })
+    );
+    assert_eq!(response.status(), 409);
+
+    // only orders that were not refunded yet can be cancelled
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/order/cancel?id=" + &order_id),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 409);
+
+    let refund = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/refund?id=" + &order_id),
+        &admin_token,
+        json!({})
+    )
+    .json::<data::models::Refund>()
+    .await?;
+    assert_eq!(refund.lines[0].quantity, 2);
+
+    let refunds = test_get_request_auth_endpoint!(
+        rc,
+        &("/api/admin/order/refunds?id=".to_string() + &order_id),
+        &admin_token
+    )
+    .json::<Vec<data::models::Refund>>()
+    .await?;
+    let refunded: i64 = refunds.iter().map(|refund| refund.amount.cents).sum();
+    assert_eq!(refunded, payment_intent.amount.cents);
+
     Ok(())
 }
 
 #[tokio::test]
-async fn test_customer_routes() -> Result<()> {
+async fn test_cancel_order() -> Result<()> {
     let rc = Client::new();
 
     let mut credentials = HashMap::new();
@@ -106,11 +2043,682 @@ async fn test_customer_routes() -> Result<()> {
             .await?
             .token;
 
-    let response = test_get_request_auth_endpoint!(rc, "/api/customer/1", &token);
-    dbg!(response.json::<Customer>().await?);
+    test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart"),
+        &token,
+        json!({ "product_id": 2, "quantity": 1 })
+    );
+    let order_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/cart/checkout"),
+        &token,
+        json!({})
+    )
+    .text()
+    .await?;
 
-    let response = test_get_request_auth_endpoint!(rc, "/api/customer/all", &token);
-    dbg!(response.json::<Vec<Customer>>().await?);
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/order/cancel?id=" + &order_id),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
+
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<data::models::Order>()
+        .await?;
+    assert_eq!(order.status, data::models::order_status::CANCELLED);
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/order/cancel?id=" + &order_id),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 409);
+
+    // reopening the order would leave its products in stock
+    let admin_token = authorize(&rc, "example_admin").await?;
+    let response = test_admin_endpoint!(
+        rc.put(URL.to_string() + "/api/v1/admin/orders/" + &order_id).header(IF_MATCH, "*"),
+        &admin_token,
+        json!({
+            "id": order.id,
+            "customer_id": order.customer_id,
+            "status": "New",
+            "created_at": "2023-05-24T12:00:00",
+            "products": { "2": 1 }
+        })
+    );
+    assert_eq!(response.status(), 409);
+
+    Ok(())
+}
+
+/// Reads Server-Sent Events off a response until one is about the given
+/// order, and returns every event read.
+async fn read_order_events(
+    response: &mut reqwest::Response,
+    buffer: &mut String,
+    order_id: i32,
+) -> Result<Vec<(String, data::models::OrderEvent)>> {
+    let mut events = Vec::new();
+    loop {
+        while let Some(end) = buffer.find("\n\n") {
+            let message: String = buffer.drain(..end + 2).collect();
+            let mut name = String::new();
+            let mut data = String::new();
+            for line in message.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    name = value.trim_start().to_string();
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push_str(value.trim_start());
+                }
+            }
+            if data.is_empty() {
+                continue;
+            }
+            let event: data::models::OrderEvent = serde_json::from_str(&data)?;
+            let done = event.order_id == order_id;
+            events.push((name, event));
+            if done {
+                return Ok(events);
+            }
+        }
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk())
+            .await??
+            .ok_or(eyre!("Stream ended before order {} showed up", order_id))?;
+        buffer.push_str(std::str::from_utf8(&chunk)?);
+    }
+}
+
+#[tokio::test]
+async fn test_order_events() -> Result<()> {
+    use data::models::OrderEventKind;
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let rc = Client::new();
+    let customer_token = authorize(&rc, "example_customer").await?;
+    let admin_token = authorize(&rc, "example_admin").await?;
+
+    let response = rc.get(URL.to_string() + "/api/v1/orders/stream").send().await?;
+    assert_eq!(response.status(), 400);
+    let mut customer_stream =
+        test_get_request_auth_endpoint!(rc, "/api/v1/orders/stream", &customer_token);
+    assert_eq!(customer_stream.status(), 200);
+    assert_eq!(customer_stream.headers()[CONTENT_TYPE], "text/event-stream");
+    let mut admin_stream = test_get_request_auth_endpoint!(rc, "/api/order/stream", &admin_token);
+    assert_eq!(admin_stream.status(), 200);
+    let mut request = (URL.replace("http://", "ws://") + "/api/v1/orders/ws").into_client_request()?;
+    request
+        .headers_mut()
+        .insert(AUTHORIZATION, admin_token.parse()?);
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await?;
+
+    test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/cart/items"),
+        &customer_token,
+        json!({ "product_id": 2, "quantity": 1 })
+    );
+    let own_order_id: i32 = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/cart/checkout"),
+        &customer_token,
+        json!({})
+    )
+    .text()
+    .await?
+    .parse()?;
+    let own_customer_id = rc
+        .get(format!("{}/api/v1/orders/{}", *URL, own_order_id))
+        .send()
+        .await?
+        .json::<data::models::Order>()
+        .await?
+        .customer_id;
+    let other_customer_id = if own_customer_id == 1 { 2 } else { 1 };
+    let response = graphql(
+        &rc,
+        None,
+        &format!(
+            "mutation {{ createOrder(input: {{ customerId: {other_customer_id}, \
+            items: [{{ productId: 2, quantity: 1 }}] }}) }}"
+        ),
+    )
+    .await?;
+    let other_order_id = response["data"]["createOrder"]
+        .as_i64()
+        .ok_or(eyre!("No order created: {}", response))? as i32;
+    let response = test_admin_endpoint!(
+        rc.post(format!("{}/api/v1/orders/{}/cancel", *URL, own_order_id)),
+        &customer_token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
+
+    let mut buffer = String::new();
+    let events = read_order_events(&mut customer_stream, &mut buffer, own_order_id).await?;
+    let (name, created) = events.last().ok_or(eyre!("No events"))?;
+    assert_eq!(name, "order_created");
+    assert_eq!(created.event, OrderEventKind::OrderCreated);
+    assert_eq!(created.customer_id, own_customer_id);
+    let events = read_order_events(&mut customer_stream, &mut buffer, own_order_id).await?;
+    let (name, cancelled) = events.last().ok_or(eyre!("No events"))?;
+    assert_eq!(name, "order_status_changed");
+    assert_eq!(cancelled.status, data::models::order_status::CANCELLED);
+    assert_eq!(
+        cancelled.previous_status.as_deref(),
+        Some(data::models::order_status::NEW)
+    );
+    // the other order was created in between, but is none of the customer's business
+    assert!(events.iter().all(|(_, event)| event.customer_id == own_customer_id));
+
+    let mut buffer = String::new();
+    let events = read_order_events(&mut admin_stream, &mut buffer, other_order_id).await?;
+    assert_eq!(events.last().ok_or(eyre!("No events"))?.1.customer_id, other_customer_id);
+
+    loop {
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+            .await?
+            .ok_or(eyre!("Socket closed"))??;
+        let Message::Text(text) = message else { continue };
+        let event: data::models::OrderEvent = serde_json::from_str(&text)?;
+        if event.order_id == other_order_id {
+            assert_eq!(event.event, OrderEventKind::OrderCreated);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Request received by a webhook receiver: its headers and body.
+type ReceivedWebhook = (axum::http::HeaderMap, axum::body::Bytes);
+
+/// Starts a receiver of webhooks on a free port. `/hook` passes every request
+/// on to the returned channel, `/fail` always fails.
+async fn start_webhook_receiver() -> Result<(
+    std::net::SocketAddr,
+    tokio::sync::mpsc::UnboundedReceiver<ReceivedWebhook>,
+)> {
+    use axum::{extract::State, http::StatusCode, routing::post, Router};
+
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<ReceivedWebhook>();
+    let router = Router::new()
+        .route(
+            "/hook",
+            post(
+                |State(sender): State<tokio::sync::mpsc::UnboundedSender<ReceivedWebhook>>,
+                 headers: axum::http::HeaderMap,
+                 body: axum::body::Bytes| async move {
+                    _ = sender.send((headers, body));
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .route("/fail", post(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+        .with_state(sender);
+    let server = axum::Server::bind(&"127.0.0.1:0".parse()?).serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    Ok((addr, receiver))
+}
+
+/// Lists the deliveries of a subscription until one of them was attempted.
+async fn wait_for_delivery(
+    rc: &Client,
+    token: &str,
+    subscription_id: i32,
+) -> Result<data::models::WebhookDelivery> {
+    wait_for_delivery_matching(rc, token, subscription_id, |delivery| delivery.attempts > 0).await
+}
+
+/// Lists the deliveries of a subscription until one of them matches. The
+/// worker records how an attempt went only after the receiver answered, so
+/// the log lags behind what receivers see.
+async fn wait_for_delivery_matching(
+    rc: &Client,
+    token: &str,
+    subscription_id: i32,
+    matches: impl Fn(&data::models::WebhookDelivery) -> bool,
+) -> Result<data::models::WebhookDelivery> {
+    for _ in 0..50 {
+        let deliveries = test_get_request_auth_endpoint!(
+            rc,
+            &format!("/api/v1/admin/webhooks/{subscription_id}/deliveries"),
+            token
+        )
+        .json::<Vec<data::models::WebhookDelivery>>()
+        .await?;
+        if let Some(delivery) = deliveries.into_iter().find(&matches) {
+            return Ok(delivery);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    Err(eyre!("Nothing was delivered to subscription {}", subscription_id))
+}
+
+#[tokio::test]
+async fn test_webhooks() -> Result<()> {
+    use data::models::{
+        WebhookDeliveryStatus, WebhookEventType, WebhookPayload, WebhookSubscription,
+    };
+    use data::services::{WebhookService, WEBHOOK_EVENT_HEADER, WEBHOOK_SIGNATURE_HEADER};
+
+    let rc = Client::new();
+    let customer_token = authorize(&rc, "example_customer").await?;
+    let admin_token = authorize(&rc, "example_admin").await?;
+    let (addr, mut received) = start_webhook_receiver().await?;
+
+    let endpoint = "/api/v1/admin/webhooks";
+    let subscription = json!({
+        "url": format!("http://{addr}/hook"),
+        "event_types": ["product.created"],
+        "secret": "receiver secret"
+    });
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &customer_token,
+        subscription
+    );
+    assert_eq!(response.status(), 403);
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &admin_token,
+        json!({ "url": "ftp://example.com", "event_types": ["product.created"] })
+    );
+    assert_eq!(response.status(), 422);
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &admin_token,
+        json!({ "url": format!("http://{addr}/hook"), "event_types": [] })
+    );
+    assert_eq!(response.status(), 422);
+
+    let subscription = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &admin_token,
+        subscription
+    )
+    .json::<WebhookSubscription>()
+    .await?;
+    assert_eq!(subscription.event_types, vec![WebhookEventType::ProductCreated]);
+    assert!(subscription.active);
+    let failing = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &admin_token,
+        json!({ "url": format!("http://{addr}/fail"), "event_types": ["product.created"] })
+    )
+    .json::<WebhookSubscription>()
+    .await?;
+    // generated secrets are 32 random bytes
+    assert_eq!(failing.secret.len(), 64);
+
+    let product_id: i32 = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/admin/products"),
+        &admin_token,
+        json!({
+            "id": 0,
+            "name": "Webhook receiver gadget",
+            "price": { "cents": 1299, "currency": "PLN" },
+            "available": true,
+            "category_id": 3
+        })
+    )
+    .text()
+    .await?
+    .parse()?;
+
+    let (headers, body) = loop {
+        let (headers, body) =
+            tokio::time::timeout(std::time::Duration::from_secs(10), received.recv())
+                .await?
+                .ok_or(eyre!("Receiver stopped"))?;
+        let payload: WebhookPayload = serde_json::from_slice(&body)?;
+        // products created by other tests are delivered too
+        if payload.data["id"] == product_id {
+            break (headers, body);
+        }
+    };
+    assert_eq!(
+        headers[WEBHOOK_SIGNATURE_HEADER],
+        WebhookService::sign("receiver secret", &body)
+    );
+    assert_eq!(headers[WEBHOOK_EVENT_HEADER], "product.created");
+    let payload: WebhookPayload = serde_json::from_slice(&body)?;
+    assert_eq!(payload.event_type, WebhookEventType::ProductCreated);
+    assert_eq!(payload.data["name"], "Webhook receiver gadget");
+    assert!(payload.data.get("search_vector").is_none());
+
+    let delivery = wait_for_delivery_matching(&rc, &admin_token, subscription.id, |delivery| {
+        delivery.event_id == payload.id && delivery.status == WebhookDeliveryStatus::Delivered
+    })
+    .await?;
+    let deliveries = test_get_request_auth_endpoint!(
+        rc,
+        &format!("{endpoint}/{}/deliveries?status=delivered", subscription.id),
+        &admin_token
+    );
+    assert_eq!(deliveries.status(), 200);
+    let deliveries = deliveries
+        .json::<Vec<data::models::WebhookDelivery>>()
+        .await?;
+    assert!(deliveries
+        .iter()
+        .all(|delivery| delivery.status == WebhookDeliveryStatus::Delivered));
+    assert!(deliveries.iter().any(|listed| listed.id == delivery.id));
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.response_status, Some(204));
+
+    let failed = wait_for_delivery(&rc, &admin_token, failing.id).await?;
+    assert_eq!(failed.status, WebhookDeliveryStatus::Pending);
+    assert_eq!(failed.response_status, Some(503));
+    assert!(failed.last_error.is_some());
+    assert!(failed.next_attempt_at > failed.last_attempt_at.unwrap_or_default());
+    let response = test_admin_endpoint!(
+        rc.post(format!("{}{endpoint}/deliveries/{}/retry", *URL, failed.id)),
+        &admin_token,
+        json!({})
+    );
+    assert_eq!(response.status(), 409);
+
+    // updates leaving the product as subscribers see it, like the first one that only bumps the
+    // version, are not sent
+    let updates = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        &admin_token,
+        json!({ "url": format!("http://{addr}/hook"), "event_types": ["product.updated"] })
+    )
+    .json::<WebhookSubscription>()
+    .await?;
+    let product_url = format!("{}/api/v1/admin/products/{}", *URL, product_id);
+    for (version, name) in [(1, "Webhook receiver gadget"), (2, "Renamed webhook receiver gadget")] {
+        let response = test_admin_endpoint!(
+            rc.patch(&product_url).header(IF_MATCH, format!("\"{version}\"")),
+            &admin_token,
+            json!({ "name": name })
+        );
+        assert_eq!(response.status(), 200);
+    }
+    let payload = loop {
+        let (_, body) = tokio::time::timeout(std::time::Duration::from_secs(10), received.recv())
+            .await?
+            .ok_or(eyre!("Receiver stopped"))?;
+        let payload: WebhookPayload = serde_json::from_slice(&body)?;
+        if payload.event_type == WebhookEventType::ProductUpdated && payload.data["id"] == product_id
+        {
+            break payload;
+        }
+    };
+    assert_eq!(payload.data["name"], "Renamed webhook receiver gadget");
+
+    let response = test_admin_endpoint!(
+        rc.put(format!("{}{endpoint}/{}", *URL, failing.id)),
+        &admin_token,
+        json!({
+            "url": format!("http://{addr}/fail"),
+            "event_types": ["product.created", "product.deleted"],
+            "active": false
+        })
+    );
+    assert_eq!(response.status(), 200);
+    let updated = response.json::<WebhookSubscription>().await?;
+    assert!(!updated.active);
+    assert_eq!(updated.secret, failing.secret);
+
+    for subscription_id in [subscription.id, failing.id, updates.id] {
+        let endpoint = format!("{}{endpoint}/{subscription_id}", *URL);
+        let response = rc
+            .delete(&endpoint)
+            .header(AUTHORIZATION, &admin_token)
+            .send()
+            .await?;
+        assert_eq!(response.status(), 200);
+        let response = rc.get(&endpoint).header(AUTHORIZATION, &admin_token).send().await?;
+        assert_eq!(response.status(), 404);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_jobs() -> Result<()> {
+    use data::models::{JobStatus, QueuedJob, WebhookSubscription};
+
+    let rc = Client::new();
+    let customer_token = authorize(&rc, "example_customer").await?;
+    let admin_token = authorize(&rc, "example_admin").await?;
+    let (addr, _received) = start_webhook_receiver().await?;
+
+    let response = test_get_request_auth_endpoint!(rc, "/api/v1/admin/jobs", &customer_token);
+    assert_eq!(response.status(), 403);
+    let response =
+        test_get_request_auth_endpoint!(rc, "/api/v1/admin/jobs?sort=payload", &admin_token);
+    assert_eq!(response.status(), 400);
+
+    let subscription = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/admin/webhooks"),
+        &admin_token,
+        json!({ "url": format!("http://{addr}/fail"), "event_types": ["product.updated"] })
+    )
+    .json::<WebhookSubscription>()
+    .await?;
+    let product = json!({
+        "id": 0,
+        "name": "Queued job tablecloth",
+        "price": { "cents": 4999, "currency": "PLN" },
+        "available": true,
+        "category_id": 3
+    });
+    let product_id: i32 = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/admin/products"),
+        &admin_token,
+        product
+    )
+    .text()
+    .await?
+    .parse()?;
+    let mut update = product.clone();
+    update["id"] = json!(product_id);
+    update["available"] = json!(false);
+    let response = test_admin_endpoint!(
+        rc.put(format!("{}/api/v1/admin/products/{product_id}", *URL)).header(IF_MATCH, "*"),
+        &admin_token,
+        update
+    );
+    assert_eq!(response.status(), 200);
+
+    // events are dispatched and delivered by jobs, failed deliveries are
+    // queued again for later
+    let delivery = wait_for_delivery(&rc, &admin_token, subscription.id).await?;
+    let response = test_get_request_auth_endpoint!(
+        rc,
+        "/api/v1/admin/jobs?kind=deliver_webhook&status=queued&sort=id:desc&limit=100",
+        &admin_token
+    );
+    assert_eq!(response.status(), 200);
+    let jobs = response.json::<Vec<QueuedJob>>().await?;
+    let job = jobs
+        .iter()
+        .find(|job| job.payload["delivery_id"] == delivery.id)
+        .ok_or(eyre!("No queued job delivers {}", delivery.id))?;
+    assert_eq!(job.attempts, 1);
+    assert!(job.last_error.is_some());
+    assert!(job.finished_at.is_none());
+
+    let endpoint = format!("/api/v1/admin/jobs/{}", job.id);
+    let fetched = test_get_request_auth_endpoint!(rc, &endpoint, &admin_token)
+        .json::<QueuedJob>()
+        .await?;
+    assert_eq!(fetched.status, JobStatus::Queued);
+    assert_eq!(fetched.kind, "deliver_webhook");
+    let response = test_admin_endpoint!(
+        rc.post(format!("{}{endpoint}/retry", *URL)),
+        &admin_token,
+        json!({})
+    );
+    assert_eq!(response.status(), 409);
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/v1/admin/jobs/999999999/retry"),
+        &admin_token,
+        json!({})
+    );
+    assert_eq!(response.status(), 404);
+
+    let response = test_get_request_auth_endpoint!(
+        rc,
+        "/api/v1/admin/jobs?kind=dispatch_webhook_event&status=succeeded&limit=1",
+        &admin_token
+    );
+    let total: i64 = response.headers()["x-total-count"].to_str()?.parse()?;
+    assert!(total > 0);
+
+    let response = rc
+        .delete(format!("{}/api/v1/admin/webhooks/{}", *URL, subscription.id))
+        .header(AUTHORIZATION, &admin_token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shipping_routes() -> Result<()> {
+    let rc = Client::new();
+    let token = authorize(&rc, "example_customer").await?;
+    let admin_token = authorize(&rc, "example_admin").await?;
+
+    let address_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/customer/address"),
+        &token,
+        json!({
+            "kind": "shipping",
+            "recipient": "Jan Kowalski",
+            "line1": "ul. Długa 5",
+            "city": "Gdańsk",
+            "postal_code": "80-827",
+            "country": "PL",
+            "is_default": true
+        })
+    )
+    .text()
+    .await?;
+    let addresses = test_get_request_auth_endpoint!(rc, "/api/customer/address", &token)
+        .json::<Vec<data::models::Address>>()
+        .await?;
+    let address = addresses
+        .iter()
+        .find(|address| address.id.to_string() == address_id)
+        .ok_or(eyre!("Created address is not listed"))?;
+    assert!(address.is_default);
+
+    let customer_id = test_get_request_auth_endpoint!(rc, "/api/cart", &token)
+        .json::<data::models::Cart>()
+        .await?
+        .customer_id;
+    let order_id = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/order"),
+        &admin_token,
+        json!({
+            "id": 0,
+            "customer_id": customer_id,
+            "status": "New",
+            "created_at": "2023-05-26T12:00:00",
+            "products": { "1": 2, "2": 1 },
+            "shipping_address_id": address_id.parse::<i32>()?
+        })
+    )
+    .text()
+    .await?;
+
+    // editing the address later does not move the order
+    let response = test_admin_endpoint!(
+        rc.put(URL.to_string() + "/api/customer/address?id=" + &address_id),
+        &token,
+        json!({
+            "id": address_id.parse::<i32>()?,
+            "kind": "shipping",
+            "recipient": "Anna Kowalska",
+            "line1": "ul. Długa 5",
+            "city": "Gdańsk",
+            "postal_code": "80-827",
+            "country": "PL",
+            "is_default": true
+        })
+    );
+    assert_eq!(response.status(), 200);
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(order["shipping_address"]["recipient"], "Jan Kowalski");
+
+    let shipment = json!({ "carrier": "InPost", "tracking_number": "1234", "products": { "1": 1 } });
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/shipment?id=" + &order_id),
+        &admin_token,
+        shipment
+    );
+    assert_eq!(response.status(), 409);
+
+    pay_order(&rc, &token, &order_id).await?;
+    test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/shipment?id=" + &order_id),
+        &admin_token,
+        shipment
+    )
+    .json::<data::models::Shipment>()
+    .await?;
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/shipment?id=" + &order_id),
+        &admin_token,
+        json!({ "carrier": "InPost", "tracking_number": "1235", "products": { "1": 2 } })
+    );
+    assert_eq!(response.status(), 422);
+    let shipment = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/shipment?id=" + &order_id),
+        &admin_token,
+        json!({ "carrier": "DHL", "tracking_number": "5678" })
+    )
+    .json::<data::models::Shipment>()
+    .await?;
+    assert_eq!(shipment.lines.len(), 2);
+
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/order/shipment/delivered?id=" + &shipment.id.to_string()),
+        &admin_token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
+
+    let shipments = test_get_request_auth_endpoint!(
+        rc,
+        &("/api/order/shipments?id=".to_string() + &order_id),
+        &token
+    )
+    .json::<Vec<data::models::Shipment>>()
+    .await?;
+    assert_eq!(shipments.len(), 2);
+    assert!(shipments[1].delivered_at.is_some());
+
+    let order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<data::models::Order>()
+        .await?;
+    assert_eq!(order.status, data::models::order_status::SHIPPED);
+
+    let response = test_admin_endpoint!(
+        rc.delete(URL.to_string() + "/api/customer/address?id=" + &address_id),
+        &token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
 
     Ok(())
 }
@@ -120,6 +2728,7 @@ async fn test_admin_routes_reject_non_authed() -> Result<()> {
     assert!(test_admin_product_routes("").await.is_err());
     assert!(test_admin_customer_routes("").await.is_err());
     assert!(test_admin_order_routes("").await.is_err());
+    assert!(test_admin_discount_routes("").await.is_err());
 
     Ok(())
 }
@@ -144,6 +2753,7 @@ async fn test_admin_routes_authed() -> Result<()> {
     test_admin_product_routes(&token).await?;
     test_admin_customer_routes(&token).await?;
     test_admin_order_routes(&token).await?;
+    test_admin_discount_routes(&token).await?;
 
     Ok(())
 }
@@ -160,7 +2770,10 @@ async fn test_admin_product_routes(token: &str) -> Result<()> {
             {
                 "id": 0,
                 "name": "New product",
-                "price": 600,
+                "price": {
+                    "cents": 600,
+                    "currency": "PLN"
+                },
                 "available": true
             }
         )
@@ -171,21 +2784,24 @@ async fn test_admin_product_routes(token: &str) -> Result<()> {
     dbg!(&response);
     let product_id = &response.text().await?;
     let product =
-        test_get_request_auth_endpoint!(rc, &("/api/product/".to_string() + product_id), token)
+        test_get_request_auth_endpoint!(rc, &("/api/v1/products/".to_string() + product_id), token)
             .json::<data::models::Product>()
             .await?;
     dbg!(&product);
 
-    let endpoint = "/api/admin/product/".to_string() + product_id;
+    let endpoint = "/api/v1/admin/products/".to_string() + product_id;
     println!("\n========\nTesting: PUT {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.put(URL.to_string() + &endpoint),
+        rc.put(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
                 "id": product.id,
                 "name": "New product",
-                "price": 600,
+                "price": {
+                    "cents": 600,
+                    "currency": "PLN"
+                },
                 "available": true
             }
         )
@@ -193,14 +2809,17 @@ async fn test_admin_product_routes(token: &str) -> Result<()> {
     dbg!(&response);
     dbg!(&response.text().await?);
 
-    let endpoint = "/api/admin/product/".to_string() + product_id;
+    let endpoint = "/api/v1/admin/products/".to_string() + product_id;
     println!("\n========\nTesting: PATCH {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.patch(URL.to_string() + &endpoint),
+        rc.patch(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
-                "price": 1000
+                "price": {
+                    "cents": 1000,
+                    "currency": "PLN"
+                }
             }
         )
     );
@@ -232,10 +2851,10 @@ async fn test_admin_customer_routes(token: &str) -> Result<()> {
     dbg!(&response);
     let customer_id = &1.to_string();
 
-    let endpoint = "/api/admin/customer/".to_string() + customer_id;
+    let endpoint = "/api/v1/admin/customers/".to_string() + customer_id;
     println!("\n========\nTesting: PUT {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.put(URL.to_string() + &endpoint),
+        rc.put(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
@@ -248,10 +2867,10 @@ async fn test_admin_customer_routes(token: &str) -> Result<()> {
     dbg!(&response);
     dbg!(&response.text().await?);
 
-    let endpoint = "/api/admin/customer/".to_string() + customer_id;
+    let endpoint = "/api/v1/admin/customers/".to_string() + customer_id;
     println!("\n========\nTesting: PATCH {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.patch(URL.to_string() + &endpoint),
+        rc.patch(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
@@ -289,15 +2908,16 @@ async fn test_admin_order_routes(token: &str) -> Result<()> {
     dbg!(&response);
     let order_id = &response.text().await?;
     dbg!(&order_id);
-    let order = test_get_request_auth_endpoint!(rc, &("/api/order/".to_string() + order_id), token)
-        .json::<data::models::Order>()
-        .await?;
+    let order =
+        test_get_request_auth_endpoint!(rc, &("/api/v1/orders/".to_string() + order_id), token)
+            .json::<data::models::Order>()
+            .await?;
     dbg!(&order);
 
-    let endpoint = "/api/admin/order/".to_string() + order_id;
+    let endpoint = "/api/v1/admin/orders/".to_string() + order_id;
     println!("\n========\nTesting: PUT {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.put(URL.to_string() + &endpoint),
+        rc.put(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
@@ -318,10 +2938,10 @@ async fn test_admin_order_routes(token: &str) -> Result<()> {
     dbg!(&response);
     dbg!(&response.text().await?);
 
-    let endpoint = "/api/admin/order/".to_string() + order_id;
+    let endpoint = "/api/v1/admin/orders/".to_string() + order_id;
     println!("\n========\nTesting: PATCH {}", &endpoint);
     let response = test_admin_endpoint!(
-        rc.patch(URL.to_string() + &endpoint),
+        rc.patch(URL.to_string() + &endpoint).header(IF_MATCH, "*"),
         token,
         json!(
             {
@@ -337,3 +2957,124 @@ async fn test_admin_order_routes(token: &str) -> Result<()> {
 
     Ok(())
 }
+
+async fn test_admin_discount_routes(token: &str) -> Result<()> {
+    let rc = Client::new();
+
+    let endpoint = "/api/admin/discount";
+    println!("\n========\nTesting: POST {endpoint}");
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + endpoint),
+        token,
+        json!(
+            {
+                "id": 0,
+                "code": "TEST-".to_string() + &chrono::Utc::now().timestamp_micros().to_string(),
+                "kind": "percentage",
+                "percent": 50,
+                "amount": null,
+                "valid_from": null,
+                "valid_until": null,
+                "max_uses": 1,
+                "max_uses_per_customer": null,
+                "min_order_value": null,
+                "product_ids": [1]
+            }
+        )
+    );
+    if response.status() != 200 {
+        return Err(eyre!("Failed to create discount code"));
+    }
+    let discount_code_id = response.text().await?;
+    let discount_code = rc
+        .get(URL.to_string() + "/api/admin/discount?id=" + &discount_code_id)
+        .header(AUTHORIZATION, token)
+        .send()
+        .await?
+        .json::<data::models::DiscountCode>()
+        .await?;
+    dbg!(&discount_code);
+
+    let endpoint = "/api/order";
+    println!("\n========\nTesting: POST {endpoint} with discount code");
+    let order = json!(
+        {
+            "id": 0,
+            "customer_id": 1,
+            "status": "New",
+            "created_at": "2023-04-25T08:41:23.104715",
+            "products": {
+                "1": 2,
+                "2": 1
+            },
+            "discount_code": discount_code.code
+        }
+    );
+    let response = rc.post(URL.to_string() + endpoint).json(&order).send().await?;
+    assert_eq!(response.status(), 200);
+    let order_id = response.text().await?;
+    let created_order = rc
+        .get(URL.to_string() + "/api/order?id=" + &order_id)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    dbg!(&created_order);
+    let product_1_total = created_order["lines"]
+        .as_array()
+        .ok_or(eyre!("Order has no lines"))?
+        .iter()
+        .find(|line| line["product_id"] == 1)
+        .ok_or(eyre!("Product 1 missing from order"))?["line_total"]["cents"]
+        .as_i64()
+        .unwrap_or_default();
+    assert_eq!(
+        created_order["discount"]["cents"].as_i64(),
+        Some(product_1_total / 2)
+    );
+
+    println!("\n========\nTesting: POST {endpoint} with exhausted discount code");
+    let response = rc.post(URL.to_string() + endpoint).json(&order).send().await?;
+    assert_eq!(response.status(), 422);
+
+    // cancelled and deleted orders give their use of the code back
+    let response = test_admin_endpoint!(
+        rc.post(format!("{}/api/v1/admin/orders/{}/cancel", *URL, order_id)),
+        token,
+        json!({})
+    );
+    assert_eq!(response.status(), 200);
+    let response = rc.post(URL.to_string() + endpoint).json(&order).send().await?;
+    assert_eq!(response.status(), 200);
+    let order_id = response.text().await?;
+    let response = rc
+        .delete(format!("{}/api/v1/admin/orders/{}", *URL, order_id))
+        .header(AUTHORIZATION, token)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = rc.post(URL.to_string() + endpoint).json(&order).send().await?;
+    assert_eq!(response.status(), 200);
+
+    println!("\n========\nTesting: POST /api/admin/discount with a fixed discount of nothing");
+    let response = test_admin_endpoint!(
+        rc.post(URL.to_string() + "/api/admin/discount"),
+        token,
+        json!({
+            "id": 0,
+            "code": "FREE-".to_string() + &chrono::Utc::now().timestamp_micros().to_string(),
+            "kind": "fixed",
+            "percent": null,
+            "amount": { "cents": 0, "currency": "PLN" },
+            "valid_from": null,
+            "valid_until": null,
+            "max_uses": null,
+            "max_uses_per_customer": null,
+            "min_order_value": null,
+            "product_ids": []
+        })
+    );
+    assert_eq!(response.status(), 400);
+
+    Ok(())
+}
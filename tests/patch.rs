@@ -0,0 +1,159 @@
+use color_eyre::Result;
+use data::models::{json_patch, merge_patch, Customer, Patch, PatchError, PatchOperation};
+use serde_json::{json, Value};
+
+fn operations(value: Value) -> Result<Vec<PatchOperation>> {
+    Ok(serde_json::from_value(value)?)
+}
+
+#[test]
+fn test_merge_patch() {
+    // example from RFC 7396, section 3
+    let mut target = json!({
+        "title": "Goodbye!",
+        "author": { "givenName": "John", "familyName": "Doe" },
+        "tags": ["example", "sample"],
+        "content": "This will be unchanged"
+    });
+    merge_patch(
+        &mut target,
+        &json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": { "familyName": null },
+            "tags": ["example"]
+        }),
+    );
+    assert_eq!(
+        target,
+        json!({
+            "title": "Hello!",
+            "author": { "givenName": "John" },
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        })
+    );
+
+    let mut target = json!(["a"]);
+    merge_patch(&mut target, &json!({ "a": { "b": null, "c": 1 } }));
+    assert_eq!(target, json!({ "a": { "c": 1 } }));
+}
+
+#[test]
+fn test_json_patch() -> Result<()> {
+    let mut document = json!({ "foo": ["bar", "baz"], "a~b": { "c/d": 1 } });
+    json_patch(
+        &mut document,
+        &operations(json!([
+            { "op": "test", "path": "/a~0b/c~1d", "value": 1.0 },
+            { "op": "add", "path": "/foo/1", "value": "qux" },
+            { "op": "add", "path": "/foo/-", "value": "end" },
+            { "op": "remove", "path": "/foo/0" },
+            { "op": "replace", "path": "/a~0b/c~1d", "value": 2 },
+            { "op": "copy", "from": "/foo", "path": "/copied" },
+            { "op": "move", "from": "/copied/2", "path": "/moved" }
+        ]))?,
+    )?;
+    assert_eq!(
+        document,
+        json!({
+            "foo": ["qux", "baz", "end"],
+            "a~b": { "c/d": 2 },
+            "copied": ["qux", "baz"],
+            "moved": "end"
+        })
+    );
+
+    json_patch(
+        &mut document,
+        &operations(json!([{ "op": "add", "path": "", "value": [] }]))?,
+    )?;
+    assert_eq!(document, json!([]));
+
+    Ok(())
+}
+
+#[test]
+fn test_json_patch_errors() -> Result<()> {
+    let original = json!({ "foo": ["bar"], "n": 1 });
+    for (patch, expected) in [
+        (json!([{ "op": "add", "path": "foo", "value": 1 }]), "InvalidPointer"),
+        (json!([{ "op": "add", "path": "/foo/~2", "value": 1 }]), "InvalidPointer"),
+        (json!([{ "op": "add", "path": "/foo/2", "value": 1 }]), "PathNotFound"),
+        (json!([{ "op": "add", "path": "/foo/01", "value": 1 }]), "PathNotFound"),
+        (json!([{ "op": "add", "path": "/n/x", "value": 1 }]), "PathNotFound"),
+        (json!([{ "op": "add", "path": "/missing/x", "value": 1 }]), "PathNotFound"),
+        (json!([{ "op": "remove", "path": "/foo/1" }]), "PathNotFound"),
+        (json!([{ "op": "remove", "path": "" }]), "InvalidPointer"),
+        (json!([{ "op": "replace", "path": "/bar", "value": 1 }]), "PathNotFound"),
+        (json!([{ "op": "move", "from": "/foo", "path": "/foo/0" }]), "InvalidPointer"),
+        (json!([{ "op": "copy", "from": "/foo/-", "path": "/bar" }]), "PathNotFound"),
+        // earlier operations are undone when a later one fails
+        (
+            json!([
+                { "op": "remove", "path": "/n" },
+                { "op": "test", "path": "/foo", "value": ["baz"] }
+            ]),
+            "TestFailed",
+        ),
+    ] {
+        let mut document = original.clone();
+        let error = json_patch(&mut document, &operations(patch.clone())?)
+            .expect_err(&format!("{} should fail", patch));
+        assert!(
+            format!("{:?}", error).starts_with(expected),
+            "{} failed with {:?}",
+            patch,
+            error
+        );
+        assert_eq!(document, original);
+    }
+
+    assert!(operations(json!([{ "op": "frobnicate", "path": "/n" }])).is_err());
+    assert!(operations(json!([{ "op": "add", "path": "/n" }])).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_patch_resource() {
+    let customer = Customer {
+        id: 1,
+        name: "Customer 1".to_string(),
+        address: "Address 1".to_string(),
+        region: "PL".to_string(),
+        deleted_at: None,
+        version: 3,
+    };
+
+    let patched = Patch::Merge(json!({ "name": "Renamed", "version": 10 }))
+        .apply_to(&customer)
+        .expect("patch applies");
+    assert_eq!(patched.name, "Renamed");
+    assert_eq!(patched.address, customer.address);
+    // the version is not read from clients
+    assert_eq!(patched.version, 0);
+
+    let patched = Patch::Json(vec![PatchOperation::Replace {
+        path: "/region".to_string(),
+        value: json!("DE"),
+    }])
+    .apply_to(&customer)
+    .expect("patch applies");
+    assert_eq!(patched.region, "DE");
+
+    for patch in [
+        Patch::Merge(json!({ "name": 5 })),
+        Patch::Merge(json!({ "address": null })),
+        Patch::Merge(json!("not a customer")),
+        Patch::Json(vec![PatchOperation::Remove {
+            path: "/id".to_string(),
+        }]),
+    ] {
+        assert!(matches!(
+            patch.apply_to(&customer),
+            Err(PatchError::InvalidResult(_))
+        ));
+    }
+}